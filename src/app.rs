@@ -1,10 +1,10 @@
 use std::path::PathBuf;
 
 use egui::{mutex::Mutex, Color32};
-use egui_dock::{DockArea, DockState, Style};
+use egui_dock::{DockArea, DockState, NodeIndex, Style, SurfaceIndex, TabIndex};
 use egui_file_dialog::FileDialog;
 
-use crate::engine::{self, python::FRAME_IMAGE, redengine::{GameState, Project}, ui::CentralTabViewer};
+use crate::engine::{self, python::FRAME_IMAGE, redengine::{GameState, Project, ProjectSettings}, scene::SpriteBrowser, state_diff::StateDiffView, frame_history::FrameHistoryView, editor::{Editor, SourceLocation}, profiler::ProfilerView, memory::MemoryView, project_search::ProjectSearchView, debugger::DebugView, search::FileSearch, ui::CentralTabViewer, viewport::ViewportView};
 
 
 
@@ -28,7 +28,7 @@ pub struct TemplateApp { // Attempts to load this state if possible
     #[serde(skip)]
     side_dock_state: DockState<crate::engine::ui::SidePanelTab>,
     // ------------
-    code_editor_content: String,
+    editor: Editor,
     // ------------
     last_opened_file: Option<PathBuf>,
     project: Project,
@@ -37,6 +37,31 @@ pub struct TemplateApp { // Attempts to load this state if possible
     #[serde(skip)]
     viewport_texture: Option<egui::TextureHandle>,
     #[serde(skip)]
+    viewport_view: ViewportView,
+    #[serde(skip)]
+    viewport_detached: bool,
+    /// Where the Viewport tab sat in the dock before it was popped out.
+    #[serde(skip)]
+    viewport_dock_position: Option<(SurfaceIndex, NodeIndex, TabIndex)>,
+    #[serde(skip)]
+    sprite_browser: SpriteBrowser,
+    #[serde(skip)]
+    state_diff: StateDiffView,
+    #[serde(skip)]
+    frame_history: FrameHistoryView,
+    #[serde(skip)]
+    profiler: ProfilerView,
+    #[serde(skip)]
+    memory: MemoryView,
+    #[serde(skip)]
+    project_search: ProjectSearchView,
+    #[serde(skip)]
+    debug_view: DebugView,
+    #[serde(skip)]
+    file_search: FileSearch,
+    #[serde(skip)]
+    goto_source: Option<SourceLocation>,
+    #[serde(skip)]
     game_state: GameState
 }   
 
@@ -45,10 +70,10 @@ impl Default for TemplateApp { // Fallback State
         Self {
             file_dialog: FileDialog::new(), 
             // ------------
-            central_dock_state: DockState::new(vec![engine::ui::CentralPanelTab::Viewport, engine::ui::CentralPanelTab::Scripting]),
-            side_dock_state: DockState::new(vec![engine::ui::SidePanelTab::FileExplorer]),
+            central_dock_state: DockState::new(vec![engine::ui::CentralPanelTab::Viewport, engine::ui::CentralPanelTab::Scripting, engine::ui::CentralPanelTab::Tests, engine::ui::CentralPanelTab::StateDiff, engine::ui::CentralPanelTab::Profiler, engine::ui::CentralPanelTab::Memory, engine::ui::CentralPanelTab::Search, engine::ui::CentralPanelTab::Problems]),
+            side_dock_state: DockState::new(vec![engine::ui::SidePanelTab::FileExplorer, engine::ui::SidePanelTab::Sprites, engine::ui::SidePanelTab::Debug, engine::ui::SidePanelTab::Outline]),
             // ------------
-            code_editor_content: "# Your code".into(),
+            editor: Editor::default(),
             // ------------
             last_opened_file: None,
             project: Project::new(),
@@ -56,7 +81,19 @@ impl Default for TemplateApp { // Fallback State
             resource_search_term: "".to_owned(),
             // ------------
             viewport_texture: None,
-            game_state: GameState { running: false, size: [1280, 720]}
+            viewport_view: ViewportView::default(),
+            viewport_detached: false,
+            viewport_dock_position: None,
+            sprite_browser: SpriteBrowser::default(),
+            state_diff: StateDiffView::default(),
+            frame_history: FrameHistoryView::default(),
+            profiler: ProfilerView::default(),
+            memory: MemoryView::default(),
+            project_search: ProjectSearchView::default(),
+            debug_view: DebugView::default(),
+            file_search: FileSearch::default(),
+            goto_source: None,
+            game_state: GameState { running: false, size: [1280, 720], record_frames: 120, state_slot: 1, launch_error: None }
         }
     }
 }
@@ -75,11 +112,104 @@ impl TemplateApp {
 
 
         // Load state or revert to default.
-        if let Some(storage) = cc.storage {
+        let mut app: Self = if let Some(storage) = cc.storage {
             eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
 
         } else {
             Default::default()
+        };
+        app.editor.restore();
+        app
+    }
+
+    fn central_tab_viewer<'a>(&'a mut self, ctx: &'a egui::Context) -> CentralTabViewer<'a> {
+        CentralTabViewer {
+            viewport_texture: &mut self.viewport_texture,
+            viewport_view: &mut self.viewport_view,
+            viewport_detached: &mut self.viewport_detached,
+            sprite_browser: &mut self.sprite_browser,
+            state_diff: &mut self.state_diff,
+            frame_history: &mut self.frame_history,
+            profiler: &mut self.profiler,
+            memory: &mut self.memory,
+            project_search: &mut self.project_search,
+            goto_source: &mut self.goto_source,
+            editor: &mut self.editor,
+            game_state: &mut self.game_state,
+            project: &mut self.project,
+            egui_ctx: ctx,
+        }
+    }
+
+    /// Uploads the latest frame to the viewport texture, unless it already shows it.
+    fn update_viewport_texture(&mut self, ctx: &egui::Context) {
+        let Some(lock) = FRAME_IMAGE.get() else {
+            return;
+        };
+        let guard = lock.lock();
+        let serial = engine::python::FRAME_SERIAL.load(std::sync::atomic::Ordering::Relaxed);
+        let changed = self.viewport_view.needs_upload(serial, self.project.settings.texture_filter);
+        let Some(image) = guard.as_ref().filter(|_| changed || self.viewport_texture.is_none()) else {
+            return;
+        };
+
+        let image = self.viewport_view.isolate(image).into_owned();
+        let texture_options = self.project.settings.texture_filter.texture_options();
+        if let Some(vp_texture) = &mut self.viewport_texture {
+            vp_texture.set(image, texture_options);
+        } else {
+            self.viewport_texture = Some(ctx.load_texture("viewport_texture", image, texture_options));
+        }
+    }
+
+    /// Puts the Viewport tab back where it was before it was popped out,
+    /// or into the focused leaf if the dock has changed since.
+    fn dock_viewport(&mut self) {
+        let restored = self.viewport_dock_position.take().and_then(|(surface, node, index)| {
+            let tree = self.central_dock_state.get_surface_mut(surface)?.node_tree_mut()?;
+            if node.0 >= tree.len() || !tree[node].is_leaf() {
+                return None;
+            }
+            let index = TabIndex(index.0.min(tree[node].tabs_count()));
+            tree[node].insert_tab(index, engine::ui::CentralPanelTab::Viewport);
+            Some((surface, node, index))
+        });
+
+        match restored {
+            Some(position) => self.central_dock_state.set_active_tab(position),
+            None => self.central_dock_state.push_to_focused_leaf(engine::ui::CentralPanelTab::Viewport),
+        }
+    }
+
+    /// Moves the Viewport tab out of the dock while it is popped out into its own window, and
+    /// shows that window. The tab returns to the dock once the window is closed.
+    fn show_detached_viewport(&mut self, ctx: &egui::Context) {
+        let viewport_tab = self.central_dock_state.find_tab(&engine::ui::CentralPanelTab::Viewport);
+        if let (true, Some(position)) = (self.viewport_detached, viewport_tab) {
+            self.central_dock_state.remove_tab(position);
+            self.viewport_dock_position = Some(position);
+        } else if !self.viewport_detached && viewport_tab.is_none() {
+            self.dock_viewport();
+        }
+
+        if self.viewport_detached {
+            ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of("game_viewport"),
+                egui::ViewportBuilder::default()
+                    .with_title("RedEngine Viewport")
+                    .with_inner_size([960.0, 580.0]),
+                |ctx, _class| {
+                    egui::CentralPanel::default().show(ctx, |ui| {
+                        let mut viewer = self.central_tab_viewer(ctx);
+                        viewer.draw_viewport(ui);
+                    });
+
+                    // Closing the window docks the viewport back into the editor.
+                    if ctx.input(|i| i.viewport().close_requested()) {
+                        self.viewport_detached = false;
+                    }
+                },
+            );
         }
     }
 }
@@ -88,6 +218,7 @@ impl eframe::App for TemplateApp {
     /// Called by the framework to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         self.project.loaded = false;
+        self.editor.remember_open_files();
         eframe::set_value(storage, eframe::APP_KEY, self);
     }
 
@@ -110,6 +241,7 @@ impl eframe::App for TemplateApp {
             // } 
         
             self.project.root_item = Project::load_files(proj_dir.clone());
+            self.project.settings = ProjectSettings::load(&proj_dir);
             self.project.loaded = true;
 
             println!("{}", self.project)
@@ -119,6 +251,7 @@ impl eframe::App for TemplateApp {
                 if let Some(proj_dir) = &self.project.project_path {
                     
                     self.project.root_item = Project::load_files(proj_dir.clone());
+                    self.project.settings = ProjectSettings::load(proj_dir);
                     self.project.loaded = true;
 
                     let dir_str = proj_dir.display().to_string();
@@ -128,6 +261,19 @@ impl eframe::App for TemplateApp {
             }
         }
 
+        self.sprite_browser.update(ctx.input(|i| i.time), self.game_state.running);
+        self.memory.update(ctx.input(|i| i.time), self.game_state.running);
+        self.editor.update(ctx, self.project.project_path.as_deref());
+        self.editor.quit_prompt(ctx);
+
+        // Show where the game stopped when it hits a breakpoint or finishes a step.
+        if let Some(location) = self.debug_view.update(ctx) {
+            self.goto_source = Some(location);
+            if let Some(tab) = self.side_dock_state.find_tab(&engine::ui::SidePanelTab::Debug) {
+                self.side_dock_state.set_active_tab(tab);
+            }
+        }
+
         egui::TopBottomPanel::top("Program Menu")
             .show(ctx, |ui| {
                 egui::MenuBar::new().ui(ui, |ui| {
@@ -169,7 +315,12 @@ impl eframe::App for TemplateApp {
 
                 let mut viewer = engine::ui::SideTabViewer {
                     project: &mut self.project,
-                    code_editor_content: &mut self.code_editor_content,
+                    sprite_browser: &mut self.sprite_browser,
+                    debug_view: &mut self.debug_view,
+                    goto_source: &mut self.goto_source,
+                    editor: &mut self.editor,
+                    search_term: &mut self.resource_search_term,
+                    file_search: &mut self.file_search,
                     egui_ctx: ctx,
                 }; let mut style = Style::from_egui(ui.style());
                 style.tab_bar.bg_fill = Color32::from_gray(22);   
@@ -185,43 +336,38 @@ impl eframe::App for TemplateApp {
         
         egui::CentralPanel::default()
             .show(ctx, |ui| {
-                if self.game_state.running {  // Handle, Assign and Update the viewport texture
-                    if let Some(lock) = FRAME_IMAGE.get() {
-                        let guard = lock.lock();
-                        if let Some(image) = &*guard {
-                            if let Some(vp_texture) = &mut self.viewport_texture {
-                                // If it exists and is assigned
-                                vp_texture.set(image.clone(), Default::default());
-
-                            } else {
-                                self.viewport_texture = Some(ctx.load_texture(
-                                    "viewport_texture",
-                                    image.clone(),
-                                    Default::default(),
-                                ));
-                            }
-                        }   
-                    }
+                if self.game_state.running {
+                    self.update_viewport_texture(ctx);
                 }
 
-                let mut viewer = CentralTabViewer {
-                    // code_editor: &mut self.code_editor,
-                    viewport_texture: &mut self.viewport_texture,
-                    code_editor_content: &mut self.code_editor_content,
-                    game_state: &mut self.game_state,
-                    egui_ctx: ctx,
-                }; let mut style = Style::from_egui(ui.style());
+                // The viewer borrows the whole app, so the dock state is moved out while it is shown.
+                let mut dock_state = std::mem::replace(&mut self.central_dock_state, DockState::new(Vec::new()));
+                let mut viewer = self.central_tab_viewer(ctx);
+                let mut style = Style::from_egui(ui.style());
                 style.tab_bar.bg_fill = Color32::from_gray(22);   
 
-                DockArea::new(&mut self.central_dock_state)
+                DockArea::new(&mut dock_state)
                     .style(style)
                     .show_close_buttons(false)
                     .show_leaf_collapse_buttons(false)
                     .show_leaf_close_all_buttons(false)
                     .show_inside(ui, &mut viewer);
+                self.central_dock_state = dock_state;
+
+                // Bring the editor forward when a file was opened or another tab asked to show a line.
+                if let Some(location) = self.goto_source.take() {
+                    self.editor.goto(&location);
+                }
+                if self.editor.take_focus_request() {
+                    if let Some(tab) = self.central_dock_state.find_tab(&engine::ui::CentralPanelTab::Scripting) {
+                        self.central_dock_state.set_active_tab(tab);
+                    }
+                }
     
         });
 
+        self.show_detached_viewport(ctx);
+
         // Misc
         self.file_dialog.update(ctx);
 
@@ -0,0 +1,232 @@
+use std::path::PathBuf;
+
+use crate::engine::{golden, headless::{self, HeadlessOptions}, testing::{self, TestOutcome}};
+
+
+const USAGE: &str = "Usage:
+    redengine_rust run <script> [--frames N] [--out DIR] [--every N]
+    redengine_rust golden <project> [--update] [--tolerance N]
+    redengine_rust test <project> [--junit FILE]";
+
+/// Parses the value of `flag` as a whole number no smaller than `min`.
+fn parse_count(flag: &str, value: Option<&String>, min: usize) -> Result<usize, String> {
+    let count: usize = value
+        .ok_or_else(|| format!("{flag} expects a value"))?
+        .parse()
+        .map_err(|e| format!("{flag} expects a whole number ({e})"))?;
+    if count < min {
+        return Err(format!("{flag} must be at least {min}"));
+    }
+    Ok(count)
+}
+
+fn parse_run(args: &[String]) -> Result<HeadlessOptions, String> {
+    let mut options = HeadlessOptions::default();
+    let mut script = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => options.frames = parse_count(arg, args.next(), 0)?,
+            "--every" => options.every = parse_count(arg, args.next(), 1)?,
+            "--out" => {
+                let dir = args.next().ok_or("--out expects a directory")?;
+                options.out = Some(PathBuf::from(dir));
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {flag}")),
+            extra if script.is_some() => return Err(format!("Unexpected argument {extra}")),
+            path => script = Some(PathBuf::from(path)),
+        }
+    }
+
+    options.script = script.ok_or("Missing script path")?;
+    Ok(options)
+}
+
+fn usage_error(message: &str) -> i32 {
+    eprintln!("{message}");
+    eprintln!("{USAGE}");
+    2
+}
+
+struct GoldenOptions {
+    project: PathBuf,
+    update: bool,
+    tolerance: Option<u8>,
+}
+
+fn parse_golden(args: &[String]) -> Result<GoldenOptions, String> {
+    let mut project = None;
+    let mut update = false;
+    let mut tolerance = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--update" => update = true,
+            "--tolerance" => {
+                let value = parse_count(arg, args.next(), 0)?;
+                tolerance = Some(u8::try_from(value).map_err(|e| format!("--tolerance must be at most 255 ({e})"))?);
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {flag}")),
+            path => project = Some(PathBuf::from(path)),
+        }
+    }
+
+    Ok(GoldenOptions {
+        project: project.ok_or("Missing project path")?,
+        update,
+        tolerance,
+    })
+}
+
+struct TestOptions {
+    project: PathBuf,
+    junit: Option<PathBuf>,
+}
+
+fn parse_test(args: &[String]) -> Result<TestOptions, String> {
+    let mut project = None;
+    let mut junit = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--junit" => {
+                let file = args.next().ok_or("--junit expects a file")?;
+                junit = Some(PathBuf::from(file));
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {flag}")),
+            path => project = Some(PathBuf::from(path)),
+        }
+    }
+
+    Ok(TestOptions {
+        project: project.ok_or("Missing project path")?,
+        junit,
+    })
+}
+
+fn run_tests(options: &TestOptions) -> Result<(), String> {
+    let mut results = vec![];
+    testing::run_tests(&options.project, |result| {
+        let status = match result.outcome {
+            TestOutcome::Passed => "ok",
+            TestOutcome::Failed => "FAILED",
+            TestOutcome::Errored => "ERROR",
+        };
+        println!("{} {} ... {status}", result.file.display(), result.name);
+        if !result.message.is_empty() {
+            println!("{}", result.message);
+        }
+        results.push(result);
+    });
+
+    if let Some(path) = &options.junit {
+        std::fs::write(path, testing::to_junit_xml(&options.project, &results))
+            .map_err(|e| format!("Could not write {}: {e}", path.display()))?;
+    }
+
+    let passed = results.iter().filter(|r| r.outcome == TestOutcome::Passed).count();
+    println!("{passed} of {} tests passed", results.len());
+
+    if results.is_empty() {
+        Err(format!("No tests found in {}", options.project.join(testing::TESTS_DIR).display()))
+    } else if passed < results.len() {
+        Err(format!("{} tests failed", results.len() - passed))
+    } else {
+        Ok(())
+    }
+}
+
+/// Lets pygame open its window on Linux boxes without a display.
+fn use_dummy_video_driver() {
+    if std::env::var_os("SDL_VIDEODRIVER").is_none() {
+        // SAFETY: Called before the interpreter or any other thread has started.
+        unsafe { std::env::set_var("SDL_VIDEODRIVER", "dummy") };
+    }
+}
+
+/// Handles command-line subcommands. Returns `None` when the editor should be opened instead,
+/// otherwise the process exit code.
+pub fn dispatch(args: &[String]) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+
+    let result = match command.as_str() {
+        "run" => {
+            let options = match parse_run(rest) {
+                Ok(options) => options,
+                Err(e) => return Some(usage_error(&e)),
+            };
+            use_dummy_video_driver();
+            headless::run(&options).map(|frames| {
+                println!("Ran {frames} frames of {}", options.script.display());
+            })
+        }
+        "golden" => {
+            let options = match parse_golden(rest) {
+                Ok(options) => options,
+                Err(e) => return Some(usage_error(&e)),
+            };
+            use_dummy_video_driver();
+            let result = if options.update {
+                golden::update(&options.project)
+            } else {
+                golden::check(&options.project, options.tolerance)
+            };
+            result.map(|summary| println!("{summary}"))
+        }
+        "test" => {
+            let options = match parse_test(rest) {
+                Ok(options) => options,
+                Err(e) => return Some(usage_error(&e)),
+            };
+            use_dummy_video_driver();
+            run_tests(&options)
+        }
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => return None,
+    };
+
+    match result {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("{e}");
+            Some(1)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::parse_run;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&arg| arg.to_owned()).collect()
+    }
+
+    #[test]
+    fn parses_run_options() {
+        let options = parse_run(&args(&["game.py", "--frames", "30", "--every", "5", "--out", "frames"])).unwrap();
+        assert_eq!(options.script, Path::new("game.py"));
+        assert_eq!((options.frames, options.every), (30, 5));
+        assert_eq!(options.out.as_deref(), Some(Path::new("frames")));
+    }
+
+    #[test]
+    fn rejects_a_second_script() {
+        assert_eq!(parse_run(&args(&["game.py", "other.py"])).err().as_deref(), Some("Unexpected argument other.py"));
+    }
+
+    #[test]
+    fn rejects_saving_every_zeroth_frame() {
+        assert_eq!(parse_run(&args(&["game.py", "--every", "0"])).err().as_deref(), Some("--every must be at least 1"));
+        assert!(parse_run(&args(&["game.py", "--frames", "0"])).is_ok());
+    }
+}
@@ -0,0 +1,176 @@
+use std::{fs, path::{Path, PathBuf}, sync::{atomic::{AtomicUsize, Ordering}, mpsc, Arc}, time::Duration};
+
+use egui::ColorImage;
+use image::{codecs::gif::{GifEncoder, Repeat}, Delay, Frame};
+
+use crate::engine::helpers;
+
+
+/// Folder inside a project that screenshots and recordings are saved to.
+pub const CAPTURES_DIR: &str = "captures";
+
+/// Games are expected to tick at 60 FPS, so recordings play back at that rate.
+const FRAME_DELAY: Duration = Duration::from_micros(16_667);
+/// Frames waiting for the encoder at most. Frames arriving while the queue is full are dropped
+/// rather than buffered, so a slow encoder cannot use up memory.
+const QUEUED_FRAMES: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    Gif,
+    PngSequence,
+}
+
+struct Recording {
+    format: CaptureFormat,
+    sender: mpsc::SyncSender<ColorImage>,
+    recorded: usize,
+    /// Frames dropped because the encoder fell behind.
+    dropped: Arc<AtomicUsize>,
+    limit: Option<usize>,
+}
+
+lazy_static::lazy_static! {
+    static ref RECORDING: std::sync::Mutex<Option<Recording>> = std::sync::Mutex::new(None);
+    /// Outcome of the last screenshot or recording, shown in the viewport toolbar.
+    pub static ref CAPTURE_STATUS: std::sync::Mutex<String> = std::sync::Mutex::new(String::new());
+}
+
+
+/// Returns the captures folder for `project`, falling back to the working directory.
+pub fn captures_dir(project: Option<&Path>) -> PathBuf {
+    project.map(|p| p.join(CAPTURES_DIR)).unwrap_or_else(|| PathBuf::from(CAPTURES_DIR))
+}
+
+/// First `<prefix>_NNNN<suffix>` path in `dir` that does not exist yet.
+fn next_free_path(dir: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    (1..)
+        .map(|i| dir.join(format!("{prefix}_{i:04}{suffix}")))
+        .find(|path| !path.exists())
+        .expect("Ran out of capture names")
+}
+
+fn set_status(message: String) {
+    *CAPTURE_STATUS.lock().unwrap() = message;
+}
+
+/// Saves `frame` as a PNG at the game's native resolution.
+pub fn save_screenshot(dir: &Path, frame: &ColorImage) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Could not create {}: {e}", dir.display()))?;
+    let path = next_free_path(dir, "screenshot", ".png");
+
+    helpers::to_rgba_image(frame)
+        .save(&path)
+        .map_err(|e| format!("Could not write {}: {e}", path.display()))?;
+    Ok(path)
+}
+
+/// Saves the frame currently shown in the viewport and reports the result in `CAPTURE_STATUS`.
+pub fn screenshot(dir: &Path, frame: &ColorImage) {
+    match save_screenshot(dir, frame) {
+        Ok(path) => set_status(format!("Saved {}", path.display())),
+        Err(e) => set_status(e),
+    }
+}
+
+fn write_gif(path: &Path, frames: &mpsc::Receiver<ColorImage>) -> Result<usize, String> {
+    let file = fs::File::create(path).map_err(|e| format!("Could not create {}: {e}", path.display()))?;
+    let mut encoder = GifEncoder::new_with_speed(file, 10);
+    encoder.set_repeat(Repeat::Infinite).map_err(|e| e.to_string())?;
+
+    let mut written = 0;
+    for frame in frames {
+        let delay = Delay::from_saturating_duration(FRAME_DELAY);
+        encoder
+            .encode_frame(Frame::from_parts(helpers::to_rgba_image(&frame), 0, 0, delay))
+            .map_err(|e| format!("Could not encode {}: {e}", path.display()))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+fn write_png_sequence(dir: &Path, frames: &mpsc::Receiver<ColorImage>) -> Result<usize, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Could not create {}: {e}", dir.display()))?;
+
+    let mut written = 0;
+    for frame in frames {
+        written += 1;
+        let path = dir.join(format!("frame_{written:05}.png"));
+        helpers::to_rgba_image(&frame)
+            .save(&path)
+            .map_err(|e| format!("Could not write {}: {e}", path.display()))?;
+    }
+    Ok(written)
+}
+
+/// Starts recording frames from the runner. Frames are encoded on a background thread, and the
+/// recording stops after `limit` frames or when `stop_recording` is called.
+pub fn start_recording(dir: &Path, format: CaptureFormat, limit: Option<usize>) {
+    if let Err(e) = fs::create_dir_all(dir) {
+        set_status(format!("Could not create {}: {e}", dir.display()));
+        return;
+    }
+
+    let (sender, receiver) = mpsc::sync_channel(QUEUED_FRAMES);
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let encoder_dropped = dropped.clone();
+    let path = match format {
+        CaptureFormat::Gif => next_free_path(dir, "recording", ".gif"),
+        CaptureFormat::PngSequence => next_free_path(dir, "recording", ""),
+    };
+
+    set_status("Recording...".to_owned());
+    std::thread::spawn(move || {
+        let result = match format {
+            CaptureFormat::Gif => write_gif(&path, &receiver),
+            CaptureFormat::PngSequence => write_png_sequence(&path, &receiver),
+        };
+        match (result, encoder_dropped.load(Ordering::Relaxed)) {
+            (Ok(frames), 0) => set_status(format!("Saved {frames} frames to {}", path.display())),
+            (Ok(frames), dropped) => set_status(format!("Saved {frames} frames to {} ({dropped} dropped while encoding fell behind)", path.display())),
+            (Err(e), _) => set_status(e),
+        }
+    });
+
+    *RECORDING.lock().unwrap() = Some(Recording { format, sender, recorded: 0, dropped, limit });
+}
+
+/// Stops the current recording; the encoder thread finishes writing in the background.
+pub fn stop_recording() {
+    let mut recording = RECORDING.lock().unwrap();
+    if recording.is_some() {
+        // Set before the sender is dropped so it cannot overwrite the encoder's final status.
+        set_status("Encoding...".to_owned());
+        *recording = None;
+    }
+}
+
+/// The format and number of frames captured so far, if a recording is in progress.
+pub fn recording_progress() -> Option<(CaptureFormat, usize, Option<usize>)> {
+    RECORDING.lock().unwrap().as_ref().map(|r| (r.format, r.recorded, r.limit))
+}
+
+/// Called by the runner for every rendered frame.
+pub fn record_frame(frame: &ColorImage) {
+    let mut recording = RECORDING.lock().unwrap();
+    let Some(active) = recording.as_mut() else {
+        return;
+    };
+
+    let finished = match active.sender.try_send(frame.clone()) {
+        Ok(()) => {
+            active.recorded += 1;
+            active.limit.is_some_and(|limit| active.recorded >= limit)
+        }
+        Err(mpsc::TrySendError::Full(_)) => {
+            active.dropped.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+        Err(mpsc::TrySendError::Disconnected(_)) => true,
+    };
+
+    if finished {
+        set_status("Encoding...".to_owned());
+        *recording = None;
+    }
+}
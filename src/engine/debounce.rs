@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use pyo3::prelude::*;
+
+
+/// How long a buffer has to stay unchanged before it is parsed.
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+
+/// One long-lived thread parsing editor buffers with Python in the background. Each buffer is
+/// parsed once no newer text was sent for its key within `DEBOUNCE`, so typing only parses
+/// the text the user stopped at.
+pub struct DebouncedParser<K> {
+    sender: mpsc::Sender<(K, String)>,
+}
+
+impl<K: Ord + Send + 'static> DebouncedParser<K> {
+    /// Starts the worker. `parse` gets the key and the settled text; as a newer text may have
+    /// been sent meanwhile, it should check that the text is still current before storing results.
+    pub fn spawn(parse: impl Fn(Python<'_>, &K, &str) + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel::<(K, String)>();
+
+        std::thread::spawn(move || {
+            let mut pending: BTreeMap<K, (String, Instant)> = BTreeMap::new();
+            loop {
+                // Wait for the next buffer, or until the earliest pending one settles.
+                let settles = pending.values().map(|(_, sent)| *sent + DEBOUNCE).min();
+                let received = match settles {
+                    Some(settles) => receiver.recv_timeout(settles.saturating_duration_since(Instant::now())),
+                    None => receiver.recv().map_err(RecvTimeoutError::from),
+                };
+                match received {
+                    Ok((key, text)) => {
+                        pending.insert(key, (text, Instant::now()));
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return,
+                    Err(RecvTimeoutError::Timeout) => {}
+                }
+
+                let now = Instant::now();
+                let (settled, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut pending)
+                    .into_iter()
+                    .partition(|(_, (_, sent))| *sent + DEBOUNCE <= now);
+                pending = waiting.into_iter().collect();
+                if settled.is_empty() {
+                    continue;
+                }
+
+                pyo3::prepare_freethreaded_python();
+                Python::with_gil(|py| {
+                    for (key, (text, _)) in &settled {
+                        parse(py, key, text);
+                    }
+                });
+            }
+        });
+
+        Self { sender }
+    }
+
+    /// Queues `text` for parsing, replacing any text for `key` that has not settled yet.
+    pub fn send(&self, key: K, text: String) {
+        self.sender.send((key, text)).ok();
+    }
+}
@@ -0,0 +1,167 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use egui::{pos2, Align2, Color32, FontId, Painter, Pos2, Rect, Stroke};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::engine::{python, viewport::ViewportTransform};
+
+
+const DEFAULT_COLOR: Color32 = Color32::from_rgb(255, 0, 255);
+
+/// Shapes are only collected while the editor shows the overlay, so headless runs and
+/// games with the overlay hidden never pay for them.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    /// Shapes submitted while the current frame is being stepped.
+    static ref PENDING: std::sync::Mutex<Vec<DebugShape>> = std::sync::Mutex::new(Vec::new());
+    /// Shapes from the last finished frame, painted over the viewport.
+    static ref FRAME_SHAPES: std::sync::Mutex<Vec<DebugShape>> = std::sync::Mutex::new(Vec::new());
+}
+
+
+/// A shape in game coordinates, drawn by the editor on top of the viewport image.
+/// Shapes are never rendered into the game's own framebuffer.
+#[derive(Clone)]
+pub enum DebugShape {
+    Line { start: Pos2, end: Pos2, color: Color32, width: f32 },
+    Rect { rect: Rect, color: Color32, width: f32, filled: bool },
+    Circle { center: Pos2, radius: f32, color: Color32, width: f32, filled: bool },
+    Text { pos: Pos2, text: String, color: Color32, size: f32 },
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Publishes the shapes submitted during the frame that just finished. Called by the runner.
+pub fn end_frame() {
+    let shapes = std::mem::take(&mut *PENDING.lock().unwrap());
+    *FRAME_SHAPES.lock().unwrap() = shapes;
+}
+
+pub fn clear() {
+    PENDING.lock().unwrap().clear();
+    FRAME_SHAPES.lock().unwrap().clear();
+}
+
+fn submit(shape: DebugShape) {
+    if ENABLED.load(Ordering::Relaxed) {
+        PENDING.lock().unwrap().push(shape);
+    }
+}
+
+/// Paints the last frame's shapes, mapped through the viewport's transform.
+pub fn paint(painter: &Painter, transform: &ViewportTransform) {
+    let scale = transform.scale().x;
+
+    for shape in FRAME_SHAPES.lock().unwrap().iter() {
+        match shape {
+            DebugShape::Line { start, end, color, width } => {
+                painter.line_segment([transform.to_screen(*start), transform.to_screen(*end)], Stroke::new(*width, *color));
+            }
+            DebugShape::Rect { rect, color, width, filled } => {
+                let rect = Rect::from_min_max(transform.to_screen(rect.min), transform.to_screen(rect.max));
+                if *filled {
+                    painter.rect_filled(rect, 0.0, *color);
+                } else {
+                    painter.rect_stroke(rect, 0.0, Stroke::new(*width, *color), egui::StrokeKind::Middle);
+                }
+            }
+            DebugShape::Circle { center, radius, color, width, filled } => {
+                let center = transform.to_screen(*center);
+                if *filled {
+                    painter.circle_filled(center, radius * scale, *color);
+                } else {
+                    painter.circle_stroke(center, radius * scale, Stroke::new(*width, *color));
+                }
+            }
+            DebugShape::Text { pos, text, color, size } => {
+                painter.text(transform.to_screen(*pos), Align2::LEFT_TOP, text, FontId::monospace(*size), *color);
+            }
+        }
+    }
+}
+
+
+// ---- `redengine.debug` ------------------------------------------------------
+
+fn floats<const N: usize>(value: &Bound<'_, PyAny>) -> PyResult<[f32; N]> {
+    let values: Vec<f32> = value.extract()?;
+    let count = values.len();
+    values
+        .try_into()
+        .map_err(|_| PyValueError::new_err(format!("expected {N} numbers, got {count}")))
+}
+
+fn point(value: &Bound<'_, PyAny>) -> PyResult<Pos2> {
+    let [x, y] = floats(value)?;
+    Ok(pos2(x, y))
+}
+
+/// Accepts `(r, g, b)`, `(r, g, b, a)` or a `pygame.Color`.
+fn color(value: Option<&Bound<'_, PyAny>>) -> PyResult<Color32> {
+    let Some(value) = value else {
+        return Ok(DEFAULT_COLOR);
+    };
+
+    match value.extract::<Vec<u8>>()?[..] {
+        [r, g, b] => Ok(Color32::from_rgb(r, g, b)),
+        [r, g, b, a] => Ok(Color32::from_rgba_unmultiplied(r, g, b, a)),
+        _ => Err(PyValueError::new_err("colours need 3 or 4 components")),
+    }
+}
+
+// pyo3 0.21 unwraps required reference arguments inside an `unsafe fn` without an unsafe block,
+// which edition 2024 reports, so the drawing functions are kept together here.
+#[expect(unsafe_op_in_unsafe_fn, reason = "generated by pyo3's argument extraction")]
+mod draw {
+    use egui::{pos2, Rect};
+    use pyo3::prelude::*;
+
+    use super::{floats, point, submit, DebugShape};
+
+    #[pyfunction]
+    #[pyo3(signature = (start, end, color = None, width = 1.0))]
+    pub fn line(start: &Bound<'_, PyAny>, end: &Bound<'_, PyAny>, color: Option<&Bound<'_, PyAny>>, width: f32) -> PyResult<()> {
+        submit(DebugShape::Line { start: point(start)?, end: point(end)?, color: super::color(color)?, width });
+        Ok(())
+    }
+
+    /// `rect` is `(x, y, w, h)` or a `pygame.Rect`.
+    #[pyfunction]
+    #[pyo3(signature = (rect, color = None, width = 1.0, filled = false))]
+    pub fn rect(rect: &Bound<'_, PyAny>, color: Option<&Bound<'_, PyAny>>, width: f32, filled: bool) -> PyResult<()> {
+        let [x, y, w, h] = floats(rect)?;
+        let rect = Rect::from_min_size(pos2(x, y), egui::vec2(w, h));
+        submit(DebugShape::Rect { rect, color: super::color(color)?, width, filled });
+        Ok(())
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (center, radius, color = None, width = 1.0, filled = false))]
+    pub fn circle(center: &Bound<'_, PyAny>, radius: f32, color: Option<&Bound<'_, PyAny>>, width: f32, filled: bool) -> PyResult<()> {
+        submit(DebugShape::Circle { center: point(center)?, radius, color: super::color(color)?, width, filled });
+        Ok(())
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (pos, text, color = None, size = 14.0))]
+    pub fn text(pos: &Bound<'_, PyAny>, text: &Bound<'_, PyAny>, color: Option<&Bound<'_, PyAny>>, size: f32) -> PyResult<()> {
+        submit(DebugShape::Text { pos: point(pos)?, text: text.str()?.to_string(), color: super::color(color)?, size });
+        Ok(())
+    }
+}
+
+/// Registers `redengine.debug`. Calls are no-ops unless the editor's overlay is showing.
+pub fn install_module(py: Python<'_>) -> PyResult<()> {
+    clear();
+
+    let module = PyModule::new_bound(py, "redengine.debug")?;
+    module.add_function(wrap_pyfunction_bound!(draw::line, &module)?)?;
+    module.add_function(wrap_pyfunction_bound!(draw::rect, &module)?)?;
+    module.add_function(wrap_pyfunction_bound!(draw::circle, &module)?)?;
+    module.add_function(wrap_pyfunction_bound!(draw::text, &module)?)?;
+    python::register_submodule(py, "debug", &module)
+}
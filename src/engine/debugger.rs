@@ -0,0 +1,428 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use egui::{Context, Key, Ui};
+use pyo3::prelude::*;
+
+use crate::engine::editor::SourceLocation;
+use crate::engine::{helpers::lock, python};
+
+
+/// Line tracer installed with `sys.settrace` on the game thread. Only frames from files with
+/// breakpoints are traced until the user steps, when every frame is traced so `into` can stop
+/// anywhere. `pause` is provided by the editor and blocks until the user picks a command.
+const DEBUGGER_MODULE: &str = r#"
+import reprlib
+import sys
+
+_repr = reprlib.Repr()
+_repr.maxstring = 200
+_repr.maxother = 200
+
+
+def _safe_repr(value):
+    try:
+        return _repr.repr(value)
+    except Exception as e:
+        return f"<repr failed: {type(e).__name__}>"
+
+
+class Debugger:
+    def __init__(self):
+        self.reset({})
+
+    def reset(self, breakpoints):
+        self.mode = None
+        self.stop_frame = None
+        self.set_breakpoints(breakpoints)
+
+    def set_breakpoints(self, breakpoints):
+        self.breakpoints = {file: set(lines) for file, lines in breakpoints.items() if lines}
+        sys.settrace(self.trace_call if self.breakpoints or self.mode else None)
+
+    def trace_call(self, frame, event, arg):
+        if self.mode is not None or frame.f_code.co_filename in self.breakpoints:
+            return self.trace_line
+        return None
+
+    def trace_line(self, frame, event, arg):
+        if event == "line" and self._should_stop(frame):
+            self._pause(frame)
+        elif event == "return" and frame is self.stop_frame:
+            self.stop_frame = frame.f_back
+            if self.stop_frame is None and self.mode is not None:
+                self.mode = "into"
+        return self.trace_line
+
+    def _should_stop(self, frame):
+        if frame.f_lineno in self.breakpoints.get(frame.f_code.co_filename, ()):
+            return True
+        if self.mode == "into":
+            return True
+        return self.mode is not None and frame is self.stop_frame
+
+    def _pause(self, frame):
+        self.mode = None
+        self.stop_frame = None
+        command = pause(frame)
+
+        if command == "over":
+            self.mode, self.stop_frame = "over", frame
+        elif command == "out":
+            self.mode, self.stop_frame = ("out", frame.f_back) if frame.f_back is not None else ("into", None)
+        elif command == "into":
+            self.mode = "into"
+
+        if self.mode is not None:
+            outer = frame
+            while outer is not None:
+                outer.f_trace = self.trace_line
+                outer = outer.f_back
+        self.set_breakpoints({} if command == "stop" else breakpoints())
+
+
+debugger = Debugger()
+
+
+def describe_stack(frame):
+    stack = []
+    while frame is not None:
+        code = frame.f_code
+        variables = [(name, _safe_repr(value)) for name, value in frame.f_locals.items()]
+        stack.append((code.co_filename, frame.f_lineno, code.co_name, variables))
+        frame = frame.f_back
+    return stack
+
+
+def evaluate(frame, depth, expressions):
+    for _ in range(depth):
+        frame = frame.f_back if frame.f_back is not None else frame
+    results = []
+    for expression in expressions:
+        try:
+            results.append((True, _safe_repr(eval(expression, frame.f_globals, frame.f_locals))))
+        except Exception as e:
+            results.append((False, f"{type(e).__name__}: {e}"))
+    return results
+"#;
+
+/// Bumped every time the game stops, so the editor can jump to the new line once.
+static PAUSE_SERIAL: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static::lazy_static! {
+    /// Breakpoint lines by the filename Python reports for them.
+    static ref BREAKPOINTS: std::sync::Mutex<BTreeMap<String, BTreeSet<usize>>> = std::sync::Mutex::new(BTreeMap::new());
+    static ref PAUSED: std::sync::Mutex<Option<PausedState>> = std::sync::Mutex::new(None);
+    static ref COMMAND: std::sync::Mutex<Option<Command>> = std::sync::Mutex::new(None);
+    /// Frame depth and expressions to evaluate while paused.
+    static ref WATCH_REQUEST: std::sync::Mutex<Option<(usize, Vec<String>)>> = std::sync::Mutex::new(None);
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Continue,
+    StepOver,
+    StepInto,
+    StepOut,
+    /// Drops all tracing so the game can run to its end, used when it is closed.
+    Stop,
+}
+
+impl Command {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Continue => "continue",
+            Self::StepOver => "over",
+            Self::StepInto => "into",
+            Self::StepOut => "out",
+            Self::Stop => "stop",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct StackFrame {
+    pub location: SourceLocation,
+    pub function: String,
+    pub locals: Vec<(String, String)>,
+}
+
+#[derive(Clone)]
+pub struct PausedState {
+    /// Innermost frame first.
+    pub stack: Vec<StackFrame>,
+    /// Watch results as `(ok, value or error)`, in the order they were requested.
+    pub watches: Vec<(bool, String)>,
+}
+
+
+type StackProbe = Vec<(String, usize, String, Vec<(String, String)>)>;
+
+fn debugger_module(py: Python<'_>) -> PyResult<Bound<'_, PyModule>> {
+    python::helper_module_with(py, "debugger", DEBUGGER_MODULE, |module| {
+        module.add_function(wrap_pyfunction_bound!(tracer::pause, module)?)?;
+        module.add_function(wrap_pyfunction_bound!(breakpoints, module)?)?;
+        python::register_submodule(py, "debugger", module)
+    })
+}
+
+/// Current breakpoints, read by the tracer when it resumes.
+#[pyfunction]
+fn breakpoints() -> BTreeMap<String, BTreeSet<usize>> {
+    lock(&BREAKPOINTS).clone()
+}
+
+// pyo3 0.21 unwraps required reference arguments inside an `unsafe fn` without an unsafe block,
+// which edition 2024 reports, so `pause` is kept apart from the rest of the module.
+#[expect(unsafe_op_in_unsafe_fn, reason = "generated by pyo3's argument extraction")]
+mod tracer {
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use pyo3::prelude::*;
+
+    use super::{debugger_module, lock, PausedState, StackFrame, StackProbe, COMMAND, PAUSED, PAUSE_SERIAL, WATCH_REQUEST};
+    use crate::engine::editor::SourceLocation;
+
+    /// Called by the tracer when it stops. Publishes the stack and blocks the game thread,
+    /// answering watch requests, until the user picks a command.
+    #[pyfunction]
+    pub fn pause(py: Python<'_>, frame: &Bound<'_, PyAny>) -> PyResult<&'static str> {
+        let module = debugger_module(py)?;
+        let stack: StackProbe = module.getattr("describe_stack")?.call1((frame,))?.extract()?;
+
+        *lock(&COMMAND) = None;
+        *lock(&PAUSED) = Some(PausedState {
+            stack: stack
+                .into_iter()
+                .map(|(file, line, function, locals)| StackFrame { location: SourceLocation { file, line }, function, locals })
+                .collect(),
+            watches: Vec::new(),
+        });
+        PAUSE_SERIAL.fetch_add(1, Ordering::Relaxed);
+
+        loop {
+            if let Some(command) = lock(&COMMAND).take() {
+                *lock(&PAUSED) = None;
+                return Ok(command.as_str());
+            }
+
+            let request = lock(&WATCH_REQUEST).take();
+            if let Some((depth, expressions)) = request {
+                let results: Vec<(bool, String)> = module.getattr("evaluate")?.call1((frame, depth, expressions))?.extract()?;
+                if let Some(paused) = lock(&PAUSED).as_mut() {
+                    paused.watches = results;
+                }
+            }
+
+            py.allow_threads(|| std::thread::sleep(Duration::from_millis(10)));
+        }
+    }
+}
+
+/// Installs the tracer on the current (game) thread with the editor's breakpoints.
+pub fn install(py: Python<'_>) -> PyResult<()> {
+    *lock(&PAUSED) = None;
+    *lock(&COMMAND) = None;
+    debugger_module(py)?.getattr("debugger")?.call_method1("reset", (breakpoints(),))?;
+    Ok(())
+}
+
+fn sync_breakpoints() {
+    python::queue_python_instruction(|py| {
+        let result = debugger_module(py).and_then(|module| module.getattr("debugger")?.call_method1("set_breakpoints", (breakpoints(),)));
+        if let Err(e) = result {
+            e.print(py);
+        }
+    });
+}
+
+pub fn toggle_breakpoint(file: &str, line: usize) {
+    {
+        let mut breakpoints = lock(&BREAKPOINTS);
+        let lines = breakpoints.entry(file.to_owned()).or_default();
+        if !lines.remove(&line) {
+            lines.insert(line);
+        }
+    }
+    sync_breakpoints();
+}
+
+pub fn breakpoints_in(file: &str) -> BTreeSet<usize> {
+    lock(&BREAKPOINTS).get(file).cloned().unwrap_or_default()
+}
+
+pub fn clear_breakpoints() {
+    lock(&BREAKPOINTS).clear();
+    sync_breakpoints();
+}
+
+/// Resumes a game stopped by the debugger. Does nothing while it runs.
+pub fn resume(command: Command) {
+    if lock(&PAUSED).is_some() {
+        *lock(&COMMAND) = Some(command);
+    }
+}
+
+pub fn paused_state() -> Option<PausedState> {
+    lock(&PAUSED).clone()
+}
+
+/// The line the game is stopped on, if it is.
+pub fn paused_location() -> Option<SourceLocation> {
+    lock(&PAUSED).as_ref().and_then(|paused| paused.stack.first()).map(|frame| frame.location.clone())
+}
+
+
+/// The Debug side tab: call stack, locals and watches of the stopped game.
+#[derive(Default)]
+pub struct DebugView {
+    pub watches: Vec<String>,
+    new_watch: String,
+    selected_frame: usize,
+    last_pause: usize,
+}
+
+impl DebugView {
+    fn request_watches(&self) {
+        *lock(&WATCH_REQUEST) = Some((self.selected_frame, self.watches.clone()));
+    }
+
+    /// Handles the stepping shortcuts. Returns the line the game just stopped on, once.
+    pub fn update(&mut self, ctx: &Context) -> Option<SourceLocation> {
+        let location = paused_location()?;
+
+        ctx.input_mut(|input| {
+            if input.consume_key(egui::Modifiers::SHIFT, Key::F11) {
+                resume(Command::StepOut);
+            } else if input.consume_key(egui::Modifiers::NONE, Key::F5) {
+                resume(Command::Continue);
+            } else if input.consume_key(egui::Modifiers::NONE, Key::F10) {
+                resume(Command::StepOver);
+            } else if input.consume_key(egui::Modifiers::NONE, Key::F11) {
+                resume(Command::StepInto);
+            }
+        });
+
+        let serial = PAUSE_SERIAL.load(Ordering::Relaxed);
+        if serial == self.last_pause {
+            return None;
+        }
+        self.last_pause = serial;
+        self.selected_frame = 0;
+        self.request_watches();
+        Some(location)
+    }
+
+    fn controls(ui: &mut Ui, paused: bool) {
+        ui.add_enabled_ui(paused, |ui| {
+            let buttons = [
+                (egui_phosphor::regular::PLAY, "Continue (F5)", Command::Continue),
+                (egui_phosphor::regular::ARROW_BEND_DOWN_RIGHT, "Step over (F10)", Command::StepOver),
+                (egui_phosphor::regular::ARROW_ELBOW_DOWN_RIGHT, "Step into (F11)", Command::StepInto),
+                (egui_phosphor::regular::ARROW_ELBOW_LEFT_UP, "Step out (Shift+F11)", Command::StepOut),
+            ];
+            for (icon, hint, command) in buttons {
+                if ui.button(icon).on_hover_text(hint).clicked() {
+                    resume(command);
+                }
+            }
+        });
+    }
+
+    fn watches_ui(&mut self, ui: &mut Ui, paused: Option<&PausedState>) {
+        let mut changed = false;
+        let mut remove = None;
+
+        egui::Grid::new("debug_watches").striped(true).num_columns(3).show(ui, |ui| {
+            for (index, expression) in self.watches.iter().enumerate() {
+                ui.monospace(expression);
+                match paused.and_then(|paused| paused.watches.get(index)) {
+                    Some((true, value)) => ui.monospace(value),
+                    Some((false, error)) => ui.colored_label(egui::Color32::from_rgb(230, 80, 80), error),
+                    None => ui.weak("—"),
+                };
+                if ui.small_button(egui_phosphor::regular::X).clicked() {
+                    remove = Some(index);
+                }
+                ui.end_row();
+            }
+        });
+
+        if let Some(index) = remove {
+            self.watches.remove(index);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            let response = ui.add(egui::TextEdit::singleline(&mut self.new_watch).hint_text("Add watch..."));
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+            if (submitted || ui.button(egui_phosphor::regular::PLUS).clicked()) && !self.new_watch.trim().is_empty() {
+                self.watches.push(self.new_watch.trim().to_owned());
+                self.new_watch.clear();
+                changed = true;
+            }
+        });
+
+        if changed && paused.is_some() {
+            self.request_watches();
+        }
+    }
+
+    /// Returns a stack frame the user clicked, for the editor to jump to.
+    pub fn ui(&mut self, ui: &mut Ui) -> Option<SourceLocation> {
+        let paused = paused_state();
+        let mut goto = None;
+        if paused.is_some() {
+            // Watch results arrive from the game thread.
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+
+        ui.horizontal(|ui| {
+            Self::controls(ui, paused.is_some());
+            if ui.button(egui_phosphor::regular::TRASH).on_hover_text("Remove all breakpoints").clicked() {
+                clear_breakpoints();
+            }
+        });
+        match paused.as_ref().and_then(|paused| paused.stack.first()) {
+            Some(frame) => ui.label(format!("Paused at {}:{}", frame.location.file, frame.location.line)),
+            None => ui.label("Click beside a line in the Scripting tab to add a breakpoint."),
+        };
+        ui.add(egui::Separator::default().grow(5.0));
+
+        egui::ScrollArea::vertical().id_salt("debug_scroll").show(ui, |ui| {
+            if let Some(paused) = &paused {
+                egui::CollapsingHeader::new("Call stack").default_open(true).show(ui, |ui| {
+                    for (index, frame) in paused.stack.iter().enumerate() {
+                        let label = format!("{} {}:{}", frame.function, frame.location.file, frame.location.line);
+                        if ui.selectable_label(self.selected_frame == index, label).clicked() {
+                            self.selected_frame = index;
+                            self.request_watches();
+                            goto = Some(frame.location.clone());
+                        }
+                    }
+                });
+
+                if let Some(frame) = paused.stack.get(self.selected_frame) {
+                    egui::CollapsingHeader::new("Locals").default_open(true).show(ui, |ui| {
+                        egui::Grid::new("debug_locals").striped(true).num_columns(2).show(ui, |ui| {
+                            for (name, value) in &frame.locals {
+                                ui.monospace(name);
+                                ui.monospace(value);
+                                ui.end_row();
+                            }
+                        });
+                    });
+                }
+            }
+
+            egui::CollapsingHeader::new("Watches").default_open(true).show(ui, |ui| {
+                self.watches_ui(ui, paused.as_ref());
+            });
+        });
+
+        goto
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use egui::{pos2, text::CCursor, text_edit::TextEditOutput, Color32, Rect, Sense, Shape, Stroke, Ui};
+use pyo3::prelude::*;
+
+use crate::engine::debounce::DebouncedParser;
+use crate::engine::editor::{self, SourceLocation};
+use crate::engine::{python, testing};
+
+
+const ERROR_COLOR: Color32 = Color32::from_rgb(230, 80, 80);
+const WARNING_COLOR: Color32 = Color32::from_rgb(230, 160, 60);
+
+/// Compiles a buffer and reports its syntax error and any warnings, without running it.
+/// Catching warnings swaps the interpreter's global filters, so with `collect_warnings` off
+/// they are left alone and only the syntax error is reported.
+const SYNTAX_PROBE: &str = r#"
+import warnings
+
+def _compile(source, filename, problems):
+    try:
+        compile(source, filename, "exec", dont_inherit=True)
+    except SyntaxError as e:
+        problems.append((True, f"{type(e).__name__}: {e.msg}", e.lineno or 1, e.offset or 1,
+                         getattr(e, "end_lineno", None), getattr(e, "end_offset", None)))
+    except ValueError as e:
+        problems.append((True, f"ValueError: {e}", 1, 1, None, None))
+
+def check(source, filename, collect_warnings):
+    problems = []
+    if not collect_warnings:
+        _compile(source, filename, problems)
+        return problems
+    with warnings.catch_warnings(record=True) as caught:
+        warnings.simplefilter("always")
+        _compile(source, filename, problems)
+    for warning in caught:
+        problems.append((False, f"{warning.category.__name__}: {warning.message}", warning.lineno or 1, 1, None, None))
+    return problems
+"#;
+
+lazy_static::lazy_static! {
+    static ref FILES: Mutex<BTreeMap<PathBuf, FileDiagnostics>> = Mutex::new(BTreeMap::new());
+    static ref CHECKER: DebouncedParser<PathBuf> = DebouncedParser::spawn(|py, path: &PathBuf, text: &str| check(py, path, text));
+}
+
+
+#[derive(Clone)]
+pub struct Problem {
+    pub is_error: bool,
+    pub message: String,
+    /// 1-based.
+    pub line: usize,
+    /// 1-based, in characters.
+    pub column: usize,
+    /// Where the offending code ends, when Python knows.
+    pub end: Option<(usize, usize)>,
+}
+
+#[derive(Default)]
+struct FileDiagnostics {
+    /// The buffer last sent for checking.
+    text: String,
+    problems: Vec<Problem>,
+    /// Reported by the language server, if one is running.
+    server_problems: Vec<Problem>,
+}
+
+impl FileDiagnostics {
+    fn all(&self) -> Vec<Problem> {
+        self.problems.iter().chain(&self.server_problems).cloned().collect()
+    }
+}
+
+type ProbeResult = Vec<(bool, String, usize, usize, Option<usize>, Option<usize>)>;
+
+fn compile_problems(py: Python<'_>, text: &str, filename: &str) -> PyResult<Vec<Problem>> {
+    // Games share the interpreter, so their warning filters are not touched while one runs.
+    let collect_warnings = !python::game_thread_running() && !testing::TEST_RUN.lock().unwrap().running;
+    let check = python::helper_module(py, "diagnostics", SYNTAX_PROBE)?.getattr("check")?;
+    let problems: ProbeResult = check.call1((text, filename, collect_warnings))?.extract()?;
+
+    Ok(problems
+        .into_iter()
+        .map(|(is_error, message, line, column, end_line, end_column)| Problem {
+            is_error,
+            message,
+            line,
+            column,
+            end: end_line.zip(end_column),
+        })
+        .collect())
+}
+
+/// Runs on the checker thread once `text` of `path` settles.
+fn check(py: Python<'_>, path: &Path, text: &str) {
+    let problems = compile_problems(py, text, &path.display().to_string()).unwrap_or_else(|e| {
+        vec![Problem { is_error: true, message: format!("Could not check syntax: {e}"), line: 1, column: 1, end: None }]
+    });
+
+    // Only report if nothing newer was sent for checking.
+    if let Some(file) = FILES.lock().unwrap().get_mut(path).filter(|file| file.text == text) {
+        file.problems = problems;
+    }
+}
+
+/// Checks every buffer that changed since it was last seen, in the background once it settles.
+/// Files no longer listed lose their problems.
+pub fn sync<'a>(buffers: impl IntoIterator<Item = (&'a Path, &'a str)>) {
+    let mut files = FILES.lock().unwrap();
+    let mut open = BTreeSet::new();
+
+    for (path, text) in buffers {
+        open.insert(path.to_owned());
+        if files.get(path).is_some_and(|file| file.text == text) {
+            continue;
+        }
+
+        files.entry(path.to_owned()).or_default().text = text.to_owned();
+        CHECKER.send(path.to_owned(), text.to_owned());
+    }
+    files.retain(|path, _| open.contains(path));
+}
+
+/// Replaces the language server's problems for `path`, if it is an open file.
+pub fn set_server_problems(path: &Path, problems: Vec<Problem>) {
+    if let Some(file) = FILES.lock().unwrap().get_mut(path) {
+        file.server_problems = problems;
+    }
+}
+
+pub fn problems_in(path: &Path) -> Vec<Problem> {
+    FILES.lock().unwrap().get(path).map(FileDiagnostics::all).unwrap_or_default()
+}
+
+pub fn problem_count() -> usize {
+    FILES.lock().unwrap().values().map(|file| file.problems.len() + file.server_problems.len()).sum()
+}
+
+
+/// Char range of `problem` within `text`, kept to its first line.
+fn char_range(text: &str, problem: &Problem) -> (usize, usize) {
+    let line_start = editor::line_start(text, problem.line);
+    let line_len = text.lines().nth(problem.line.saturating_sub(1)).map_or(0, |line| line.chars().count());
+    let at = |column: usize| line_start + column.saturating_sub(1).min(line_len);
+
+    let start = at(problem.column);
+    let end = match problem.end {
+        Some((line, column)) if line == problem.line => at(column),
+        Some(_) => line_start + line_len,
+        // Without an end, mark the rest of the word.
+        None => {
+            let rest = text.chars().skip(start).take_while(|c| c.is_alphanumeric() || *c == '_').count();
+            start + rest.max(1).min(line_start + line_len - start)
+        }
+    };
+    (start, end.max(start))
+}
+
+fn squiggle(ui: &Ui, x_range: egui::Rangef, y: f32, color: Color32) {
+    let points = (0..)
+        .map(|step| step as f32 * 3.0)
+        .take_while(|&x| x_range.min + x <= x_range.max)
+        .enumerate()
+        .map(|(index, x)| pos2(x_range.min + x, y + if index % 2 == 0 { 0.0 } else { -2.0 }))
+        .collect::<Vec<_>>();
+    ui.painter().add(Shape::line(points, Stroke::new(1.0, color)));
+}
+
+/// Underlines `problems` in a laid out editor and marks their lines in `gutter`,
+/// both showing the message on hover.
+pub fn paint_problems(ui: &Ui, gutter: Rect, output: &TextEditOutput, text: &str, problems: &[Problem]) {
+    let offset = output.galley_pos.to_vec2();
+    for (index, problem) in problems.iter().enumerate() {
+        let color = if problem.is_error { ERROR_COLOR } else { WARNING_COLOR };
+        let (start, end) = char_range(text, problem);
+        let from = output.galley.pos_from_cursor(CCursor::new(start)).translate(offset);
+        let to = output.galley.pos_from_cursor(CCursor::new(end)).translate(offset);
+        // A problem wrapped onto a second row is only underlined on the first.
+        let right = if to.top() == from.top() { to.left() } else { output.response.rect.right() };
+        let x_range = egui::Rangef::new(from.left(), right.max(from.left() + 8.0));
+
+        squiggle(ui, x_range, from.bottom(), color);
+        let id = output.response.id.with(("problem", index));
+        ui.interact(Rect::from_x_y_ranges(x_range, from.y_range()), id, Sense::hover())
+            .on_hover_text(&problem.message);
+
+        let icon = if problem.is_error { egui_phosphor::regular::X_CIRCLE } else { egui_phosphor::regular::WARNING };
+        let marker = Rect::from_center_size(pos2(gutter.center().x, from.center().y), egui::vec2(gutter.width(), from.height()));
+        ui.painter().text(marker.center(), egui::Align2::CENTER_CENTER, icon, egui::FontId::proportional(12.0), color);
+        ui.interact(marker, id.with("gutter"), Sense::hover()).on_hover_text(&problem.message);
+    }
+}
+
+
+/// The Problems tab: every problem in the open files. Returns the one that was clicked.
+pub fn problems_ui(ui: &mut Ui) -> Option<SourceLocation> {
+    let files: Vec<(PathBuf, Vec<Problem>)> = FILES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(path, file)| (path.clone(), file.all()))
+        .filter(|(_, problems)| !problems.is_empty())
+        .collect();
+
+    if files.is_empty() {
+        ui.centered_and_justified(|ui| {
+            ui.label("No problems in the open files.");
+        });
+        return None;
+    }
+
+    let mut clicked = None;
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for (path, problems) in &files {
+            let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            egui::CollapsingHeader::new(format!("{} {name} ({})", egui_phosphor::regular::FILE, problems.len()))
+                .id_salt(path)
+                .default_open(true)
+                .show(ui, |ui| {
+                    for problem in problems {
+                        let (icon, color) = if problem.is_error {
+                            (egui_phosphor::regular::X_CIRCLE, ERROR_COLOR)
+                        } else {
+                            (egui_phosphor::regular::WARNING, WARNING_COLOR)
+                        };
+                        let label = egui::RichText::new(format!("{icon} {} [{}:{}]", problem.message, problem.line, problem.column)).color(color);
+                        if ui.selectable_label(false, label).on_hover_text(path.display().to_string()).clicked() {
+                            clicked = Some(SourceLocation { file: path.display().to_string(), line: problem.line });
+                        }
+                    }
+                });
+        }
+    });
+    clicked
+}
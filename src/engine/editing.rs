@@ -0,0 +1,364 @@
+use std::ops::Range;
+
+use egui::{text::{CCursor, CCursorRange, LayoutJob}, text_edit::TextEditState, Color32, Event, Id, Key, KeyboardShortcut, Modifiers, Ui};
+
+use crate::engine::editor::{line_of, line_start};
+use crate::engine::find;
+
+
+const INDENT: &str = "    ";
+/// Brackets and quotes typed in pairs.
+const PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+/// Statements after which the next line goes back one level.
+const DEDENT_KEYWORDS: [&str; 5] = ["return", "pass", "break", "continue", "raise"];
+/// How far to look for a matching bracket, so huge files stay responsive.
+const MAX_BRACKET_SCAN: usize = 20_000;
+const BRACKET_MATCH_COLOR: Color32 = Color32::from_rgba_premultiplied(60, 60, 60, 90);
+
+
+/// Selection of the `TextEdit` with `id` as sorted char indices.
+fn selection(ctx: &egui::Context, id: Id) -> Option<(usize, usize)> {
+    let range = TextEditState::load(ctx, id)?.cursor.char_range()?;
+    Some((range.primary.index.min(range.secondary.index), range.primary.index.max(range.secondary.index)))
+}
+
+fn select(ctx: &egui::Context, id: Id, start: usize, end: usize) {
+    let mut state = TextEditState::load(ctx, id).unwrap_or_default();
+    state.cursor.set_char_range(Some(CCursorRange::two(CCursor::new(start), CCursor::new(end))));
+    state.store(ctx, id);
+}
+
+/// Replaces chars `start..end` of `text` with `with`.
+fn splice(text: &mut String, start: usize, end: usize, with: &str) {
+    let range = find::byte_index(text, start)..find::byte_index(text, end);
+    text.replace_range(range, with);
+}
+
+/// Char index of the end of `line` (1-based), before its newline.
+fn line_end(text: &str, line: usize) -> usize {
+    line_start(text, line) + text.lines().nth(line - 1).map_or(0, |line| line.chars().count())
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Lines touched by the selection `start..end`, leaving out a last line it only reaches the start of.
+fn selected_lines(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let first = line_of(text, start);
+    let last = line_of(text, end);
+    if last > first && line_start(text, last) == end { (first, last - 1) } else { (first, last) }
+}
+
+/// Rewrites lines `first..=last` of `text` with `edit` as one undo step. The selection covers
+/// the edited lines, or a lone cursor shifts with its line.
+fn edit_lines(ui: &Ui, id: Id, text: &mut String, (start, end): (usize, usize), edit: impl Fn(&str) -> String) {
+    let (first, last) = selected_lines(text, start, end);
+    let block_start = line_start(text, first);
+    let block_end = line_end(text, last);
+    let block: String = text.chars().skip(block_start).take(block_end - block_start).collect();
+    let edited = block.split('\n').map(edit).collect::<Vec<_>>().join("\n");
+
+    find::push_undo(ui, id, text);
+    splice(text, block_start, block_end, &edited);
+    let edited_end = block_start + edited.chars().count();
+    if start == end {
+        let cursor = (start + edited_end).saturating_sub(block_end).max(block_start);
+        select(ui.ctx(), id, cursor, cursor);
+    } else {
+        select(ui.ctx(), id, block_start, edited_end);
+    }
+}
+
+fn indent_line(line: &str) -> String {
+    if line.trim().is_empty() { line.to_owned() } else { format!("{INDENT}{line}") }
+}
+
+fn outdent_line(line: &str) -> String {
+    let spaces = line.chars().take(INDENT.len()).take_while(|&c| c == ' ').count();
+    match line.strip_prefix('\t') {
+        Some(rest) if spaces == 0 => rest.to_owned(),
+        _ => line[spaces..].to_owned(),
+    }
+}
+
+/// Comments the lines of the selection at their shallowest indent, or uncomments them if
+/// they all are comments already.
+fn toggle_comment(ui: &Ui, id: Id, text: &mut String, selection: (usize, usize)) {
+    let (first, last) = selected_lines(text, selection.0, selection.1);
+    let lines: Vec<&str> = text.lines().skip(first - 1).take(last - first + 1).filter(|line| !line.trim().is_empty()).collect();
+    // Only spaces and tabs count as indent, so slicing at the shallowest one stays on a char boundary.
+    let code = |line: &str| line.trim_start_matches([' ', '\t']).len();
+    let commented = !lines.is_empty() && lines.iter().all(|line| line.trim_start_matches([' ', '\t']).starts_with('#'));
+    let indent = lines.iter().map(|line| line.len() - code(line)).min().unwrap_or(0);
+
+    edit_lines(ui, id, text, selection, |line| {
+        if line.trim().is_empty() {
+            line.to_owned()
+        } else if commented {
+            let (indent, comment) = line.split_at(line.len() - code(line));
+            let uncommented = comment.strip_prefix("# ").unwrap_or(&comment[1..]);
+            format!("{indent}{uncommented}")
+        } else {
+            format!("{}# {}", &line[..indent], &line[indent..])
+        }
+    });
+}
+
+/// Starts a new line at the indentation the current one calls for: one level deeper after
+/// a `:`, one level shallower after `return`, `pass` and friends.
+fn newline(text: &mut String, (start, end): (usize, usize)) -> usize {
+    let line = line_of(text, start);
+    let before: String = text.chars().skip(line_start(text, line)).take(start - line_start(text, line)).collect();
+    let indent: String = before.chars().take_while(|&c| c == ' ' || c == '\t').collect();
+    let code = before.split('#').next().unwrap_or_default().trim();
+    let keyword = code.split(|c: char| !is_identifier(c)).next().unwrap_or_default();
+
+    let indent = if code.ends_with(':') {
+        format!("{indent}{INDENT}")
+    } else if DEDENT_KEYWORDS.contains(&keyword) {
+        outdent_line(&indent)
+    } else {
+        indent
+    };
+    let inserted = format!("\n{indent}");
+    splice(text, start, end, &inserted);
+    start + inserted.chars().count()
+}
+
+/// Types `typed`, pairing brackets and quotes, wrapping a selection in them, and stepping over
+/// a closing one that is already there. Returns the new cursor.
+fn type_paired(text: &mut String, (start, end): (usize, usize), typed: char) -> usize {
+    let next = text.chars().nth(end);
+    let previous = start.checked_sub(1).and_then(|index| text.chars().nth(index));
+
+    if start == end && next == Some(typed) && PAIRS.iter().any(|&(_, close)| close == typed) {
+        return start + 1;
+    }
+    let Some(&(open, close)) = PAIRS.iter().find(|&&(open, _)| open == typed) else {
+        splice(text, start, end, &typed.to_string());
+        return start + 1;
+    };
+
+    if start != end {
+        let selected: String = text.chars().skip(start).take(end - start).collect();
+        splice(text, start, end, &format!("{open}{selected}{close}"));
+        return end + 2;
+    }
+
+    let is_quote = open == close;
+    let pairs = if is_quote {
+        // Not inside words like `don't`, nor on the third quote of a docstring.
+        !previous.is_some_and(|c| is_identifier(c) || c == typed) && !next.is_some_and(is_identifier)
+    } else {
+        next.is_none_or(|c| c.is_whitespace() || ")]},:".contains(c))
+    };
+    let inserted = if pairs { format!("{open}{close}") } else { open.to_string() };
+    splice(text, start, end, &inserted);
+    start + 1
+}
+
+/// Takes a typed bracket or quote out of this frame's input, so the `TextEdit` does not insert it.
+fn take_typed_pair(ui: &Ui) -> Option<char> {
+    ui.input_mut(|i| {
+        let index = i.events.iter().position(|event| match event {
+            Event::Text(typed) => typed.chars().count() == 1 && PAIRS.iter().any(|&pair| typed.starts_with(<[char; 2]>::from(pair))),
+            _ => false,
+        })?;
+        match i.events.remove(index) {
+            Event::Text(typed) => typed.chars().next(),
+            _ => None,
+        }
+    })
+}
+
+/// Handles Python editing keys for the focused `TextEdit` with `id` before it sees them.
+/// Returns the cursor after an edit, if one was made.
+pub fn handle_keys(ui: &Ui, id: Id, text: &mut String) -> Option<usize> {
+    if !ui.memory(|memory| memory.has_focus(id)) {
+        return None;
+    }
+    let (start, end) = selection(ui.ctx(), id)?;
+    let multiline = text.chars().skip(start).take(end - start).any(|c| c == '\n');
+
+    let comment = KeyboardShortcut::new(Modifiers::COMMAND, Key::Slash);
+    if ui.input_mut(|i| i.consume_shortcut(&comment)) {
+        toggle_comment(ui, id, text, (start, end));
+        return selection(ui.ctx(), id).map(|(_, end)| end);
+    }
+    // Shift+Tab first: a plain Tab pattern also matches it.
+    if ui.input_mut(|i| i.consume_key(Modifiers::SHIFT, Key::Tab)) {
+        edit_lines(ui, id, text, (start, end), outdent_line);
+        return selection(ui.ctx(), id).map(|(_, end)| end);
+    }
+    if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Tab)) {
+        if multiline {
+            edit_lines(ui, id, text, (start, end), indent_line);
+            return selection(ui.ctx(), id).map(|(_, end)| end);
+        }
+        let column = start - line_start(text, line_of(text, start));
+        let spaces = " ".repeat(INDENT.len() - column % INDENT.len());
+        splice(text, start, end, &spaces);
+        let cursor = start + spaces.len();
+        select(ui.ctx(), id, cursor, cursor);
+        return Some(cursor);
+    }
+
+    let cursor = if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Enter)) {
+        newline(text, (start, end))
+    } else if start == end && start > 0 && is_empty_pair(text, start) && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Backspace)) {
+        splice(text, start - 1, start + 1, "");
+        start - 1
+    } else {
+        let typed = take_typed_pair(ui)?;
+        type_paired(text, (start, end), typed)
+    };
+    select(ui.ctx(), id, cursor, cursor);
+    Some(cursor)
+}
+
+/// Whether the cursor at `at` sits inside an empty pair like `()` or `""`.
+fn is_empty_pair(text: &str, at: usize) -> bool {
+    let mut chars = text.chars().skip(at - 1);
+    let (Some(before), Some(after)) = (chars.next(), chars.next()) else {
+        return false;
+    };
+    PAIRS.contains(&(before, after))
+}
+
+
+/// Byte offset of the bracket matching the one at byte `position`, if it is a bracket.
+fn partner(bytes: &[u8], position: usize) -> Option<usize> {
+    let (open, close, forward) = match bytes[position] {
+        b'(' => (b'(', b')', true),
+        b'[' => (b'[', b']', true),
+        b'{' => (b'{', b'}', true),
+        b')' => (b'(', b')', false),
+        b']' => (b'[', b']', false),
+        b'}' => (b'{', b'}', false),
+        _ => return None,
+    };
+    let (deeper, shallower) = if forward { (open, close) } else { (close, open) };
+
+    let mut depth = 0;
+    let mut check = |index: usize| {
+        if bytes[index] == deeper {
+            depth += 1;
+        } else if bytes[index] == shallower {
+            depth -= 1;
+        }
+        depth == 0
+    };
+    if forward {
+        (position..bytes.len().min(position + MAX_BRACKET_SCAN)).find(|&index| check(index))
+    } else {
+        (position.saturating_sub(MAX_BRACKET_SCAN)..=position).rev().find(|&index| check(index))
+    }
+}
+
+/// Byte offsets of the bracket beside the cursor at char index `cursor` and its partner.
+pub fn matching_brackets(text: &str, cursor: usize) -> Option<[usize; 2]> {
+    let at = find::byte_index(text, cursor);
+    // The bracket just typed, before the cursor, wins over the one after it.
+    [at.checked_sub(1), Some(at)]
+        .into_iter()
+        .flatten()
+        .filter(|&position| position < text.len())
+        .find_map(|position| Some([position, partner(text.as_bytes(), position)?]))
+}
+
+/// Highlights the `brackets` found in `text` in its laid out `job`, if they are still brackets.
+pub fn highlight_brackets(job: &mut LayoutJob, text: &str, brackets: Option<[usize; 2]>) {
+    let Some(mut brackets) = brackets else {
+        return;
+    };
+    // `text` may have been edited since the brackets were found.
+    if !brackets.iter().all(|&position| text.as_bytes().get(position).is_some_and(|byte| b"()[]{}".contains(byte))) {
+        return;
+    }
+    brackets.sort_unstable();
+    let ranges: Vec<Range<usize>> = brackets.iter().map(|&position| position..position + 1).collect();
+    find::restyle_ranges(job, &ranges, |_, format| format.background = BRACKET_MATCH_COLOR);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{matching_brackets, newline, outdent_line, type_paired};
+
+    /// Presses Enter at the end of `text`, returning the new text.
+    fn enter(text: &str) -> String {
+        let mut text = text.to_owned();
+        let end = text.chars().count();
+        let cursor = newline(&mut text, (end, end));
+        assert_eq!(cursor, text.chars().count());
+        text
+    }
+
+    /// Types `typed` over the chars `start..end` of `text`, returning the new text and cursor.
+    fn typed(text: &str, selection: (usize, usize), typed: char) -> (String, usize) {
+        let mut text = text.to_owned();
+        let cursor = type_paired(&mut text, selection, typed);
+        (text, cursor)
+    }
+
+    #[test]
+    fn indents_after_a_colon() {
+        assert_eq!(enter("def update(self):"), "def update(self):\n    ");
+        assert_eq!(enter("    if ready:  # go"), "    if ready:  # go\n        ");
+        assert_eq!(enter("    x = 1"), "    x = 1\n    ");
+    }
+
+    #[test]
+    fn dedents_after_return_and_friends() {
+        assert_eq!(enter("        return self.x"), "        return self.x\n    ");
+        assert_eq!(enter("    pass"), "    pass\n");
+        assert_eq!(enter("\t\tbreak"), "\t\tbreak\n\t");
+        // Only the statement keyword counts, not names starting with it.
+        assert_eq!(enter("    returned = 1"), "    returned = 1\n    ");
+    }
+
+    #[test]
+    fn outdents_one_level() {
+        assert_eq!(outdent_line("      x"), "  x");
+        assert_eq!(outdent_line("  x"), "x");
+        assert_eq!(outdent_line("\tx"), "x");
+        assert_eq!(outdent_line("x"), "x");
+    }
+
+    #[test]
+    fn pairs_brackets_and_quotes() {
+        assert_eq!(typed("f", (1, 1), '('), ("f()".to_owned(), 2));
+        assert_eq!(typed("x = ", (4, 4), '"'), ("x = \"\"".to_owned(), 5));
+        // Not in front of a word, nor inside one.
+        assert_eq!(typed("f(x)", (2, 2), '('), ("f((x)".to_owned(), 3));
+        assert_eq!(typed("don", (3, 3), '\''), ("don'".to_owned(), 4));
+        assert_eq!(typed("a b", (0, 3), '['), ("[a b]".to_owned(), 5));
+    }
+
+    #[test]
+    fn steps_over_closing_brackets_and_quotes() {
+        assert_eq!(typed("f()", (2, 2), ')'), ("f()".to_owned(), 3));
+        assert_eq!(typed("[1]", (2, 2), ']'), ("[1]".to_owned(), 3));
+        assert_eq!(typed("\"hi\"", (3, 3), '"'), ("\"hi\"".to_owned(), 4));
+        // A closing bracket with nothing to step over is typed.
+        assert_eq!(typed("f(", (2, 2), ')'), ("f()".to_owned(), 3));
+    }
+
+    #[test]
+    fn matches_brackets_beside_the_cursor() {
+        let text = "f(a[1], {b: (2)})";
+        assert_eq!(matching_brackets(text, 1), Some([1, 16]));
+        assert_eq!(matching_brackets(text, 2), Some([1, 16]));
+        assert_eq!(matching_brackets(text, 17), Some([16, 1]));
+        assert_eq!(matching_brackets(text, 8), Some([8, 15]));
+        assert_eq!(matching_brackets(text, 5), Some([5, 3]));
+        assert_eq!(matching_brackets("x = 1", 2), None);
+        assert_eq!(matching_brackets("f(a", 2), None);
+    }
+
+    #[test]
+    fn matches_brackets_after_multibyte_text() {
+        assert_eq!(matching_brackets("é(ü)", 2), Some([2, 5]));
+    }
+}
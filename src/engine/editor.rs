@@ -0,0 +1,697 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use egui::{pos2, text::{CCursor, CCursorRange}, text_edit::{TextEditOutput, TextEditState}, Color32, Id, Key, KeyboardShortcut, Modifiers, Rect, Sense, Ui};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::debugger;
+use crate::engine::diagnostics;
+use crate::engine::editing;
+use crate::engine::find::{self, FindBar};
+use crate::engine::gutter::{self, FOLD_WIDTH};
+use crate::engine::lsp::LspClient;
+use crate::engine::symbols::{Outline, SymbolPicker};
+
+
+/// Width of the strip left of the editor that holds breakpoints.
+pub const GUTTER_WIDTH: f32 = 18.0;
+
+/// Id of the `TextEdit` editing `path`.
+fn editor_id(path: &Path) -> Id {
+    Id::new(("code_editor", path))
+}
+
+/// A line in a source file, as reported by Python tools such as the profiler.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    /// 1-based, 0 when Python has no line for it (builtins).
+    pub line: usize,
+}
+
+impl SourceLocation {
+    /// The file on disk, or `None` for places like `<string>` or builtins (`~`).
+    pub fn path(&self) -> Option<PathBuf> {
+        Some(PathBuf::from(&self.file)).filter(|path| path.is_file())
+    }
+}
+
+
+/// Char index of the start of `line` (1-based) in `text`.
+pub fn line_start(text: &str, line: usize) -> usize {
+    text.split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(|line| line.chars().count())
+        .sum()
+}
+
+/// Moves the cursor of the `TextEdit` with `id` to the start of `line` and focuses it.
+/// Returns the cursor so the caller can scroll to it once the editor is laid out.
+pub fn goto_line(ui: &Ui, id: Id, text: &str, line: usize) -> CCursor {
+    let cursor = CCursor::new(line_start(text, line));
+    let mut state = TextEditState::load(ui.ctx(), id).unwrap_or_default();
+    state.cursor.set_char_range(Some(CCursorRange::one(cursor)));
+    state.store(ui.ctx(), id);
+    ui.ctx().memory_mut(|memory| memory.request_focus(id));
+    cursor
+}
+
+/// 1-based line holding char index `index` of `text`.
+pub fn line_of(text: &str, index: usize) -> usize {
+    text.chars().take(index).filter(|&c| c == '\n').count() + 1
+}
+
+/// Screen rect of `line` in a laid out editor.
+fn line_rect(output: &TextEditOutput, text: &str, line: usize) -> Rect {
+    output.galley.pos_from_cursor(CCursor::new(line_start(text, line))).translate(output.galley_pos.to_vec2())
+}
+
+/// Draws breakpoint markers in `gutter` beside the editor and highlights `current_line`,
+/// the line the debugger stopped on. Returns the line whose marker was clicked.
+pub fn breakpoint_gutter(
+    ui: &Ui,
+    gutter: Rect,
+    output: &TextEditOutput,
+    text: &str,
+    breakpoints: &BTreeSet<usize>,
+    current_line: Option<usize>,
+) -> Option<usize> {
+    let response = ui.interact(gutter, output.response.id.with("breakpoint_gutter"), Sense::click());
+    let painter = ui.painter();
+
+    if let Some(line) = current_line {
+        let row = line_rect(output, text, line);
+        let highlight = Rect::from_x_y_ranges(gutter.left()..=output.response.rect.right(), row.y_range());
+        painter.rect_filled(highlight, 0.0, Color32::from_rgba_unmultiplied(255, 210, 0, 40));
+        painter.text(pos2(gutter.center().x, row.center().y), egui::Align2::CENTER_CENTER, egui_phosphor::regular::ARROW_RIGHT, egui::FontId::proportional(12.0), Color32::from_rgb(255, 210, 0));
+    }
+
+    let radius = (GUTTER_WIDTH * 0.3).min(5.0);
+    for &line in breakpoints {
+        let row = line_rect(output, text, line);
+        painter.circle_filled(pos2(gutter.center().x, row.center().y), radius, Color32::from_rgb(220, 50, 50));
+    }
+
+    if let Some(hover) = response.hover_pos() {
+        let cursor = output.galley.cursor_from_pos(hover - output.galley_pos);
+        let row = line_rect(output, text, line_of(text, cursor.index));
+        painter.circle_stroke(pos2(gutter.center().x, row.center().y), radius, egui::Stroke::new(1.0, Color32::from_rgb(220, 50, 50)));
+    }
+
+    let click = response.interact_pointer_pos().filter(|_| response.clicked())?;
+    let cursor = output.galley.cursor_from_pos(click - output.galley_pos);
+    Some(line_of(text, cursor.index))
+}
+
+
+/// A file open in the editor, backed by the file on disk.
+pub struct OpenFile {
+    pub path: PathBuf,
+    pub text: String,
+    /// Contents as last read from or written to disk.
+    saved: String,
+    /// Header lines of the blocks folded away.
+    pub folded: BTreeSet<usize>,
+}
+
+impl OpenFile {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Could not open {}: {e}", path.display()))?;
+        Ok(Self { path: path.to_owned(), saved: text.clone(), text, folded: BTreeSet::new() })
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.text != self.saved
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        fs::write(&self.path, &self.text).map_err(|e| format!("Could not save {}: {e}", self.path.display()))?;
+        self.saved.clone_from(&self.text);
+        Ok(())
+    }
+
+    pub fn name(&self) -> String {
+        self.path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+
+    /// The filename Python reports for code from this file.
+    pub fn filename(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+
+/// The Scripting tab: files opened from the File Explorer, one editor tab each.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Editor {
+    /// Files open when the app was last closed, reopened on start.
+    open_paths: Vec<PathBuf>,
+    #[serde(skip)]
+    pub files: Vec<OpenFile>,
+    #[serde(skip)]
+    pub active: usize,
+    /// Tab waiting on the unsaved changes prompt before it closes.
+    #[serde(skip)]
+    closing: Option<usize>,
+    /// Whether the app is waiting on the unsaved changes prompt before it quits.
+    #[serde(skip)]
+    quitting: bool,
+    /// Set once the user chose to quit, so the close goes through.
+    #[serde(skip)]
+    quit_confirmed: bool,
+    /// Line to move the cursor to in the active file once it is laid out.
+    #[serde(skip)]
+    goto: Option<usize>,
+    #[serde(skip)]
+    focus_requested: bool,
+    /// Line typed into the go to line prompt (Ctrl+G) while it is open.
+    #[serde(skip)]
+    goto_input: Option<String>,
+    #[serde(skip)]
+    find: FindBar,
+    #[serde(skip)]
+    lsp: Option<LspClient>,
+    /// Project the editor was last updated for, where the language server runs.
+    #[serde(skip)]
+    root: Option<PathBuf>,
+    #[serde(skip)]
+    outline: Outline,
+    #[serde(skip)]
+    symbol_picker: SymbolPicker,
+    #[serde(skip)]
+    pub status: String,
+}
+
+impl Editor {
+    /// Reopens the files that were open last session.
+    pub fn restore(&mut self) {
+        self.files = self.open_paths.iter().filter_map(|path| OpenFile::open(path).ok()).collect();
+        self.active = self.active.min(self.files.len().saturating_sub(1));
+    }
+
+    pub fn remember_open_files(&mut self) {
+        self.open_paths = self.files.iter().map(|file| file.path.clone()).collect();
+    }
+
+    /// Focuses `path`, opening it first if it is not open yet.
+    pub fn open(&mut self, path: &Path) -> bool {
+        self.focus_requested = true;
+        if let Some(index) = self.files.iter().position(|file| file.path == path) {
+            self.active = index;
+            return true;
+        }
+
+        match OpenFile::open(path) {
+            Ok(file) => {
+                self.files.push(file);
+                self.active = self.files.len() - 1;
+                true
+            }
+            Err(e) => {
+                self.status = e;
+                false
+            }
+        }
+    }
+
+    /// Opens the file of `location` and moves the cursor to its line.
+    pub fn goto(&mut self, location: &SourceLocation) {
+        if let Some(path) = location.path() {
+            if self.open(&path) {
+                self.goto = Some(location.line);
+            }
+        }
+    }
+
+    /// Whether the Scripting tab should be brought forward, cleared once read.
+    pub fn take_focus_request(&mut self) -> bool {
+        std::mem::take(&mut self.focus_requested)
+    }
+
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.files.iter().any(OpenFile::is_dirty)
+    }
+
+    /// Keeps syntax problems, the outline and the language server for the project at `root`
+    /// in step with the open Python files.
+    pub fn update(&mut self, ctx: &egui::Context, root: Option<&Path>) {
+        if root != self.root.as_deref() {
+            self.root = root.map(Path::to_owned);
+            self.lsp = None;
+            if let Some(root) = root {
+                match LspClient::start(root, ctx) {
+                    Ok(client) => self.lsp = Some(client),
+                    Err(e) => self.status = e,
+                }
+            }
+        }
+
+        let python: Vec<(&Path, &str)> = self
+            .files
+            .iter()
+            .filter(|file| file.path.extension().is_some_and(|ext| ext == "py"))
+            .map(|file| (file.path.as_path(), file.text.as_str()))
+            .collect();
+        diagnostics::sync(python.iter().copied());
+        match self.files.get(self.active).filter(|file| file.path.extension().is_some_and(|ext| ext == "py")) {
+            Some(file) => self.outline.sync(&file.path, &file.text),
+            None => self.outline.clear(),
+        }
+
+        let definition = self.lsp.as_mut().and_then(|lsp| {
+            lsp.poll();
+            lsp.sync(python);
+            lsp.take_definition()
+        });
+        if let Some(location) = definition {
+            self.goto(&location);
+        }
+    }
+
+    /// Whether `path` is open with unsaved changes.
+    pub fn is_unsaved(&self, path: &Path) -> bool {
+        self.files.iter().any(|file| file.path == path && file.is_dirty())
+    }
+
+    /// Rereads open files among `paths` after they were rewritten outside the editor.
+    pub fn reload<'a>(&mut self, paths: impl IntoIterator<Item = &'a Path>) {
+        for path in paths {
+            if let Some(file) = self.files.iter_mut().find(|file| file.path == path) {
+                match OpenFile::open(path) {
+                    Ok(reopened) => *file = reopened,
+                    Err(e) => self.status = e,
+                }
+            }
+        }
+    }
+
+    fn save(&mut self, index: usize) -> bool {
+        let Some(file) = self.files.get_mut(index) else {
+            return false;
+        };
+        match file.save() {
+            Ok(()) => {
+                self.status = format!("Saved {}", file.name());
+                true
+            }
+            Err(e) => {
+                self.status = e;
+                false
+            }
+        }
+    }
+
+    /// Saves every file with unsaved changes. Returns `false` if any could not be written.
+    pub fn save_all(&mut self) -> bool {
+        let dirty: Vec<usize> = (0..self.files.len()).filter(|&index| self.files[index].is_dirty()).collect();
+        let saved = dirty.iter().filter(|&&index| self.save(index)).count();
+        if saved == dirty.len() && saved > 1 {
+            self.status = format!("Saved {saved} files");
+        }
+        saved == dirty.len()
+    }
+
+    fn close(&mut self, index: usize) {
+        self.files.remove(index);
+        if self.active > index || self.active >= self.files.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+    }
+
+    fn request_close(&mut self, index: usize) {
+        if self.files[index].is_dirty() {
+            self.closing = Some(index);
+        } else {
+            self.close(index);
+        }
+    }
+
+    fn shortcuts(&mut self, ui: &Ui) {
+        let save_all = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::S);
+        let save = KeyboardShortcut::new(Modifiers::COMMAND, Key::S);
+
+        if ui.input_mut(|i| i.consume_shortcut(&save_all)) {
+            self.save_all();
+        } else if ui.input_mut(|i| i.consume_shortcut(&save)) {
+            self.save(self.active);
+        }
+
+        let open_goto = KeyboardShortcut::new(Modifiers::COMMAND, Key::G);
+        if ui.input_mut(|i| i.consume_shortcut(&open_goto)) && !self.files.is_empty() {
+            self.goto_input = Some(String::new());
+        }
+
+        let open_find = KeyboardShortcut::new(Modifiers::COMMAND, Key::F);
+        let open_replace = KeyboardShortcut::new(Modifiers::COMMAND, Key::H);
+        let replacing = ui.input_mut(|i| i.consume_shortcut(&open_replace));
+        if replacing || ui.input_mut(|i| i.consume_shortcut(&open_find)) {
+            let selection = self.files.get(self.active).and_then(|file| find::selected_text(ui.ctx(), editor_id(&file.path), &file.text));
+            self.find.show(replacing, selection);
+        }
+
+        let open_symbols = KeyboardShortcut::new(Modifiers::COMMAND, Key::T);
+        if ui.input_mut(|i| i.consume_shortcut(&open_symbols)) {
+            match &self.root {
+                Some(root) => self.symbol_picker.show(root),
+                None => self.status = "Open a project to search its symbols".to_owned(),
+            }
+        }
+        if self.find.open && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape)) {
+            self.find.open = false;
+        }
+    }
+
+    fn tab_strip(&mut self, ui: &mut Ui, theme: &mut egui_extras::syntax_highlighting::CodeTheme) {
+        let mut close = None;
+
+        ui.horizontal(|ui| {
+            ui.menu_button(egui_phosphor::regular::PALETTE, |ui| {
+                theme.ui(ui);
+                theme.clone().store_in_memory(ui.ctx());
+            }).response.on_hover_text("Editor theme");
+
+            if let Some(lsp) = &mut self.lsp {
+                ui.weak(format!("{} {}", egui_phosphor::regular::LIGHTNING, lsp.status()))
+                    .on_hover_text("Python language server: Ctrl+Space completes, F12 or Ctrl+Click goes to definition");
+            }
+
+            let has_unsaved_changes = self.has_unsaved_changes();
+            if ui.add_enabled(has_unsaved_changes, egui::Button::new(egui_phosphor::regular::FLOPPY_DISK_BACK))
+                .on_hover_text("Save all (Ctrl+Shift+S)")
+                .clicked()
+            {
+                self.save_all();
+            }
+            ui.separator();
+
+            egui::ScrollArea::horizontal().id_salt("editor_tabs").show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for (index, file) in self.files.iter().enumerate() {
+                        let label = if file.is_dirty() { format!("{} ●", file.name()) } else { file.name() };
+                        if ui.selectable_label(index == self.active, label).on_hover_text(file.filename()).clicked() {
+                            self.active = index;
+                        }
+                        if ui.small_button(egui_phosphor::regular::X).on_hover_text("Close").clicked() {
+                            close = Some(index);
+                        }
+                        ui.add_space(4.0);
+                    }
+                    if !self.status.is_empty() {
+                        ui.separator();
+                        ui.weak(&self.status);
+                    }
+                });
+            });
+        });
+
+        if let Some(index) = close {
+            self.request_close(index);
+        }
+    }
+
+    fn goto_prompt(&mut self, ctx: &egui::Context) {
+        let Some(input) = &mut self.goto_input else {
+            return;
+        };
+        let lines = self.files.get(self.active).map_or(1, |file| file.text.lines().count().max(1));
+
+        let mut line = None;
+        let response = egui::Modal::new(Id::new("editor_goto_prompt")).show(ctx, |ui| {
+            ui.label("Go to line");
+            let field = ui.add(egui::TextEdit::singleline(input).hint_text(format!("1 - {lines}")).desired_width(160.0));
+            field.request_focus();
+            if field.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                line = Some(input.trim().parse::<usize>().ok());
+            }
+        });
+
+        match line {
+            Some(Some(line)) => {
+                self.goto = Some(line.clamp(1, lines));
+                self.goto_input = None;
+            }
+            Some(None) => {}
+            None if response.should_close() => self.goto_input = None,
+            None => {}
+        }
+    }
+
+    /// The Outline tab: symbols of the active Python file. Returns the one that was clicked.
+    pub fn outline_ui(&mut self, ui: &mut Ui) -> Option<SourceLocation> {
+        let Some(file) = self.files.get(self.active).filter(|file| file.path.extension().is_some_and(|ext| ext == "py")) else {
+            ui.centered_and_justified(|ui| {
+                ui.label("Open a Python file to see its outline.");
+            });
+            return None;
+        };
+        let cursor_line = TextEditState::load(ui.ctx(), editor_id(&file.path))
+            .and_then(|state| state.cursor.char_range())
+            .map(|range| line_of(&file.text, range.primary.index));
+        self.outline.ui(ui, &file.path, cursor_line)
+    }
+
+    /// Line, column and selection of the cursor in the active file.
+    fn status_bar(&self, ui: &mut Ui) {
+        let Some(file) = self.files.get(self.active) else {
+            return;
+        };
+        let Some(range) = TextEditState::load(ui.ctx(), editor_id(&file.path)).and_then(|state| state.cursor.char_range()) else {
+            return;
+        };
+
+        let index = range.primary.index;
+        let line = line_of(&file.text, index);
+        let column = index - line_start(&file.text, line) + 1;
+        let selected = range.primary.index.abs_diff(range.secondary.index);
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if selected > 0 {
+                ui.weak(format!("({selected} selected)"));
+            }
+            ui.weak(format!("Ln {line}, Col {column}"));
+        });
+    }
+
+    fn close_prompt(&mut self, ctx: &egui::Context) {
+        let Some(index) = self.closing else {
+            return;
+        };
+        let Some(file) = self.files.get(index) else {
+            self.closing = None;
+            return;
+        };
+
+        let name = file.name();
+        let mut choice = None;
+        let response = egui::Modal::new(Id::new("editor_close_prompt")).show(ctx, |ui| {
+            ui.heading("Unsaved changes");
+            ui.label(format!("Save the changes to {name} before closing it?"));
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    choice = Some(true);
+                }
+                if ui.button("Don't Save").clicked() {
+                    choice = Some(false);
+                }
+                if ui.button("Cancel").clicked() {
+                    self.closing = None;
+                }
+            });
+        });
+        if response.should_close() {
+            self.closing = None;
+        }
+
+        if let Some(save) = choice {
+            self.closing = None;
+            if !save || self.save(index) {
+                self.close(index);
+            }
+        }
+    }
+
+    /// Shortcuts, the tab strip, prompts and the status bar around the active file.
+    fn chrome(&mut self, ui: &mut Ui, theme: &mut egui_extras::syntax_highlighting::CodeTheme) {
+        self.shortcuts(ui);
+        self.tab_strip(ui, theme);
+        ui.add(egui::Separator::default().grow(5.0));
+        self.close_prompt(ui.ctx());
+        self.goto_prompt(ui.ctx());
+        if let Some(location) = self.symbol_picker.ui(ui.ctx()) {
+            self.goto(&location);
+        }
+        if !self.files.is_empty() {
+            egui::TopBottomPanel::bottom("editor_status").show_inside(ui, |ui| self.status_bar(ui));
+        }
+    }
+
+    /// Holds off closing the app while files have unsaved changes and asks what to do with them.
+    /// Called every frame for the root viewport.
+    pub fn quit_prompt(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| i.viewport().close_requested()) && !self.quit_confirmed && self.has_unsaved_changes() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.quitting = true;
+        }
+        if !self.quitting {
+            return;
+        }
+
+        let dirty = self.files.iter().filter(|file| file.is_dirty()).count();
+        let mut choice = None;
+        let response = egui::Modal::new(Id::new("editor_quit_prompt")).show(ctx, |ui| {
+            ui.heading("Unsaved changes");
+            ui.label(format!("Save the changes to {dirty} files before quitting?"));
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if ui.button("Save All").clicked() {
+                    choice = Some(true);
+                }
+                if ui.button("Don't Save").clicked() {
+                    choice = Some(false);
+                }
+                if ui.button("Cancel").clicked() {
+                    self.quitting = false;
+                }
+            });
+        });
+        if response.should_close() {
+            self.quitting = false;
+        }
+
+        if let Some(save) = choice {
+            self.quitting = false;
+            // A failed save leaves the app open with the error in the status bar.
+            if !save || self.save_all() {
+                self.quit_confirmed = true;
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let mut theme = egui_extras::syntax_highlighting::CodeTheme::from_memory(ui.ctx(), ui.style());
+        self.chrome(ui, &mut theme);
+
+        let goto = self.goto.take();
+        let Some(file) = self.files.get_mut(self.active) else {
+            ui.centered_and_justified(|ui| {
+                ui.label("Open a file from the File Explorer to edit it.");
+            });
+            return;
+        };
+
+        let editor_id = editor_id(&file.path);
+        let found = self.find.ui(ui, &mut file.text, editor_id);
+        let mut lsp = self.lsp.as_mut().filter(|lsp| lsp.is_ready());
+        if let Some(lsp) = &mut lsp {
+            lsp.editor_input(ui, editor_id, &file.path, &mut file.text);
+        }
+        if let Some(cursor) = editing::handle_keys(ui, editor_id, &mut file.text) {
+            if let Some(lsp) = &mut lsp {
+                lsp.edited(&file.path, &file.text, cursor);
+            }
+        }
+        let highlight = self.find.highlight();
+        let folded = file.folded.clone();
+        let brackets = TextEditState::load(ui.ctx(), editor_id)
+            .and_then(|state| state.cursor.char_range())
+            .and_then(|range| editing::matching_brackets(&file.text, range.primary.index));
+
+        let mut layouter = |ui: &egui::Ui, buf: &dyn egui::TextBuffer, wrap_width: f32| {
+            let mut layout_job = egui_extras::syntax_highlighting::highlight(
+                ui.ctx(),
+                ui.style(),
+                &theme,
+                buf.as_str(),
+                "Python",
+            );
+            if let Some((regex, current)) = &highlight {
+                find::highlight_matches(&mut layout_job, &find::find_matches(buf.as_str(), regex), current.as_ref());
+            }
+            editing::highlight_brackets(&mut layout_job, buf.as_str(), brackets);
+            gutter::hide_folded(&mut layout_job, buf.as_str(), &folded);
+            layout_job.wrap.max_width = wrap_width;
+            ui.fonts(|f| f.layout_job(layout_job))
+        };
+
+        let goto = goto.map(|line| goto_line(ui, editor_id, &file.text, line)).or(found);
+
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        let digits = file.text.lines().count().max(99).to_string().len();
+        let numbers_width = ui.fonts(|f| f.glyph_width(&font, '0')) * digits as f32 + 12.0;
+
+        egui::ScrollArea::vertical().id_salt(("editor_scroll", &file.path)).show(ui, |ui| {
+            ui.horizontal_top(|ui| {
+                let (gutter, _) = ui.allocate_exact_size(egui::vec2(GUTTER_WIDTH + numbers_width + FOLD_WIDTH, 0.0), Sense::hover());
+                // Reserved so the current line highlight goes under the text.
+                let current_line_shape = ui.painter().add(egui::Shape::Noop);
+                let output = egui::TextEdit::multiline(&mut file.text)
+                    .id(editor_id)
+                    .font(egui::TextStyle::Monospace) // for cursor height
+                    .code_editor()
+                    .desired_rows(10)
+                    .lock_focus(true)
+                    .desired_width(f32::INFINITY)
+                    .layouter(&mut layouter)
+                    .show(ui);
+
+                decorate(ui, file, &output, gutter, numbers_width, current_line_shape, lsp);
+
+                if let Some(cursor) = goto {
+                    let cursor_rect = output.galley.pos_from_cursor(cursor).translate(output.galley_pos.to_vec2());
+                    ui.scroll_to_rect(cursor_rect, Some(egui::Align::Center));
+                }
+            });
+        });
+    }
+}
+
+
+/// Line numbers, folds, breakpoints, problems and language server popups around the laid out
+/// editor of `file`, and the current line highlight in the reserved `current_line_shape`.
+fn decorate(
+    ui: &Ui,
+    file: &mut OpenFile,
+    output: &TextEditOutput,
+    gutter: Rect,
+    numbers_width: f32,
+    current_line_shape: egui::layers::ShapeIdx,
+    lsp: Option<&mut LspClient>,
+) {
+    let filename = file.filename();
+    let y_range = output.response.rect.y_range();
+    let numbers = Rect::from_x_y_ranges(gutter.left() + GUTTER_WIDTH..=gutter.left() + GUTTER_WIDTH + numbers_width, y_range);
+    let markers = Rect::from_x_y_ranges(numbers.right()..=gutter.right(), y_range);
+    let gutter = Rect::from_x_y_ranges(gutter.left()..=numbers.left(), y_range);
+
+    let cursor_line = output.cursor_range.map(|range| line_of(&file.text, range.primary.index));
+    if let Some(line) = cursor_line {
+        let row = line_rect(output, &file.text, line);
+        let highlight = Rect::from_x_y_ranges(output.response.rect.x_range(), row.y_range());
+        ui.painter().set(current_line_shape, egui::Shape::rect_filled(highlight, 0.0, ui.visuals().text_color().gamma_multiply(0.06)));
+    }
+
+    let folds = gutter::fold_regions(&file.text);
+    file.folded.retain(|header| folds.iter().any(|fold| fold.header == *header));
+    // Typing or moving into a folded block opens it.
+    if let Some(line) = cursor_line {
+        file.folded.retain(|header| !folds.iter().any(|fold| fold.header == *header && (fold.header + 1..=fold.end).contains(&line)));
+    }
+    if let Some(header) = gutter::paint(ui, numbers, markers, output, &folds, &file.folded, cursor_line) {
+        if !file.folded.remove(&header) {
+            file.folded.insert(header);
+        }
+    }
+
+    let breakpoints = debugger::breakpoints_in(&filename);
+    let current_line = debugger::paused_location().filter(|location| location.file == filename).map(|location| location.line);
+    if let Some(line) = breakpoint_gutter(ui, gutter, output, &file.text, &breakpoints, current_line) {
+        debugger::toggle_breakpoint(&filename, line);
+    }
+    diagnostics::paint_problems(ui, gutter, output, &file.text, &diagnostics::problems_in(&file.path));
+    if let Some(lsp) = lsp {
+        lsp.editor_overlay(ui, output, output.response.id, &file.path, &mut file.text);
+    }
+}
@@ -0,0 +1,403 @@
+use std::ops::Range;
+
+use egui::{text::{CCursor, CCursorRange, LayoutJob}, text_edit::TextEditState, Color32, Id, Key, TextFormat, Ui};
+use regex::{Captures, Regex, RegexBuilder};
+
+
+const MATCH_COLOR: Color32 = Color32::from_rgba_premultiplied(70, 60, 0, 70);
+const CURRENT_MATCH_COLOR: Color32 = Color32::from_rgba_premultiplied(150, 90, 0, 150);
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct FindOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Treat the query as a regular expression and allow `$1` style groups in replacements.
+    pub regex: bool,
+}
+
+impl FindOptions {
+    /// Toggle buttons for each option. Returns `true` if any changed.
+    pub fn ui(&mut self, ui: &mut Ui) -> bool {
+        let case = ui.toggle_value(&mut self.case_sensitive, "Aa").on_hover_text("Match case");
+        let word = ui.toggle_value(&mut self.whole_word, "ab").on_hover_text("Match whole word");
+        let regex = ui.toggle_value(&mut self.regex, ".*").on_hover_text("Use regular expression");
+        case.changed() || word.changed() || regex.changed()
+    }
+}
+
+/// Compiles `query` into a regex honouring `options`. `None` for an empty query.
+pub fn build_regex(query: &str, options: FindOptions) -> Option<Result<Regex, String>> {
+    if query.is_empty() {
+        return None;
+    }
+
+    let pattern = if options.regex { query.to_owned() } else { regex::escape(query) };
+    let pattern = if options.whole_word { format!(r"\b(?:{pattern})\b") } else { pattern };
+    Some(
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .build()
+            .map_err(|e| e.to_string()),
+    )
+}
+
+/// Byte ranges of every non-empty match of `regex` in `text`.
+pub fn find_matches(text: &str, regex: &Regex) -> Vec<Range<usize>> {
+    regex.find_iter(text).filter(|m| !m.is_empty()).map(|m| m.range()).collect()
+}
+
+fn expand(caps: &Captures<'_>, replacement: &str, options: FindOptions) -> String {
+    if options.regex {
+        let mut expanded = String::new();
+        caps.expand(replacement, &mut expanded);
+        expanded
+    } else {
+        replacement.to_owned()
+    }
+}
+
+/// What the match at `range` of `text` is replaced with, with groups expanded in regex mode.
+pub fn replacement_for(text: &str, regex: &Regex, range: &Range<usize>, replacement: &str, options: FindOptions) -> String {
+    match regex.captures_at(text, range.start) {
+        Some(caps) => expand(&caps, replacement, options),
+        None => replacement.to_owned(),
+    }
+}
+
+/// `text` with every non-empty match of `regex` replaced.
+pub fn replace_all(text: &str, regex: &Regex, replacement: &str, options: FindOptions) -> String {
+    regex
+        .replace_all(text, |caps: &Captures<'_>| {
+            // Empty matches are never listed, so leave them untouched.
+            if caps[0].is_empty() { String::new() } else { expand(caps, replacement, options) }
+        })
+        .into_owned()
+}
+
+/// Splits the sections of `job` at the edges of `ranges` (sorted byte ranges) and calls
+/// `style` with the range's index on the format of each piece inside one.
+pub fn restyle_ranges(job: &mut LayoutJob, ranges: &[Range<usize>], mut style: impl FnMut(usize, &mut TextFormat)) {
+    if ranges.is_empty() {
+        return;
+    }
+
+    let mut sections = Vec::with_capacity(job.sections.len() + ranges.len() * 2);
+    for section in std::mem::take(&mut job.sections) {
+        let Range { start: mut from, end } = section.byte_range.clone();
+        let mut leading_space = section.leading_space;
+        let mut push = |range: Range<usize>, styled: Option<usize>| {
+            let mut piece = section.clone();
+            piece.byte_range = range;
+            piece.leading_space = std::mem::take(&mut leading_space);
+            if let Some(index) = styled {
+                style(index, &mut piece.format);
+            }
+            sections.push(piece);
+        };
+
+        let first = ranges.partition_point(|range| range.end <= from);
+        for (index, range) in ranges.iter().enumerate().skip(first).take_while(|(_, range)| range.start < end) {
+            if range.start > from {
+                push(from..range.start, None);
+            }
+            let to = range.end.min(end);
+            push(from.max(range.start)..to, Some(index));
+            from = to;
+        }
+        if from < end {
+            push(from..end, None);
+        }
+    }
+    job.sections = sections;
+}
+
+/// Gives `matches` (sorted byte ranges) a background in a laid out `job`, `current` stronger.
+pub fn highlight_matches(job: &mut LayoutJob, matches: &[Range<usize>], current: Option<&Range<usize>>) {
+    restyle_ranges(job, matches, |index, format| {
+        format.background = if Some(&matches[index]) == current { CURRENT_MATCH_COLOR } else { MATCH_COLOR };
+    });
+}
+
+
+/// Char index of the byte offset `byte` of `text`.
+pub fn char_index(text: &str, byte: usize) -> usize {
+    text[..byte].chars().count()
+}
+
+/// Byte offset of the char index `char_index` of `text`.
+pub fn byte_index(text: &str, char_index: usize) -> usize {
+    text.char_indices().nth(char_index).map_or(text.len(), |(byte, _)| byte)
+}
+
+/// Records `text` as an undo point of the `TextEdit` with `id`, so an edit made from outside
+/// it can be undone with Ctrl+Z like any other.
+pub fn push_undo(ui: &Ui, id: Id, text: &str) {
+    let mut state = TextEditState::load(ui.ctx(), id).unwrap_or_default();
+    let cursor = state.cursor.char_range().unwrap_or(CCursorRange::one(CCursor::default()));
+    let mut undoer = state.undoer();
+    undoer.add_undo(&(cursor, text.to_owned()));
+    state.set_undoer(undoer);
+    state.store(ui.ctx(), id);
+}
+
+/// Selects the bytes `range` of `text` in the `TextEdit` with `id`.
+fn select(ui: &Ui, id: Id, text: &str, range: &Range<usize>) -> CCursor {
+    let start = CCursor::new(char_index(text, range.start));
+    let end = CCursor::new(char_index(text, range.end));
+    let mut state = TextEditState::load(ui.ctx(), id).unwrap_or_default();
+    state.cursor.set_char_range(Some(CCursorRange::two(start, end)));
+    state.store(ui.ctx(), id);
+    start
+}
+
+/// The selected text of the `TextEdit` with `id`, if it is on a single line.
+pub fn selected_text(ctx: &egui::Context, id: Id, text: &str) -> Option<String> {
+    let range = TextEditState::load(ctx, id)?.cursor.char_range()?;
+    let start = range.primary.index.min(range.secondary.index);
+    let end = range.primary.index.max(range.secondary.index);
+    let selected: String = text.chars().skip(start).take(end - start).collect();
+    Some(selected).filter(|selected| !selected.is_empty() && !selected.contains('\n'))
+}
+
+
+enum FindAction {
+    /// The query or options changed.
+    Refresh,
+    Next,
+    Previous,
+    Replace,
+    ReplaceAll,
+}
+
+/// The find (Ctrl+F) and replace (Ctrl+H) bar above the editor.
+#[derive(Default)]
+pub struct FindBar {
+    pub open: bool,
+    pub replacing: bool,
+    query: String,
+    replacement: String,
+    options: FindOptions,
+    regex: Option<Result<Regex, String>>,
+    /// Byte range of the selected match.
+    current: Option<Range<usize>>,
+    focus_query: bool,
+}
+
+impl FindBar {
+    /// Opens the bar, replacing the query with `selection` if there is one.
+    pub fn show(&mut self, replacing: bool, selection: Option<String>) {
+        if let Some(selection) = selection {
+            self.query = if self.options.regex { regex::escape(&selection) } else { selection };
+            self.regex = build_regex(&self.query, self.options);
+        }
+        self.open = true;
+        self.replacing = replacing;
+        self.focus_query = true;
+    }
+
+    /// The compiled query and selected match, for highlighting, while the bar is open.
+    pub fn highlight(&self) -> Option<(Regex, Option<Range<usize>>)> {
+        match (&self.regex, self.open) {
+            (Some(Ok(regex)), true) => Some((regex.clone(), self.current.clone())),
+            _ => None,
+        }
+    }
+
+    /// Draws the bar and applies its actions to `text`, the contents of the `TextEdit` with `id`.
+    /// Returns the cursor of a newly selected match so the caller can scroll to it.
+    pub fn ui(&mut self, ui: &mut Ui, text: &mut String, id: Id) -> Option<CCursor> {
+        if !self.open {
+            return None;
+        }
+
+        let action = self.draw(ui, text);
+        self.apply(ui, text, id, action)
+    }
+
+    /// The find row and, while replacing, the replace row. Returns the action picked in them.
+    fn draw(&mut self, ui: &mut Ui, text: &str) -> Option<FindAction> {
+        let mut action = None;
+        ui.horizontal(|ui| {
+            let query = ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .hint_text("Find")
+                    .desired_width(220.0),
+            );
+            if std::mem::take(&mut self.focus_query) {
+                query.request_focus();
+            }
+            if query.changed() {
+                action = Some(FindAction::Refresh);
+            }
+            if query.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                action = Some(if ui.input(|i| i.modifiers.shift) { FindAction::Previous } else { FindAction::Next });
+                query.request_focus();
+            }
+
+            if self.options.ui(ui) {
+                action = Some(FindAction::Refresh);
+            }
+            ui.separator();
+
+            match &self.regex {
+                Some(Err(e)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, "Invalid regex").on_hover_text(e);
+                }
+                Some(Ok(regex)) => {
+                    let matches = find_matches(text, regex);
+                    let position = self.current.as_ref().and_then(|current| matches.iter().position(|m| m == current));
+                    match (position, matches.len()) {
+                        (_, 0) => ui.weak("No results"),
+                        (Some(index), count) => ui.label(format!("{} of {count}", index + 1)),
+                        (None, count) => ui.label(format!("{count} matches")),
+                    };
+                }
+                None => {}
+            }
+
+            if ui.small_button(egui_phosphor::regular::ARROW_UP).on_hover_text("Previous match (Shift+Enter)").clicked() {
+                action = Some(FindAction::Previous);
+            }
+            if ui.small_button(egui_phosphor::regular::ARROW_DOWN).on_hover_text("Next match (Enter)").clicked() {
+                action = Some(FindAction::Next);
+            }
+            ui.toggle_value(&mut self.replacing, egui_phosphor::regular::SWAP).on_hover_text("Replace (Ctrl+H)");
+            if ui.small_button(egui_phosphor::regular::X).on_hover_text("Close (Esc)").clicked() {
+                self.open = false;
+            }
+        });
+
+        if self.replacing {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.replacement)
+                        .hint_text(if self.options.regex { "Replace ($1 for groups)" } else { "Replace" })
+                        .desired_width(220.0),
+                );
+                if ui.button("Replace").clicked() {
+                    action = Some(FindAction::Replace);
+                }
+                if ui.button("Replace All").clicked() {
+                    action = Some(FindAction::ReplaceAll);
+                }
+            });
+        }
+        ui.add(egui::Separator::default().grow(5.0));
+        action
+    }
+
+    /// Keeps the selected match in step with `text` and carries out `action`.
+    fn apply(&mut self, ui: &Ui, text: &mut String, id: Id, action: Option<FindAction>) -> Option<CCursor> {
+        if matches!(action, Some(FindAction::Refresh)) {
+            self.regex = build_regex(&self.query, self.options);
+        }
+        let Some(Ok(regex)) = self.regex.clone() else {
+            self.current = None;
+            return None;
+        };
+        let matches = find_matches(text, &regex);
+        if self.current.as_ref().is_some_and(|current| !matches.contains(current)) {
+            self.current = None;
+        }
+
+        // Searches continue from the selected match, or from the cursor when there is none.
+        let anchor = match &self.current {
+            Some(current) => current.start,
+            None => TextEditState::load(ui.ctx(), id)
+                .and_then(|state| state.cursor.char_range())
+                .map_or(0, |range| byte_index(text, range.primary.index.min(range.secondary.index))),
+        };
+        let from = |matches: &[Range<usize>], position: usize| {
+            matches.iter().find(|m| m.start >= position).or(matches.first()).cloned()
+        };
+
+        let selected = match action? {
+            FindAction::Refresh => from(&matches, anchor),
+            FindAction::Next => from(&matches, anchor + usize::from(self.current.is_some())),
+            FindAction::Previous => matches.iter().rev().find(|m| m.start < anchor).or(matches.last()).cloned(),
+            FindAction::Replace => {
+                let Some(current) = self.current.take() else {
+                    self.current = from(&matches, anchor);
+                    return self.current.as_ref().map(|range| select(ui, id, text, range));
+                };
+                let replacement = replacement_for(text, &regex, &current, &self.replacement, self.options);
+                push_undo(ui, id, text);
+                text.replace_range(current.clone(), &replacement);
+                from(&find_matches(text, &regex), current.start + replacement.len())
+            }
+            FindAction::ReplaceAll => {
+                if !matches.is_empty() {
+                    push_undo(ui, id, text);
+                    *text = replace_all(text, &regex, &self.replacement, self.options);
+                }
+                self.current = None;
+                return None;
+            }
+        };
+
+        self.current = selected;
+        self.current.as_ref().map(|range| select(ui, id, text, range))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{build_regex, find_matches, replace_all, replacement_for, FindOptions};
+
+    const LITERAL: FindOptions = FindOptions { case_sensitive: false, whole_word: false, regex: false };
+    const REGEX: FindOptions = FindOptions { case_sensitive: false, whole_word: false, regex: true };
+
+    fn matches(query: &str, options: FindOptions, text: &str) -> Vec<String> {
+        let regex = build_regex(query, options).unwrap().unwrap();
+        find_matches(text, &regex).into_iter().map(|range| text[range].to_owned()).collect()
+    }
+
+    #[test]
+    fn empty_and_invalid_queries() {
+        assert!(build_regex("", REGEX).is_none());
+        assert!(build_regex("(", REGEX).unwrap().is_err());
+        // Literal queries are escaped.
+        assert_eq!(matches("a.b(", LITERAL, "a.b( axb("), ["a.b("]);
+    }
+
+    #[test]
+    fn case_sensitivity() {
+        assert_eq!(matches("speed", LITERAL, "Speed speed SPEED"), ["Speed", "speed", "SPEED"]);
+        let options = FindOptions { case_sensitive: true, ..LITERAL };
+        assert_eq!(matches("speed", options, "Speed speed SPEED"), ["speed"]);
+    }
+
+    #[test]
+    fn whole_words() {
+        let options = FindOptions { whole_word: true, ..LITERAL };
+        assert_eq!(matches("x", options, "x max x_pos (x)"), ["x", "x"]);
+        // Alternatives in a regex are all bounded, not just the first and last.
+        let options = FindOptions { whole_word: true, ..REGEX };
+        assert_eq!(matches("up|down", options, "up upper down sundown"), ["up", "down"]);
+    }
+
+    #[test]
+    fn regex_replacements_expand_groups() {
+        let regex = build_regex(r"self\.(\w+)", REGEX).unwrap().unwrap();
+        let text = "self.x = self.y";
+        assert_eq!(replace_all(text, &regex, "${1}_", REGEX), "x_ = y_");
+        assert_eq!(replacement_for(text, &regex, &(9..15), "game.$1", REGEX), "game.y");
+    }
+
+    #[test]
+    fn literal_replacements_keep_dollar_signs() {
+        let regex = build_regex("price", LITERAL).unwrap().unwrap();
+        let text = "price = Price";
+        assert_eq!(replace_all(text, &regex, "$1 cost", LITERAL), "$1 cost = $1 cost");
+        assert_eq!(replacement_for(text, &regex, &(8..13), "$0", LITERAL), "$0");
+    }
+
+    #[test]
+    fn empty_matches_are_left_alone() {
+        let regex = build_regex("x*", REGEX).unwrap().unwrap();
+        assert_eq!(find_matches("axxb", &regex), [1..3]);
+        assert_eq!(replace_all("axxb", &regex, "-", REGEX), "a-b");
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::Range;
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use egui::{pos2, vec2, Color32, ColorImage, Context, Rect, Sense, Stroke, TextureHandle, TextureOptions, Ui};
+
+use crate::engine::helpers;
+
+
+/// Number of recent frames kept as thumbnails in the filmstrip.
+const THUMBNAIL_FRAMES: usize = 600;
+/// Number of the most recent frames also kept at full size, about half a second at 60 fps.
+const FULL_FRAMES: usize = 30;
+const THUMBNAIL_HEIGHT: usize = 48;
+/// Height the filmstrip takes under the viewport, including its scrollbar.
+pub const FILMSTRIP_HEIGHT: f32 = 64.0;
+/// Frames waiting to be thumbnailed at most. The game thread drops frames instead of waiting
+/// when the recorder falls behind.
+const QUEUED_FRAMES: usize = 8;
+
+/// Bumped whenever recorded frames are replaced, so the filmstrip re-uploads its thumbnails.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static::lazy_static! {
+    static ref HISTORY: std::sync::Mutex<VecDeque<HistoryFrame>> = std::sync::Mutex::new(VecDeque::new());
+    static ref RECORDER: mpsc::SyncSender<Recorded> = spawn_recorder();
+}
+
+
+struct HistoryFrame {
+    frame: usize,
+    thumbnail: ColorImage,
+    full: Option<Arc<ColorImage>>,
+}
+
+enum Recorded {
+    Frame(usize, ColorImage),
+    Clear,
+}
+
+/// Thumbnails and stores frames on their own thread, so the game thread only pays for a copy.
+fn spawn_recorder() -> mpsc::SyncSender<Recorded> {
+    let (sender, receiver) = mpsc::sync_channel(QUEUED_FRAMES);
+    std::thread::spawn(move || {
+        for recorded in receiver {
+            match recorded {
+                Recorded::Frame(frame, image) => store(frame, image),
+                Recorded::Clear => {
+                    HISTORY.lock().unwrap().clear();
+                    GENERATION.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    });
+    sender
+}
+
+fn store(frame: usize, image: ColorImage) {
+    let thumbnail = helpers::thumbnail(&image, THUMBNAIL_HEIGHT);
+
+    let mut history = HISTORY.lock().unwrap();
+    // Frames replayed after a rewind replace the ones recorded the first time round.
+    if history.back().is_some_and(|entry| entry.frame >= frame) {
+        history.retain(|entry| entry.frame < frame);
+        GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+    history.push_back(HistoryFrame { frame, thumbnail, full: Some(Arc::new(image)) });
+    while history.len() > THUMBNAIL_FRAMES {
+        history.pop_front();
+    }
+
+    let len = history.len();
+    if let Some(expired) = len.checked_sub(FULL_FRAMES + 1).and_then(|i| history.get_mut(i)) {
+        expired.full = None;
+    }
+}
+
+/// Stores a rendered frame. Called by the runner for every stepped frame.
+pub fn record(frame: usize, image: &ColorImage) {
+    RECORDER.try_send(Recorded::Frame(frame, image.clone())).ok();
+}
+
+pub fn clear() {
+    // Sent through the queue so frames of the previous run still waiting there are dropped too.
+    RECORDER.send(Recorded::Clear).ok();
+}
+
+fn is_recorded(frame: usize) -> bool {
+    HISTORY.lock().unwrap().iter().any(|entry| entry.frame == frame)
+}
+
+fn full_frame(frame: usize) -> Option<Arc<ColorImage>> {
+    HISTORY.lock().unwrap().iter().find(|entry| entry.frame == frame).and_then(|entry| entry.full.clone())
+}
+
+fn thumbnail(frame: usize) -> Option<ColorImage> {
+    HISTORY.lock().unwrap().iter().find(|entry| entry.frame == frame).map(|entry| entry.thumbnail.clone())
+}
+
+/// The recorded frame before or after `frame`.
+fn neighbour(frame: usize, forward: bool) -> Option<usize> {
+    let history = HISTORY.lock().unwrap();
+    let mut frames = history.iter().map(|entry| entry.frame);
+    if forward {
+        frames.find(|&f| f > frame)
+    } else {
+        frames.take_while(|&f| f < frame).last()
+    }
+}
+
+/// Highlights pixels that differ between two frames over a dimmed copy of `new`.
+/// Returns `None` when the frames are different sizes.
+pub fn diff_image(old: &ColorImage, new: &ColorImage) -> Option<(ColorImage, usize)> {
+    if old.size != new.size {
+        return None;
+    }
+
+    let mut changed = 0;
+    let bytes: Vec<u8> = old.pixels.iter().zip(&new.pixels).flat_map(|(a, b)| {
+        if a == b {
+            [b.r() / 4, b.g() / 4, b.b() / 4, 255]
+        } else {
+            changed += 1;
+            [255, 0, 255, 255]
+        }
+    }).collect();
+
+    Some((ColorImage::from_rgba_unmultiplied(new.size, &bytes), changed))
+}
+
+
+/// Filmstrip of recently rendered frames and a window inspecting one of them.
+#[derive(Default)]
+pub struct FrameHistoryView {
+    pub show_filmstrip: bool,
+    selected: Option<usize>,
+    show_diff: bool,
+    /// Textures of the thumbnails currently scrolled into view.
+    thumbnails: BTreeMap<usize, TextureHandle>,
+    generation: usize,
+    /// Texture shown in the inspector, keyed by the frame and whether it is the diff view.
+    inspected: Option<((usize, bool), TextureHandle, String)>,
+}
+
+impl FrameHistoryView {
+    /// Uploads thumbnails for the history entries at `visible` and drops the textures of
+    /// frames scrolled out of view. Returns the recorded frame at each visible index.
+    fn sync_thumbnails(&mut self, ctx: &Context, visible: Range<usize>) -> Vec<(usize, usize)> {
+        let generation = GENERATION.load(Ordering::Relaxed);
+        if generation != self.generation {
+            self.generation = generation;
+            self.thumbnails.clear();
+        }
+
+        let history = HISTORY.lock().unwrap();
+        let frames: Vec<(usize, usize)> = visible.filter_map(|i| history.get(i).map(|entry| (i, entry.frame))).collect();
+        self.thumbnails.retain(|frame, _| frames.iter().any(|(_, f)| f == frame));
+
+        for &(i, frame) in &frames {
+            self.thumbnails.entry(frame).or_insert_with(|| {
+                ctx.load_texture(format!("frame_thumbnail_{frame}"), history[i].thumbnail.clone(), TextureOptions::LINEAR)
+            });
+        }
+        frames
+    }
+
+    /// The strip under the viewport, newest frame on the right. Clicking a frame inspects it.
+    pub fn filmstrip(&mut self, ui: &mut Ui) {
+        let (count, thumbnail_size) = {
+            let history = HISTORY.lock().unwrap();
+            let size = history.front().map(|entry| entry.thumbnail.size);
+            (history.len(), size)
+        };
+        let Some([width, height]) = thumbnail_size else {
+            ui.label("Rendered frames appear here.");
+            return;
+        };
+        let thumbnail_size = vec2(width as f32, height as f32);
+        let item_width = thumbnail_size.x + ui.spacing().item_spacing.x;
+
+        egui::ScrollArea::horizontal()
+            .id_salt("frame_history_filmstrip")
+            .max_height(FILMSTRIP_HEIGHT)
+            .stick_to_right(true)
+            .show_viewport(ui, |ui, viewport| {
+                ui.set_width(item_width * count as f32);
+                ui.set_height(thumbnail_size.y);
+
+                // Only the thumbnails in view are laid out and kept on the GPU.
+                let first = (viewport.min.x / item_width).floor().max(0.0) as usize;
+                let last = ((viewport.max.x / item_width).ceil().max(0.0) as usize).min(count);
+                let origin = ui.max_rect().min;
+
+                for (i, frame) in self.sync_thumbnails(ui.ctx(), first..last) {
+                    let rect = Rect::from_min_size(origin + vec2(i as f32 * item_width, 0.0), thumbnail_size);
+                    let response = ui.interact(rect, ui.id().with(("frame_thumbnail", frame)), Sense::click())
+                        .on_hover_text(format!("Frame {frame}"));
+
+                    if let Some(texture) = self.thumbnails.get(&frame) {
+                        let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+                        ui.painter().image(texture.id(), rect, uv, Color32::WHITE);
+                    }
+                    if self.selected == Some(frame) {
+                        ui.painter().rect_stroke(rect, 0.0, Stroke::new(2.0, Color32::YELLOW), egui::StrokeKind::Outside);
+                    }
+                    if response.clicked() {
+                        self.selected = Some(frame);
+                    }
+                }
+            });
+    }
+
+    /// Builds the texture for the inspector, falling back to the thumbnail once the full
+    /// frame has been dropped.
+    fn inspected_texture(&self, ctx: &Context, frame: usize) -> Option<(TextureHandle, String)> {
+        let load = |image: ColorImage| ctx.load_texture("frame_history_inspected", image, TextureOptions::NEAREST);
+        let Some(full) = full_frame(frame) else {
+            let texture = load(thumbnail(frame)?);
+            return Some((texture, "Only the thumbnail of this frame is still kept.".to_owned()));
+        };
+
+        if !self.show_diff {
+            return Some((load((*full).clone()), String::new()));
+        }
+
+        let previous = frame.checked_sub(1).and_then(full_frame);
+        Some(match previous.and_then(|previous| diff_image(&previous, &full)) {
+            Some((diff, 0)) => (load(diff), format!("No pixels changed since frame {}.", frame - 1)),
+            Some((diff, changed)) => (load(diff), format!("{changed} pixels changed since frame {}.", frame - 1)),
+            None => (load((*full).clone()), "The previous frame is not kept at full size.".to_owned()),
+        })
+    }
+
+    /// Shows the selected frame full size.
+    pub fn inspector(&mut self, ctx: &Context) {
+        let Some(frame) = self.selected else {
+            return;
+        };
+
+        let key = (frame, self.show_diff);
+        if !is_recorded(frame) {
+            self.selected = None;
+            self.inspected = None;
+            return;
+        }
+        if self.inspected.as_ref().is_none_or(|(inspected, ..)| *inspected != key) {
+            self.inspected = self.inspected_texture(ctx, frame).map(|(texture, note)| (key, texture, note));
+        }
+
+        let mut open = true;
+        egui::Window::new(format!("Frame {frame}"))
+            .id(egui::Id::new("frame_history_inspector"))
+            .open(&mut open)
+            .default_size([660.0, 420.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button(egui_phosphor::regular::CARET_LEFT).on_hover_text("Previous frame").clicked() {
+                        self.selected = neighbour(frame, false).or(Some(frame));
+                    }
+                    if ui.button(egui_phosphor::regular::CARET_RIGHT).on_hover_text("Next frame").clicked() {
+                        self.selected = neighbour(frame, true).or(Some(frame));
+                    }
+                    ui.checkbox(&mut self.show_diff, "Diff against previous frame");
+                });
+
+                if let Some((_, texture, note)) = &self.inspected {
+                    if !note.is_empty() {
+                        ui.label(note);
+                    }
+                    ui.add(egui::Image::new(texture).shrink_to_fit());
+                }
+            });
+
+        if !open {
+            self.selected = None;
+            self.inspected = None;
+        }
+    }
+}
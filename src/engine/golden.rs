@@ -0,0 +1,281 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use image::{Rgba, RgbaImage};
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{helpers, python::{self, GameSession}};
+
+
+/// Folder inside a project that holds `golden.ron` and the reference frames.
+pub const GOLDEN_DIR: &str = "golden";
+const CONFIG_FILE: &str = "golden.ron";
+const OUTPUT_DIR: &str = "out";
+
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    Down,
+    Up,
+    Press,
+}
+
+/// A key event posted to pygame right before `frame` is stepped. Frames are numbered from 1,
+/// the first frame the game renders, as are the frames listed in `captures`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScriptedInput {
+    pub frame: usize,
+    pub key: String,
+    pub action: KeyAction,
+}
+
+/// Contents of `golden/golden.ron`.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct GoldenConfig {
+    pub script: PathBuf,
+    pub seed: u64,
+    /// Frames to compare against their references, numbered from 1 like `ScriptedInput::frame`.
+    pub captures: Vec<usize>,
+    pub inputs: Vec<ScriptedInput>,
+    /// Largest per-channel difference that still counts as a matching pixel.
+    pub tolerance: u8,
+    /// Number of pixels allowed to exceed `tolerance` before a frame fails.
+    pub max_mismatched: usize,
+}
+
+impl Default for GoldenConfig {
+    fn default() -> Self {
+        Self {
+            script: PathBuf::from("main.py"),
+            seed: 0,
+            captures: vec![],
+            inputs: vec![],
+            tolerance: 0,
+            max_mismatched: 0,
+        }
+    }
+}
+
+impl GoldenConfig {
+    pub fn load(project: &Path) -> Result<Self, String> {
+        let path = project.join(GOLDEN_DIR).join(CONFIG_FILE);
+        let text = fs::read_to_string(&path).map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        let config: Self = ron::from_str(&text).map_err(|e| format!("Could not parse {}: {e}", path.display()))?;
+
+        // Frame 0 is never stepped, so an input or capture there would silently do nothing.
+        if config.captures.contains(&0) || config.inputs.iter().any(|input| input.frame == 0) {
+            return Err(format!("{}: frames are numbered from 1, frame 0 never runs", path.display()));
+        }
+        Ok(config)
+    }
+}
+
+
+pub struct FrameDiff {
+    pub mismatched: usize,
+    pub max_delta: u8,
+    pub diff: RgbaImage,
+}
+
+/// Compares two frames pixel by pixel. Pixels over `tolerance` are painted red in `diff`,
+/// everything else is a dimmed copy of `actual`.
+pub fn compare(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> Option<FrameDiff> {
+    if actual.dimensions() != expected.dimensions() {
+        return None;
+    }
+
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut mismatched = 0;
+    let mut max_delta = 0;
+
+    for ((a, e), d) in actual.pixels().zip(expected.pixels()).zip(diff.pixels_mut()) {
+        let delta = a.0.iter().zip(e.0).map(|(a, e)| a.abs_diff(e)).max().unwrap_or(0);
+        max_delta = max_delta.max(delta);
+
+        *d = if delta > tolerance {
+            mismatched += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let luma = (a.0[0] as u16 + a.0[1] as u16 + a.0[2] as u16) / 12;
+            Rgba([luma as u8, luma as u8, luma as u8, 255])
+        };
+    }
+
+    Some(FrameDiff { mismatched, max_delta, diff })
+}
+
+
+fn reference_path(project: &Path, frame: usize) -> PathBuf {
+    project.join(GOLDEN_DIR).join(format!("frame_{frame:05}.png"))
+}
+
+fn post_inputs(py: Python<'_>, inputs: &[ScriptedInput], frame: usize) -> PyResult<()> {
+    for input in inputs.iter().filter(|i| i.frame == frame) {
+        if input.action != KeyAction::Up {
+            python::post_key_event(py, &input.key, true)?;
+        }
+        if input.action != KeyAction::Down {
+            python::post_key_event(py, &input.key, false)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs the project's game with the configured seed and inputs, returning the captured frames.
+fn capture(project: &Path, config: &GoldenConfig) -> Result<Vec<(usize, RgbaImage)>, String> {
+    let script = project.join(&config.script);
+    let code = fs::read_to_string(&script).map_err(|e| format!("Could not read {}: {e}", script.display()))?;
+    let last_frame = config.captures.iter().copied().max().unwrap_or(0);
+
+    pyo3::prepare_freethreaded_python();
+
+    Python::with_gil(|py| {
+        let run = || -> PyResult<Vec<(usize, RgbaImage)>> {
+            python::seed_random(py, config.seed)?;
+            let mut session = GameSession::launch(py, &code, &script.display().to_string(), python::DEFAULT_FRAME_SIZE)?;
+            let mut frames = vec![];
+
+            while session.frame < last_frame {
+                post_inputs(py, &config.inputs, session.frame + 1)?;
+                let Some(image) = session.step(py)? else {
+                    break;
+                };
+                if config.captures.contains(&session.frame) {
+                    frames.push((session.frame, helpers::to_rgba_image(&image)));
+                }
+            }
+            Ok(frames)
+        };
+
+        run().map_err(|e| {
+            e.print(py);
+            format!("{} raised an exception", script.display())
+        })
+    })
+}
+
+/// Writes the captured frames as the new references.
+pub fn update(project: &Path) -> Result<String, String> {
+    let config = GoldenConfig::load(project)?;
+
+    let frames = capture(project, &config)?;
+    for (frame, image) in &frames {
+        let path = reference_path(project, *frame);
+        image.save(&path).map_err(|e| format!("Could not write {}: {e}", path.display()))?;
+    }
+
+    Ok(format!("Updated {} reference frames", frames.len()))
+}
+
+/// Compares captured frames against the references. Diff images and `report.txt` are
+/// written to `golden/out` whenever a frame does not match.
+pub fn check(project: &Path, tolerance: Option<u8>) -> Result<String, String> {
+    let config = GoldenConfig::load(project)?;
+    let tolerance = tolerance.unwrap_or(config.tolerance);
+    let out = project.join(GOLDEN_DIR).join(OUTPUT_DIR);
+
+    let mut report = vec![];
+    let mut failures = 0;
+
+    let frames = capture(project, &config)?;
+    for &frame in &config.captures {
+        let Some((_, actual)) = frames.iter().find(|(f, _)| *f == frame) else {
+            failures += 1;
+            report.push(format!("frame {frame}: FAILED, game ended before this frame"));
+            continue;
+        };
+
+        let expected = match image::open(reference_path(project, frame)) {
+            Ok(expected) => expected.to_rgba8(),
+            Err(e) => {
+                failures += 1;
+                report.push(format!("frame {frame}: FAILED, no reference ({e})"));
+                continue;
+            }
+        };
+
+        match compare(actual, &expected, tolerance) {
+            Some(diff) if diff.mismatched <= config.max_mismatched => {
+                report.push(format!("frame {frame}: ok ({} pixels over tolerance)", diff.mismatched));
+            }
+            Some(diff) => {
+                failures += 1;
+                report.push(format!(
+                    "frame {frame}: FAILED, {} pixels over tolerance {tolerance} (max delta {})",
+                    diff.mismatched, diff.max_delta
+                ));
+                fs::create_dir_all(&out).map_err(|e| format!("Could not create {}: {e}", out.display()))?;
+                for (suffix, image) in [("actual", actual), ("diff", &diff.diff)] {
+                    let path = out.join(format!("frame_{frame:05}_{suffix}.png"));
+                    image.save(&path).map_err(|e| format!("Could not write {}: {e}", path.display()))?;
+                }
+            }
+            None => {
+                failures += 1;
+                report.push(format!(
+                    "frame {frame}: FAILED, size {:?} does not match reference {:?}",
+                    actual.dimensions(),
+                    expected.dimensions()
+                ));
+            }
+        }
+    }
+
+    report.push(format!("{} of {} frames matched", config.captures.len() - failures, config.captures.len()));
+
+    let report = report.join("\n");
+    if failures == 0 {
+        return Ok(report);
+    }
+
+    let path = out.join("report.txt");
+    fs::create_dir_all(&out)
+        .and_then(|()| fs::write(&path, &report))
+        .map_err(|e| format!("Could not write {}: {e}", path.display()))?;
+    Err(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::compare;
+
+    fn filled(width: u32, height: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba([value, value, value, 255]))
+    }
+
+    #[test]
+    fn differences_within_tolerance_match() {
+        let diff = compare(&filled(4, 4, 100), &filled(4, 4, 103), 3).unwrap();
+        assert_eq!(diff.mismatched, 0);
+        assert_eq!(diff.max_delta, 3);
+
+        let diff = compare(&filled(4, 4, 100), &filled(4, 4, 104), 3).unwrap();
+        assert_eq!(diff.mismatched, 16);
+        assert_eq!(diff.max_delta, 4);
+    }
+
+    #[test]
+    fn only_differing_pixels_are_counted_and_painted_red() {
+        let expected = filled(3, 2, 50);
+        let mut actual = expected.clone();
+        actual.put_pixel(1, 0, Rgba([50, 90, 50, 255]));
+        actual.put_pixel(2, 1, Rgba([50, 50, 50, 0]));
+
+        let diff = compare(&actual, &expected, 10).unwrap();
+        assert_eq!(diff.mismatched, 2);
+        assert_eq!(diff.max_delta, 255);
+        assert_eq!(*diff.diff.get_pixel(1, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(*diff.diff.get_pixel(2, 1), Rgba([255, 0, 0, 255]));
+        assert_ne!(*diff.diff.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn frames_of_different_sizes_are_not_compared() {
+        assert!(compare(&filled(4, 4, 0), &filled(4, 3, 0), 255).is_none());
+        assert!(compare(&filled(3, 4, 0), &filled(4, 4, 0), 255).is_none());
+    }
+}
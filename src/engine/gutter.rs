@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+use std::ops::{Range, RangeInclusive};
+
+use egui::{pos2, text::LayoutJob, text_edit::TextEditOutput, Color32, Rect, Sense, Ui};
+
+use crate::engine::find;
+
+
+/// Width of the strip holding fold markers, right of the line numbers.
+pub const FOLD_WIDTH: f32 = 14.0;
+/// Folded lines are laid out this small so their rows take no visible space.
+const FOLDED_FONT_SIZE: f32 = 0.1;
+
+/// Statements that open an indented block which can be folded.
+const BLOCK_KEYWORDS: [&str; 14] = [
+    "class", "def", "async", "if", "elif", "else", "for", "while", "with", "try", "except", "finally", "match", "case",
+];
+
+
+/// A foldable Python block.
+pub struct Fold {
+    /// Line of the statement opening the block, 1-based.
+    pub header: usize,
+    /// Last non-blank line of the block's body.
+    pub end: usize,
+}
+
+/// Indentation of `line`, or `None` for blank and comment-only lines, which do not end blocks.
+fn indent_of(line: &str) -> Option<usize> {
+    let code = line.trim_start();
+    (!code.is_empty() && !code.starts_with('#')).then_some(line.len() - code.len())
+}
+
+/// Blocks in `text` opened by a line like `def f():` or `for x in y:`, found by indentation.
+pub fn fold_regions(text: &str) -> Vec<Fold> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut folds = vec![];
+
+    for (index, line) in lines.iter().enumerate() {
+        let Some(indent) = indent_of(line) else {
+            continue;
+        };
+        let code = line.split('#').next().unwrap_or_default().trim();
+        let keyword = code.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or_default();
+        if !BLOCK_KEYWORDS.contains(&keyword) || !code.ends_with(':') {
+            continue;
+        }
+
+        let end = lines[index + 1..]
+            .iter()
+            .map(|line| indent_of(line))
+            .enumerate()
+            .take_while(|(_, body)| body.is_none_or(|body| body > indent))
+            .filter(|(_, body)| body.is_some())
+            .last()
+            .map(|(offset, _)| index + 1 + offset);
+        if let Some(end) = end {
+            folds.push(Fold { header: index + 1, end: end + 1 });
+        }
+    }
+    folds
+}
+
+/// Lines hidden by the folds whose headers are in `folded`, nested folds merged.
+pub fn hidden_lines(folds: &[Fold], folded: &BTreeSet<usize>) -> Vec<RangeInclusive<usize>> {
+    let mut hidden: Vec<RangeInclusive<usize>> = vec![];
+    for fold in folds.iter().filter(|fold| folded.contains(&fold.header)) {
+        match hidden.last_mut() {
+            Some(last) if fold.header <= *last.end() => {}
+            _ => hidden.push(fold.header + 1..=fold.end),
+        }
+    }
+    hidden
+}
+
+/// Byte ranges of `lines` in `text`, each with its newline.
+fn line_bytes(text: &str, lines: &[RangeInclusive<usize>]) -> Vec<Range<usize>> {
+    let starts: Vec<usize> = std::iter::once(0).chain(text.match_indices('\n').map(|(index, _)| index + 1)).collect();
+    let start_of = |line: usize| starts.get(line - 1).copied().unwrap_or(text.len());
+    lines.iter().map(|lines| start_of(*lines.start())..start_of(lines.end() + 1)).collect()
+}
+
+/// Shrinks the lines of `text` hidden by `folded` out of sight in its laid out `job`.
+pub fn hide_folded(job: &mut LayoutJob, text: &str, folded: &BTreeSet<usize>) {
+    if folded.is_empty() {
+        return;
+    }
+    let hidden = line_bytes(text, &hidden_lines(&fold_regions(text), folded));
+    find::restyle_ranges(job, &hidden, |_, format| {
+        format.font_id.size = FOLDED_FONT_SIZE;
+        format.color = Color32::TRANSPARENT;
+        format.background = Color32::TRANSPARENT;
+    });
+}
+
+
+/// Paints line numbers in `numbers` and fold markers in `markers` beside a laid out editor,
+/// skipping folded lines. Returns the header line of a fold marker that was clicked.
+pub fn paint(
+    ui: &Ui,
+    numbers: Rect,
+    markers: Rect,
+    output: &TextEditOutput,
+    folds: &[Fold],
+    folded: &BTreeSet<usize>,
+    current_line: Option<usize>,
+) -> Option<usize> {
+    let font = egui::TextStyle::Monospace.resolve(ui.style());
+    let headers: BTreeSet<usize> = folds.iter().map(|fold| fold.header).collect();
+    let clip = ui.clip_rect().y_range();
+    let offset = output.galley_pos.to_vec2();
+    let mut clicked = None;
+
+    let mut line = 1;
+    let mut starts_line = true;
+    for row in &output.galley.rows {
+        let rect = row.rect().translate(offset);
+        // Rows of folded lines are shrunk to almost nothing.
+        if starts_line && rect.height() > 1.0 && clip.intersects(rect.y_range()) {
+            let color = if current_line == Some(line) { ui.visuals().strong_text_color() } else { ui.visuals().weak_text_color() };
+            ui.painter().text(pos2(numbers.right() - 6.0, rect.center().y), egui::Align2::RIGHT_CENTER, line.to_string(), font.clone(), color);
+
+            if headers.contains(&line) {
+                let is_folded = folded.contains(&line);
+                let marker = Rect::from_x_y_ranges(markers.x_range(), rect.y_range());
+                let response = ui.interact(marker, output.response.id.with(("fold", line)), Sense::click());
+                let icon = if is_folded { egui_phosphor::regular::CARET_RIGHT } else { egui_phosphor::regular::CARET_DOWN };
+                let color = if response.hovered() { ui.visuals().strong_text_color() } else { ui.visuals().weak_text_color() };
+                ui.painter().text(marker.center(), egui::Align2::CENTER_CENTER, icon, egui::FontId::proportional(11.0), color);
+                if is_folded {
+                    ui.painter().text(pos2(rect.right() + 8.0, rect.center().y), egui::Align2::LEFT_CENTER, "⋯", font.clone(), ui.visuals().weak_text_color());
+                }
+                if response.on_hover_text(if is_folded { "Unfold" } else { "Fold" }).clicked() {
+                    clicked = Some(line);
+                }
+            }
+        }
+
+        starts_line = row.ends_with_newline;
+        if row.ends_with_newline {
+            line += 1;
+        }
+    }
+    clicked
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{fold_regions, hidden_lines};
+
+    fn folds(text: &str) -> Vec<(usize, usize)> {
+        fold_regions(text).iter().map(|fold| (fold.header, fold.end)).collect()
+    }
+
+    const NESTED: &str = "\
+class Player:
+    def update(self):
+        if self.alive:
+            self.move()
+        self.draw()
+
+    def draw(self):
+        pass
+x = 1
+";
+
+    #[test]
+    fn nested_blocks_fold_to_their_last_line() {
+        assert_eq!(folds(NESTED), [(1, 8), (2, 5), (3, 4), (7, 8)]);
+    }
+
+    #[test]
+    fn blank_lines_and_comments_stay_inside_a_block() {
+        let text = "def f():\n    a = 1\n\n# note\n    b = 2\n\nc = 3\n";
+        assert_eq!(folds(text), [(1, 5)]);
+    }
+
+    #[test]
+    fn a_block_can_run_to_the_end_of_the_file() {
+        assert_eq!(folds("while True:\n    step()\n    if done:\n        break"), [(1, 4), (3, 4)]);
+        // A header without a body yet folds nothing.
+        assert_eq!(folds("x = 1\nif x:"), []);
+    }
+
+    #[test]
+    fn only_block_statements_fold() {
+        let text = "values = {\n    1: 2,\n}\nif ready:  # go\n    start()\ndata = [x for x in y if x]:\n    z\n";
+        assert_eq!(folds(text), [(4, 5)]);
+    }
+
+    #[test]
+    fn nested_folds_merge_into_the_outer_one() {
+        let folds = fold_regions(NESTED);
+        assert_eq!(hidden_lines(&folds, &BTreeSet::from([1, 3])), [2..=8]);
+        assert_eq!(hidden_lines(&folds, &BTreeSet::from([3, 7])), [4..=4, 8..=8]);
+        assert_eq!(hidden_lines(&folds, &BTreeSet::from([5])), []);
+    }
+}
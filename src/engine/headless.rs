@@ -0,0 +1,74 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use pyo3::prelude::*;
+
+use crate::engine::{helpers, python};
+
+
+/// Options for running a game script without the editor window.
+pub struct HeadlessOptions {
+    pub script: PathBuf,
+    pub frames: usize,
+    pub out: Option<PathBuf>,
+    pub every: usize,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            script: PathBuf::new(),
+            frames: 300,
+            out: None,
+            every: 1,
+        }
+    }
+}
+
+fn frame_path(out: &Path, frame: usize) -> PathBuf {
+    out.join(format!("frame_{frame:05}.png"))
+}
+
+/// Runs the script for `options.frames` frames, writing every `options.every`th frame to `options.out`.
+/// Returns an error if the script could not be read, raised, or a frame could not be written.
+pub fn run(options: &HeadlessOptions) -> Result<usize, String> {
+    let code = fs::read_to_string(&options.script)
+        .map_err(|e| format!("Could not read {}: {e}", options.script.display()))?;
+
+    if let Some(out) = &options.out {
+        fs::create_dir_all(out).map_err(|e| format!("Could not create {}: {e}", out.display()))?;
+    }
+
+    let every = options.every.max(1);
+    let mut write_error = None;
+    let mut frames_run = 0;
+
+    pyo3::prepare_freethreaded_python();
+
+    Python::with_gil(|py| {
+        let result = python::run_code(py, &code, &options.script.display().to_string(), python::DEFAULT_FRAME_SIZE, |_py, session, image| {
+            frames_run = session.frame;
+
+            if let Some(out) = &options.out {
+                if session.frame % every == 0 {
+                    let path = frame_path(out, session.frame);
+                    if let Err(e) = helpers::to_rgba_image(&image).save(&path) {
+                        write_error = Some(format!("Could not write {}: {e}", path.display()));
+                        return false;
+                    }
+                }
+            }
+
+            session.frame < options.frames
+        });
+
+        result.map_err(|e| {
+            e.print(py);
+            format!("{} raised an exception", options.script.display())
+        })
+    })?;
+
+    match write_error {
+        Some(e) => Err(e),
+        None => Ok(frames_run),
+    }
+}
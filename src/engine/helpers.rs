@@ -1,4 +1,6 @@
-use egui::Vec2;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use egui::{ColorImage, Vec2};
 
 
 pub fn fit_aspect(original: [usize; 2], max: Vec2) -> Vec2 {
@@ -15,4 +17,40 @@ pub fn fit_aspect(original: [usize; 2], max: Vec2) -> Vec2 {
     };
 
     Vec2::new(new_w, new_h)
-}
\ No newline at end of file
+}
+
+/// Largest whole-number scale at which `original` fits in `max`. Falls back to a fractional
+/// fit when the image is bigger than the space available.
+pub fn integer_scale(original: [usize; 2], max: Vec2) -> f32 {
+    let fit = fit_aspect(original, max).x / original[0] as f32;
+    if fit < 1.0 { fit } else { fit.floor() }
+}
+
+
+/// Converts a rendered frame into an `image` buffer so it can be written to disk.
+pub fn to_rgba_image(frame: &ColorImage) -> image::RgbaImage {
+    let [w, h] = frame.size;
+    let bytes = frame.pixels.iter().flat_map(|p| p.to_srgba_unmultiplied()).collect();
+
+    image::RgbaImage::from_raw(w as u32, h as u32, bytes).expect("Frame size does not match its pixels")
+}
+
+/// Nearest-neighbour downscale of `frame` to `height` pixels tall, keeping its aspect ratio.
+pub fn thumbnail(frame: &ColorImage, height: usize) -> ColorImage {
+    let [w, h] = frame.size;
+    let height = height.clamp(1, h.max(1));
+    let width = (w * height / h.max(1)).max(1);
+
+    let bytes: Vec<u8> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x * w / width, y * h / height)))
+        .flat_map(|(x, y)| frame.pixels[y * w + x].to_srgba_unmultiplied())
+        .collect();
+    ColorImage::from_rgba_unmultiplied([width, height], &bytes)
+}
+
+
+/// Locks `mutex`, carrying on with its data if another thread panicked while holding it.
+/// The editor's shared state is plain data, so it stays usable after such a panic.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,786 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use egui::{text::{CCursor, CCursorRange}, text_edit::{TextEditOutput, TextEditState}, Id, Key, KeyboardShortcut, Modifiers, Ui};
+use serde_json::{json, Value};
+
+use crate::engine::diagnostics::{self, Problem};
+use crate::engine::editor::{self, SourceLocation};
+use crate::engine::find;
+use crate::engine::helpers::lock;
+use crate::engine::search;
+
+
+/// Language servers tried in order, with the arguments that make them talk over stdio.
+const SERVERS: [(&str, &[&str]); 3] = [
+    ("pylsp", &[]),
+    ("pyright-langserver", &["--stdio"]),
+    ("jedi-language-server", &[]),
+];
+/// How long the pointer rests on a word before its hover docs are asked for.
+const HOVER_DELAY: f64 = 0.5;
+const MAX_COMPLETIONS: usize = 50;
+/// How long closing waits for the server to answer `shutdown`, and then to exit, before killing it.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(500);
+
+
+/// `file://` URI of `path`, percent-encoding anything that is not plain.
+fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let path = if path.starts_with('/') { path } else { format!("/{path}") };
+    let encoded: String = path
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => char::from(byte).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect();
+    format!("file://{encoded}")
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut index = 0;
+    while index < encoded.len() {
+        let escaped = encoded.get(index + 1..index + 3).filter(|_| encoded[index] == b'%');
+        match escaped.and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()) {
+            Some(byte) => {
+                bytes.push(byte);
+                index += 3;
+            }
+            None => {
+                bytes.push(encoded[index]);
+                index += 1;
+            }
+        }
+    }
+
+    let path = String::from_utf8(bytes).ok()?;
+    // Windows paths arrive as `/C:/...`.
+    let path = if path.get(2..3) == Some(":") { &path[1..] } else { &path };
+    Some(PathBuf::from(path))
+}
+
+/// LSP position (0-based line, UTF-16 column) of char index `at` in `text`.
+fn position(text: &str, at: usize) -> Value {
+    let line = editor::line_of(text, at);
+    let column: usize = text.chars().skip(editor::line_start(text, line)).take(at - editor::line_start(text, line)).map(char::len_utf16).sum();
+    json!({ "line": line - 1, "character": column })
+}
+
+/// Char index in `text` of an LSP position.
+fn char_index(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+    let start = editor::line_start(text, line + 1);
+
+    let mut units = 0;
+    let column = text
+        .chars()
+        .skip(start)
+        .take_while(|&c| {
+            units += c.len_utf16();
+            c != '\n' && units <= character
+        })
+        .count();
+    start + column
+}
+
+/// Byte offset in `text` of a UTF-16 offset.
+fn utf16_to_byte(text: &str, offset: u64) -> usize {
+    let mut units = 0;
+    text.char_indices()
+        .find(|(_, c)| {
+            let past = units >= offset;
+            units += c.len_utf16() as u64;
+            past
+        })
+        .map_or(text.len(), |(byte, _)| byte)
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Char index where the identifier ending at `at` starts.
+fn word_start(text: &str, at: usize) -> usize {
+    let before: Vec<char> = text.chars().take(at).collect();
+    at - before.iter().rev().take_while(|&&c| is_identifier(c)).count()
+}
+
+fn cursor_index(ctx: &egui::Context, id: Id) -> Option<usize> {
+    TextEditState::load(ctx, id)?.cursor.char_range().map(|range| range.primary.index)
+}
+
+fn set_cursor(ctx: &egui::Context, id: Id, index: usize) {
+    let mut state = TextEditState::load(ctx, id).unwrap_or_default();
+    state.cursor.set_char_range(Some(CCursorRange::one(CCursor::new(index))));
+    state.store(ctx, id);
+}
+
+
+fn read_messages(stdout: ChildStdout, incoming: &Mutex<Vec<Value>>, ctx: &egui::Context) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap_or(0) == 0 {
+                return;
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse().ok();
+            }
+        }
+
+        let Some(length) = length else {
+            continue;
+        };
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+        if let Ok(message) = serde_json::from_slice(&body) {
+            lock(incoming).push(message);
+            ctx.request_repaint();
+        }
+    }
+}
+
+fn write_messages(mut stdin: ChildStdin, outgoing: Receiver<Value>) {
+    for message in outgoing {
+        let body = message.to_string();
+        if write!(stdin, "Content-Length: {}\r\n\r\n{body}", body.len()).and_then(|()| stdin.flush()).is_err() {
+            return;
+        }
+    }
+}
+
+
+/// What a request sent to the server was for, so its response can be routed.
+enum Pending {
+    Initialize,
+    Completion { path: PathBuf, word_start: usize },
+    Hover { path: PathBuf, at: usize },
+    Signature { path: PathBuf },
+    Definition,
+    Shutdown,
+}
+
+struct Document {
+    version: i64,
+    text: String,
+}
+
+#[derive(Clone)]
+struct CompletionItem {
+    label: String,
+    detail: String,
+    insert: String,
+    filter: String,
+    sort: String,
+}
+
+struct Completion {
+    path: PathBuf,
+    /// Char index where the word being completed starts.
+    word_start: usize,
+    items: Vec<CompletionItem>,
+    selected: usize,
+}
+
+impl Completion {
+    /// Items matching what was typed since the popup opened, or `None` once the cursor left the word.
+    fn visible(&self, text: &str, cursor: usize) -> Option<Vec<&CompletionItem>> {
+        let typed: String = text.chars().skip(self.word_start).take(cursor.checked_sub(self.word_start)?).collect();
+        if !typed.chars().all(is_identifier) {
+            return None;
+        }
+        Some(self.items.iter().filter(|item| search::fuzzy_match(&typed, &item.filter)).take(MAX_COMPLETIONS).collect())
+    }
+}
+
+struct Hover {
+    path: PathBuf,
+    at: usize,
+    text: String,
+}
+
+/// Where the pointer rests in the editor, waiting for `HOVER_DELAY`.
+struct HoverProbe {
+    at: usize,
+    since: f64,
+    requested: bool,
+}
+
+struct Signature {
+    path: PathBuf,
+    label: String,
+    /// Byte range in `label` of the parameter being typed.
+    active: Option<Range<usize>>,
+}
+
+fn completion_items(result: &Value) -> Vec<CompletionItem> {
+    let items = result.get("items").unwrap_or(result);
+    let mut items: Vec<CompletionItem> = items
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            let label = item["label"].as_str()?.to_owned();
+            let text = |key: &str| item.pointer(key).and_then(Value::as_str).map(str::to_owned);
+            Some(CompletionItem {
+                insert: text("/textEdit/newText").or_else(|| text("/insertText")).unwrap_or_else(|| label.clone()),
+                filter: text("/filterText").unwrap_or_else(|| label.clone()),
+                sort: text("/sortText").unwrap_or_else(|| label.clone()),
+                detail: text("/detail").unwrap_or_default(),
+                label,
+            })
+        })
+        .collect();
+    items.sort_by(|a, b| a.sort.cmp(&b.sort));
+    items
+}
+
+/// Plain text of hover contents, which may be markup, a marked string or a list of them.
+fn hover_text(contents: &Value) -> String {
+    let text = match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts.iter().map(hover_text).filter(|part| !part.is_empty()).collect::<Vec<_>>().join("\n\n"),
+        Value::Object(_) => contents["value"].as_str().unwrap_or_default().to_owned(),
+        _ => String::new(),
+    };
+    text.lines().filter(|line| !line.trim_start().starts_with("```")).collect::<Vec<_>>().join("\n").trim().to_owned()
+}
+
+fn signature(result: &Value) -> Option<(String, Option<Range<usize>>)> {
+    let signatures = result.get("signatures")?.as_array()?;
+    let active = result["activeSignature"].as_u64().unwrap_or(0) as usize;
+    let signature = signatures.get(active).or(signatures.first())?;
+    let label = signature["label"].as_str()?.to_owned();
+
+    let parameter = signature["activeParameter"].as_u64().or(result["activeParameter"].as_u64());
+    let active = parameter
+        .and_then(|index| signature["parameters"].get(index as usize))
+        .and_then(|parameter| match &parameter["label"] {
+            Value::String(name) => label.find(name.as_str()).map(|start| start..start + name.len()),
+            Value::Array(offsets) => {
+                let start = utf16_to_byte(&label, offsets.first()?.as_u64()?);
+                let end = utf16_to_byte(&label, offsets.get(1)?.as_u64()?);
+                Some(start..end)
+            }
+            _ => None,
+        })
+        // Offsets come from the server, so a buggy one must not make the popup slice out of bounds.
+        .filter(|range| range.start <= range.end && range.end <= label.len());
+    Some((label, active))
+}
+
+fn definition(result: &Value) -> Option<SourceLocation> {
+    let location = match result {
+        Value::Array(locations) => locations.first()?,
+        location => location,
+    };
+    let uri = location.get("uri").or(location.get("targetUri"))?.as_str()?;
+    let range = location.get("targetSelectionRange").or(location.get("range"))?;
+    let line = range.pointer("/start/line")?.as_u64()? as usize;
+    Some(SourceLocation { file: uri_to_path(uri)?.display().to_string(), line: line + 1 })
+}
+
+
+/// A Python language server running over stdio for the open project.
+pub struct LspClient {
+    /// Command of the server that was started.
+    pub name: &'static str,
+    child: Child,
+    outgoing: Sender<Value>,
+    incoming: Arc<Mutex<Vec<Value>>>,
+    next_id: i64,
+    pending: HashMap<i64, Pending>,
+    /// Set once the server answered `initialize`.
+    ready: bool,
+    documents: HashMap<PathBuf, Document>,
+    completion: Option<Completion>,
+    hover: Option<Hover>,
+    hover_probe: Option<HoverProbe>,
+    signature: Option<Signature>,
+    definition: Option<SourceLocation>,
+}
+
+impl LspClient {
+    /// Starts the first language server found on `PATH` for the project at `root`.
+    pub fn start(root: &Path, ctx: &egui::Context) -> Result<Self, String> {
+        for (name, args) in SERVERS {
+            let spawned = Command::new(name)
+                .args(args)
+                .current_dir(root)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn();
+            let Ok(mut child) = spawned else {
+                continue;
+            };
+            let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+                continue;
+            };
+
+            let (outgoing, receiver) = mpsc::channel();
+            let incoming = Arc::new(Mutex::new(Vec::new()));
+            std::thread::spawn(move || write_messages(stdin, receiver));
+            let reader_incoming = incoming.clone();
+            let ctx = ctx.clone();
+            std::thread::spawn(move || read_messages(stdout, &reader_incoming, &ctx));
+
+            let mut client = Self {
+                name,
+                child,
+                outgoing,
+                incoming,
+                next_id: 0,
+                pending: HashMap::new(),
+                ready: false,
+                documents: HashMap::new(),
+                completion: None,
+                hover: None,
+                hover_probe: None,
+                signature: None,
+                definition: None,
+            };
+            let uri = path_to_uri(root);
+            let folder = root.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            client.request(Pending::Initialize, "initialize", &json!({
+                "processId": std::process::id(),
+                "clientInfo": { "name": "RedEngine" },
+                "rootUri": uri,
+                "rootPath": root,
+                "workspaceFolders": [{ "uri": uri, "name": folder }],
+                "capabilities": {
+                    "textDocument": {
+                        "synchronization": { "didSave": false },
+                        "completion": { "completionItem": { "snippetSupport": false } },
+                        "hover": { "contentFormat": ["plaintext", "markdown"] },
+                        "signatureHelp": { "signatureInformation": { "parameterInformation": { "labelOffsetSupport": true } } },
+                        "definition": { "linkSupport": true },
+                        "publishDiagnostics": {},
+                    },
+                    "workspace": { "configuration": true, "workspaceFolders": true },
+                },
+            }));
+            return Ok(client);
+        }
+        Err("No Python language server found: install pylsp, pyright or jedi-language-server".to_owned())
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    /// Whether the server process is still alive.
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    fn send(&self, message: Value) {
+        // The writer only hangs up once the server is gone, which `is_running` reports.
+        self.outgoing.send(message).ok();
+    }
+
+    fn request(&mut self, pending: Pending, method: &str, params: &Value) {
+        self.next_id += 1;
+        self.pending.insert(self.next_id, pending);
+        self.send(json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params }));
+    }
+
+    fn notify(&self, method: &str, params: &Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Handles everything the server sent since the last frame.
+    pub fn poll(&mut self) {
+        let messages = std::mem::take(&mut *lock(&self.incoming));
+        for message in messages {
+            match (message.get("id"), message["method"].as_str()) {
+                // Requests from the server: settings are left at their defaults.
+                (Some(id), Some(method)) => {
+                    let result = match method {
+                        "workspace/configuration" => Value::Array(vec![Value::Null; message["params"]["items"].as_array().map_or(0, Vec::len)]),
+                        _ => Value::Null,
+                    };
+                    self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+                }
+                (Some(id), None) => {
+                    if let Some(pending) = id.as_i64().and_then(|id| self.pending.remove(&id)) {
+                        self.respond(pending, &message["result"]);
+                    }
+                }
+                (None, Some("textDocument/publishDiagnostics")) => self.publish_diagnostics(&message["params"]),
+                _ => {}
+            }
+        }
+    }
+
+    fn respond(&mut self, pending: Pending, result: &Value) {
+        match pending {
+            Pending::Initialize => {
+                self.ready = true;
+                self.notify("initialized", &json!({}));
+            }
+            Pending::Completion { path, word_start } => {
+                let items = completion_items(result);
+                self.completion = (!items.is_empty()).then_some(Completion { path, word_start, items, selected: 0 });
+            }
+            Pending::Hover { path, at } => {
+                let text = hover_text(&result["contents"]);
+                self.hover = (!text.is_empty()).then_some(Hover { path, at, text });
+            }
+            Pending::Signature { path } => {
+                self.signature = signature(result).map(|(label, active)| Signature { path, label, active });
+            }
+            Pending::Definition => self.definition = definition(result),
+            Pending::Shutdown => {}
+        }
+    }
+
+    fn publish_diagnostics(&self, params: &Value) {
+        let Some(path) = params["uri"].as_str().and_then(uri_to_path) else {
+            return;
+        };
+        let Some(document) = self.documents.get(&path) else {
+            return;
+        };
+
+        let text = &document.text;
+        let line_column = |position: &Value| {
+            let index = char_index(text, position);
+            let line = editor::line_of(text, index);
+            (line, index - editor::line_start(text, line) + 1)
+        };
+        let problems = params["diagnostics"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|diagnostic| {
+                let (line, column) = line_column(diagnostic.pointer("/range/start")?);
+                let end = line_column(diagnostic.pointer("/range/end")?);
+                let message = diagnostic["message"].as_str()?;
+                Some(Problem {
+                    // Severity 1 is an error, the rest are warnings, hints and info.
+                    is_error: diagnostic["severity"].as_u64().unwrap_or(1) == 1,
+                    message: match diagnostic["source"].as_str() {
+                        Some(source) => format!("{source}: {message}"),
+                        None => message.to_owned(),
+                    },
+                    line,
+                    column,
+                    end: Some(end),
+                })
+            })
+            .collect();
+        diagnostics::set_server_problems(&path, problems);
+    }
+
+    /// Tells the server about `text`, the buffer of `path`, if it changed since last time.
+    fn sync_document(&mut self, path: &Path, text: &str) {
+        let uri = path_to_uri(path);
+        match self.documents.get_mut(path) {
+            Some(document) if document.text == text => {}
+            Some(document) => {
+                document.version += 1;
+                document.text = text.to_owned();
+                let version = document.version;
+                self.notify("textDocument/didChange", &json!({
+                    "textDocument": { "uri": uri, "version": version },
+                    "contentChanges": [{ "text": text }],
+                }));
+            }
+            None => {
+                self.documents.insert(path.to_owned(), Document { version: 1, text: text.to_owned() });
+                self.notify("textDocument/didOpen", &json!({
+                    "textDocument": { "uri": uri, "languageId": "python", "version": 1, "text": text },
+                }));
+            }
+        }
+    }
+
+    /// Opens, updates and closes server documents to match the editor's Python buffers.
+    pub fn sync<'a>(&mut self, buffers: impl IntoIterator<Item = (&'a Path, &'a str)>) {
+        if !self.ready {
+            return;
+        }
+
+        let mut open = BTreeSet::new();
+        for (path, text) in buffers {
+            open.insert(path.to_owned());
+            self.sync_document(path, text);
+        }
+
+        let closed: Vec<PathBuf> = self.documents.keys().filter(|path| !open.contains(*path)).cloned().collect();
+        for path in closed {
+            self.documents.remove(&path);
+            self.notify("textDocument/didClose", &json!({ "textDocument": { "uri": path_to_uri(&path) } }));
+        }
+    }
+
+    fn text_position(&mut self, path: &Path, text: &str, at: usize) -> Value {
+        self.sync_document(path, text);
+        json!({ "textDocument": { "uri": path_to_uri(path) }, "position": position(text, at) })
+    }
+
+    fn request_completion(&mut self, path: &Path, text: &str, at: usize) {
+        if self.pending.values().any(|pending| matches!(pending, Pending::Completion { .. })) {
+            return;
+        }
+        let params = self.text_position(path, text, at);
+        self.request(Pending::Completion { path: path.to_owned(), word_start: word_start(text, at) }, "textDocument/completion", &params);
+    }
+
+    fn request_signature(&mut self, path: &Path, text: &str, at: usize) {
+        let params = self.text_position(path, text, at);
+        self.request(Pending::Signature { path: path.to_owned() }, "textDocument/signatureHelp", &params);
+    }
+
+    fn request_definition(&mut self, path: &Path, text: &str, at: usize) {
+        let params = self.text_position(path, text, at);
+        self.request(Pending::Definition, "textDocument/definition", &params);
+    }
+
+    /// Where go-to-definition landed, cleared once read.
+    pub fn take_definition(&mut self) -> Option<SourceLocation> {
+        self.definition.take()
+    }
+
+    /// Replaces the word being completed with the item's text.
+    fn accept(&mut self, ctx: &egui::Context, id: Id, text: &mut String, cursor: usize, item: &CompletionItem) {
+        let Some(completion) = self.completion.take() else {
+            return;
+        };
+        let range = find::byte_index(text, completion.word_start)..find::byte_index(text, cursor);
+        text.replace_range(range, &item.insert);
+        set_cursor(ctx, id, completion.word_start + item.insert.chars().count());
+    }
+
+    /// Handles the completion popup keys and the language server shortcuts, before the
+    /// `TextEdit` with `id` editing `path` sees them.
+    pub fn editor_input(&mut self, ui: &Ui, id: Id, path: &Path, text: &mut String) {
+        if !ui.memory(|memory| memory.has_focus(id)) {
+            return;
+        }
+        let Some(cursor) = cursor_index(ui.ctx(), id) else {
+            return;
+        };
+
+        let complete = KeyboardShortcut::new(Modifiers::COMMAND, Key::Space);
+        let signature = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Space);
+        if ui.input_mut(|i| i.consume_shortcut(&signature)) {
+            self.request_signature(path, text, cursor);
+        } else if ui.input_mut(|i| i.consume_shortcut(&complete)) {
+            self.request_completion(path, text, cursor);
+        }
+        if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::F12)) {
+            self.request_definition(path, text, cursor);
+        }
+
+        let Some(completion) = self.completion.as_mut().filter(|completion| completion.path == path) else {
+            return;
+        };
+        let Some(visible) = completion.visible(text, cursor).filter(|visible| !visible.is_empty()) else {
+            self.completion = None;
+            return;
+        };
+
+        let count = visible.len();
+        let selected = completion.selected.min(count - 1);
+        let item = visible[selected].clone();
+        if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::ArrowDown)) {
+            completion.selected = (selected + 1) % count;
+        } else if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::ArrowUp)) {
+            completion.selected = (selected + count - 1) % count;
+        } else if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Enter) || i.consume_key(Modifiers::NONE, Key::Tab)) {
+            self.accept(ui.ctx(), id, text, cursor, &item);
+        } else if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape)) {
+            self.completion = None;
+        }
+    }
+
+    /// Asks for completions or signature help as the character before `cursor` calls for,
+    /// after `text` was edited.
+    pub fn edited(&mut self, path: &Path, text: &str, cursor: usize) {
+        // A completion still pending is for older text. Dropping it also keeps a response that
+        // never arrives from blocking completions for good.
+        self.pending.retain(|_, pending| !matches!(pending, Pending::Completion { .. }));
+        let typed = text.chars().nth(cursor.wrapping_sub(1));
+        match typed {
+            Some('.') => self.request_completion(path, text, cursor),
+            Some(c) if is_identifier(c) && self.completion.is_none() => self.request_completion(path, text, cursor),
+            _ => {}
+        }
+        match typed {
+            Some('(' | ',') => self.request_signature(path, text, cursor),
+            Some(')') => self.signature = None,
+            _ if self.signature.is_some() => self.request_signature(path, text, cursor),
+            _ => {}
+        }
+    }
+
+    /// Reacts to edits, clicks and hovers in a laid out editor and draws the completion,
+    /// signature and hover popups over it.
+    pub fn editor_overlay(&mut self, ui: &Ui, output: &TextEditOutput, id: Id, path: &Path, text: &mut String) {
+        let cursor = output.cursor_range.map(|range| range.primary.index).filter(|_| output.response.has_focus());
+
+        if let (Some(cursor), true) = (cursor, output.response.changed()) {
+            self.edited(path, text, cursor);
+        }
+        if let (Some(cursor), true) = (cursor, output.response.clicked() && ui.input(|i| i.modifiers.command)) {
+            self.request_definition(path, text, cursor);
+        }
+
+        self.hover_ui(ui, output, path, text);
+
+        let Some(cursor) = cursor else {
+            return;
+        };
+        let offset = output.galley_pos.to_vec2();
+        let cursor_rect = output.galley.pos_from_cursor(CCursor::new(cursor)).translate(offset);
+
+        if let Some(signature) = self.signature.as_ref().filter(|signature| signature.path == path) {
+            egui::Area::new(id.with("lsp_signature"))
+                .order(egui::Order::Foreground)
+                .pivot(egui::Align2::LEFT_BOTTOM)
+                .fixed_pos(cursor_rect.left_top())
+                .show(ui.ctx(), |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        let label = &signature.label;
+                        let active = signature.active.clone().unwrap_or(0..0);
+                        ui.horizontal(|ui| {
+                            ui.spacing_mut().item_spacing.x = 0.0;
+                            ui.monospace(&label[..active.start]);
+                            ui.label(egui::RichText::new(&label[active.clone()]).monospace().strong().underline());
+                            ui.monospace(&label[active.end..]);
+                        });
+                    });
+                });
+        }
+
+        let Some(completion) = self.completion.as_ref().filter(|completion| completion.path == path) else {
+            return;
+        };
+        let Some(visible) = completion.visible(text, cursor).filter(|visible| !visible.is_empty()) else {
+            return;
+        };
+
+        let selected = completion.selected.min(visible.len() - 1);
+        let mut clicked = None;
+        egui::Area::new(id.with("lsp_completion"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(cursor_rect.left_bottom())
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    egui::ScrollArea::vertical().max_height(220.0).show(ui, |ui| {
+                        for (index, item) in visible.iter().enumerate() {
+                            let response = ui.horizontal(|ui| {
+                                let response = ui.selectable_label(index == selected, egui::RichText::new(&item.label).monospace());
+                                if !item.detail.is_empty() {
+                                    ui.weak(&item.detail);
+                                }
+                                response
+                            }).inner;
+                            if index == selected {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                clicked = Some((*item).clone());
+                            }
+                        }
+                    });
+                });
+            });
+
+        if let Some(item) = clicked {
+            self.accept(ui.ctx(), id, text, cursor, &item);
+            ui.memory_mut(|memory| memory.request_focus(id));
+        }
+    }
+
+    /// Asks for the docs of the word under the pointer once it rests there, and shows them.
+    fn hover_ui(&mut self, ui: &Ui, output: &TextEditOutput, path: &Path, text: &str) {
+        let Some(pointer) = output.response.hover_pos() else {
+            self.hover_probe = None;
+            return;
+        };
+        let at = output.galley.cursor_from_pos(pointer - output.galley_pos).index;
+        let now = ui.input(|i| i.time);
+
+        let due = match &mut self.hover_probe {
+            Some(probe) if probe.at == at => {
+                let due = !probe.requested && now - probe.since >= HOVER_DELAY;
+                probe.requested |= due;
+                due
+            }
+            _ => {
+                self.hover_probe = Some(HoverProbe { at, since: now, requested: false });
+                self.hover = None;
+                ui.ctx().request_repaint_after(Duration::from_secs_f64(HOVER_DELAY));
+                false
+            }
+        };
+        if due {
+            let params = self.text_position(path, text, at);
+            self.request(Pending::Hover { path: path.to_owned(), at }, "textDocument/hover", &params);
+        }
+
+        if let Some(hover) = self.hover.as_ref().filter(|hover| hover.path == path && hover.at == at) {
+            output.response.clone().on_hover_ui_at_pointer(|ui| {
+                ui.set_max_width(520.0);
+                ui.label(egui::RichText::new(&hover.text).monospace());
+            });
+        }
+    }
+
+    /// Status shown beside the editor tabs.
+    pub fn status(&mut self) -> String {
+        if !self.is_running() {
+            format!("{} stopped", self.name)
+        } else if !self.ready {
+            format!("Starting {}…", self.name)
+        } else {
+            self.name.to_owned()
+        }
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        if self.ready {
+            self.request(Pending::Shutdown, "shutdown", &Value::Null);
+            let id = self.next_id;
+            let answered = |incoming: &Mutex<Vec<Value>>| {
+                lock(incoming).iter().any(|message| message["id"].as_i64() == Some(id) && message.get("method").is_none())
+            };
+            let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+            while !answered(&self.incoming) && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+
+            self.notify("exit", &Value::Null);
+            let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+            while matches!(self.child.try_wait(), Ok(None)) && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+        // Still running when the server ignored `exit` or never got as far as initializing.
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
@@ -0,0 +1,496 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use egui::{pos2, vec2, Align2, Color32, FontId, Sense, Stroke, Ui};
+use pyo3::prelude::*;
+
+use crate::engine::editor::SourceLocation;
+use crate::engine::python;
+
+
+/// Seconds between samples of the running interpreter.
+const SAMPLE_INTERVAL: f64 = 1.0;
+/// Samples kept for the graph, ten minutes at one per second.
+const HISTORY_LEN: usize = 600;
+const TOP_SITES: usize = 25;
+const TOP_TYPES: usize = 40;
+const GRAPH_HEIGHT: f32 = 140.0;
+
+/// Measures the interpreter. Object counts include untracked objects (such as Surfaces)
+/// that a tracked container refers to, since `gc.get_objects` only lists containers.
+const MEMORY_PROBE: &str = r#"
+import collections
+import gc
+import os
+import sys
+import tracemalloc
+
+def _type_name(obj):
+    t = type(obj)
+    return t.__qualname__ if t.__module__ == "builtins" else f"{t.__module__}.{t.__qualname__}"
+
+def object_counts():
+    objects = gc.get_objects()
+    counts = collections.Counter(map(_type_name, objects))
+    seen = set(map(id, objects))
+    for ref in gc.get_referents(*objects):
+        if id(ref) not in seen and not gc.is_tracked(ref):
+            seen.add(id(ref))
+            counts[_type_name(ref)] += 1
+    return dict(counts)
+
+def rss():
+    try:
+        import psutil
+        return psutil.Process().memory_info().rss
+    except ImportError:
+        pass
+    if sys.platform.startswith("linux"):
+        with open("/proc/self/statm") as statm:
+            return int(statm.read().split()[1]) * os.sysconf("SC_PAGE_SIZE")
+    if sys.platform == "win32":
+        import ctypes
+        from ctypes import wintypes
+
+        class Counters(ctypes.Structure):
+            _fields_ = [("cb", wintypes.DWORD), ("PageFaultCount", wintypes.DWORD)] + [
+                (name, ctypes.c_size_t) for name in (
+                    "PeakWorkingSetSize", "WorkingSetSize", "QuotaPeakPagedPoolUsage", "QuotaPagedPoolUsage",
+                    "QuotaPeakNonPagedPoolUsage", "QuotaNonPagedPoolUsage", "PagefileUsage", "PeakPagefileUsage",
+                )
+            ]
+
+        counters = Counters()
+        counters.cb = ctypes.sizeof(Counters)
+        process = ctypes.windll.kernel32.GetCurrentProcess()
+        if ctypes.windll.psapi.GetProcessMemoryInfo(process, ctypes.byref(counters), counters.cb):
+            return counters.WorkingSetSize
+    return None
+
+def _snapshot():
+    if not tracemalloc.is_tracing():
+        return None
+    return tracemalloc.take_snapshot().filter_traces((tracemalloc.Filter(False, tracemalloc.__file__),))
+
+def _site(stat):
+    frame = stat.traceback[0]
+    return frame.filename, frame.lineno
+
+def sample(limit):
+    traced = tracemalloc.get_traced_memory()[0] if tracemalloc.is_tracing() else None
+    snapshot = _snapshot()
+    sites = [] if snapshot is None else [_site(s) + (s.size, s.count) for s in snapshot.statistics("lineno")[:limit]]
+    return rss(), traced, sites, object_counts(), snapshot
+
+def compare(old, new, limit):
+    return [_site(s) + (s.size, s.size_diff, s.count_diff) for s in new.compare_to(old, "lineno")[:limit]]
+
+def set_tracing(enabled):
+    if enabled and not tracemalloc.is_tracing():
+        tracemalloc.start()
+    elif not enabled and tracemalloc.is_tracing():
+        tracemalloc.stop()
+"#;
+
+static SAMPLE_PENDING: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref STATE: std::sync::Mutex<MemoryState> = std::sync::Mutex::new(MemoryState::default());
+}
+
+
+#[derive(Clone)]
+pub struct AllocationSite {
+    pub location: SourceLocation,
+    pub size: u64,
+    pub count: u64,
+}
+
+#[derive(Clone)]
+pub struct SiteDiff {
+    pub location: SourceLocation,
+    pub size: u64,
+    pub size_diff: i64,
+    pub count_diff: i64,
+}
+
+#[derive(Clone, Copy)]
+struct MemoryPoint {
+    time: f64,
+    rss: Option<u64>,
+    traced: Option<u64>,
+}
+
+#[derive(Clone)]
+struct MemorySample {
+    sites: Vec<AllocationSite>,
+    types: BTreeMap<String, usize>,
+}
+
+struct MemorySnapshot {
+    time: f64,
+    types: BTreeMap<String, usize>,
+    /// The `tracemalloc` snapshot, when allocations were being traced.
+    traces: Option<Py<PyAny>>,
+}
+
+#[derive(Clone)]
+struct Comparison {
+    snapshots: (usize, usize),
+    sites: Vec<SiteDiff>,
+    /// Type name, count in the newer snapshot and change since the older one.
+    types: Vec<(String, usize, i64)>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    history: VecDeque<MemoryPoint>,
+    latest: Option<MemorySample>,
+    snapshots: Vec<MemorySnapshot>,
+    comparison: Option<Comparison>,
+}
+
+
+fn probe_function<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyAny>> {
+    python::helper_module(py, "memory", MEMORY_PROBE)?.getattr(name)
+}
+
+fn to_location((file, line): (String, usize)) -> SourceLocation {
+    SourceLocation { file, line }
+}
+
+type SampleResult<'py> = (Option<u64>, Option<u64>, Vec<(String, usize, u64, u64)>, BTreeMap<String, usize>, Bound<'py, PyAny>);
+
+fn take_sample(py: Python<'_>, time: f64, keep_snapshot: bool) -> PyResult<()> {
+    let (rss, traced, sites, types, traces): SampleResult<'_> = probe_function(py, "sample")?.call1((TOP_SITES,))?.extract()?;
+
+    let sites = sites
+        .into_iter()
+        .map(|(file, line, size, count)| AllocationSite { location: to_location((file, line)), size, count })
+        .collect();
+
+    let mut state = STATE.lock().unwrap();
+    if keep_snapshot {
+        state.snapshots.push(MemorySnapshot {
+            time,
+            types: types.clone(),
+            traces: (!traces.is_none()).then(|| traces.unbind()),
+        });
+    }
+    state.history.push_back(MemoryPoint { time, rss, traced });
+    while state.history.len() > HISTORY_LEN {
+        state.history.pop_front();
+    }
+    state.latest = Some(MemorySample { sites, types });
+    Ok(())
+}
+
+/// Queues a sample unless one is already waiting to run. `keep_snapshot` also stores it for
+/// later comparison.
+fn request_sample(time: f64, keep_snapshot: bool) {
+    if !keep_snapshot && SAMPLE_PENDING.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    python::queue_python_instruction(move |py| {
+        if let Err(e) = take_sample(py, time, keep_snapshot) {
+            e.print(py);
+        }
+        if !keep_snapshot {
+            SAMPLE_PENDING.store(false, Ordering::Relaxed);
+        }
+    });
+}
+
+/// Starts or stops `tracemalloc` in the running game.
+pub fn set_tracing(enabled: bool) {
+    python::queue_python_instruction(move |py| {
+        let result = probe_function(py, "set_tracing").and_then(|set_tracing| set_tracing.call1((enabled,)));
+        if let Err(e) = result {
+            e.print(py);
+        }
+    });
+}
+
+/// Compares two stored snapshots, the allocation sites in the game thread.
+fn compare(old: usize, new: usize) {
+    let types = {
+        let state = STATE.lock().unwrap();
+        let (Some(before), Some(after)) = (state.snapshots.get(old), state.snapshots.get(new)) else {
+            return;
+        };
+        let mut names: Vec<&String> = before.types.keys().chain(after.types.keys()).collect();
+        names.sort_unstable();
+        names.dedup();
+
+        let mut types: Vec<(String, usize, i64)> = names
+            .into_iter()
+            .map(|name| {
+                let count = after.types.get(name).copied().unwrap_or_default();
+                let previous = before.types.get(name).copied().unwrap_or_default();
+                (name.clone(), count, count as i64 - previous as i64)
+            })
+            .filter(|(_, _, diff)| *diff != 0)
+            .collect();
+        types.sort_by_key(|(_, _, diff)| std::cmp::Reverse(*diff));
+        types
+    };
+
+    python::queue_python_instruction(move |py| {
+        // Cloned out so the lock is not held while Python compares the traces.
+        let traces = {
+            let state = STATE.lock().unwrap();
+            let traces = |index: usize| state.snapshots.get(index).and_then(|s| s.traces.as_ref()).map(|t| t.clone_ref(py));
+            traces(old).zip(traces(new))
+        };
+
+        let sites = match traces {
+            Some((before, after)) => {
+                let result: PyResult<Vec<(String, usize, u64, i64, i64)>> = probe_function(py, "compare")
+                    .and_then(|compare| compare.call1((before, after, TOP_SITES))?.extract());
+                match result {
+                    Ok(sites) => sites,
+                    Err(e) => {
+                        e.print(py);
+                        Vec::new()
+                    }
+                }
+            }
+            None => Vec::new(),
+        };
+
+        STATE.lock().unwrap().comparison = Some(Comparison {
+            snapshots: (old, new),
+            sites: sites
+                .into_iter()
+                .map(|(file, line, size, size_diff, count_diff)| SiteDiff { location: to_location((file, line)), size, size_diff, count_diff })
+                .collect(),
+            types: types.clone(),
+        });
+    });
+}
+
+pub fn clear() {
+    *STATE.lock().unwrap() = MemoryState::default();
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    let bytes = bytes as f64;
+    if bytes >= 1024.0 * 1024.0 {
+        format!("{:.1} MiB", bytes / (1024.0 * 1024.0))
+    } else if bytes >= 1024.0 {
+        format!("{:.1} KiB", bytes / 1024.0)
+    } else {
+        format!("{bytes} B")
+    }
+}
+
+fn format_signed_bytes(bytes: i64) -> String {
+    let sign = if bytes < 0 { "-" } else { "+" };
+    format!("{sign}{}", format_bytes(bytes.unsigned_abs()))
+}
+
+
+/// The Memory tab: samples the game every second while it runs.
+#[derive(Default)]
+pub struct MemoryView {
+    pub tracing: bool,
+    last_sample: f64,
+    compare_old: usize,
+    compare_new: usize,
+}
+
+impl MemoryView {
+    pub fn update(&mut self, time: f64, running: bool) {
+        if running && time - self.last_sample >= SAMPLE_INTERVAL {
+            self.last_sample = time;
+            request_sample(time, false);
+        }
+    }
+
+    fn graph(ui: &mut Ui, state: &MemoryState) {
+        let (rect, _) = ui.allocate_exact_size(vec2(ui.available_width(), GRAPH_HEIGHT), Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+        let (Some(first), Some(last)) = (state.history.front(), state.history.back()) else {
+            return;
+        };
+        let span = (last.time - first.time).max(SAMPLE_INTERVAL);
+        let x = |time: f64| rect.left() + ((time - first.time) / span) as f32 * rect.width();
+
+        let max = state.history.iter().flat_map(|point| point.rss.into_iter().chain(point.traced)).max().unwrap_or(1).max(1);
+        let y = |bytes: u64| rect.bottom() - (bytes as f64 / max as f64) as f32 * (rect.height() - 16.0);
+
+        for (index, snapshot) in state.snapshots.iter().enumerate() {
+            if snapshot.time >= first.time {
+                let sx = x(snapshot.time);
+                painter.vline(sx, rect.y_range(), Stroke::new(1.0, Color32::from_gray(120)));
+                painter.text(pos2(sx + 2.0, rect.top() + 2.0), Align2::LEFT_TOP, format!("#{}", index + 1), FontId::monospace(11.0), Color32::from_gray(160));
+            }
+        }
+
+        let lines: [(&str, Color32, fn(&MemoryPoint) -> Option<u64>); 2] = [
+            ("RSS", Color32::from_rgb(90, 160, 230), |point| point.rss),
+            ("Traced", Color32::from_rgb(230, 160, 60), |point| point.traced),
+        ];
+        let mut legend = rect.right_top() + vec2(-6.0, 2.0);
+        for (label, color, value) in lines {
+            let points: Vec<_> = state.history.iter().filter_map(|point| Some(pos2(x(point.time), y(value(point)?)))).collect();
+            painter.add(egui::Shape::line(points, Stroke::new(1.5, color)));
+            if let Some(current) = value(last) {
+                let text = painter.text(legend, Align2::RIGHT_TOP, format!("{label} {}", format_bytes(current)), FontId::monospace(11.0), color);
+                legend.y = text.bottom();
+            }
+        }
+        painter.text(rect.left_bottom() + vec2(4.0, -2.0), Align2::LEFT_BOTTOM, format!("{:.0} s", span), FontId::monospace(11.0), Color32::from_gray(140));
+    }
+
+    fn sites_grid(ui: &mut Ui, id: &str, sites: &[AllocationSite], goto: &mut Option<SourceLocation>) {
+        egui::Grid::new(id).striped(true).num_columns(3).show(ui, |ui| {
+            ui.strong("Location");
+            ui.strong("Size");
+            ui.strong("Blocks");
+            ui.end_row();
+            for site in sites {
+                if ui.link(format!("{}:{}", site.location.file, site.location.line)).clicked() {
+                    *goto = Some(site.location.clone());
+                }
+                ui.label(format_bytes(site.size));
+                ui.label(site.count.to_string());
+                ui.end_row();
+            }
+        });
+    }
+
+    fn comparison_ui(ui: &mut Ui, comparison: &Comparison, goto: &mut Option<SourceLocation>) {
+        let (old, new) = comparison.snapshots;
+        ui.label(format!("Snapshot #{} → #{}", old + 1, new + 1));
+
+        if comparison.sites.is_empty() {
+            ui.label("Turn on allocation tracing before taking snapshots to compare allocation sites.");
+        } else {
+            egui::Grid::new("memory_compare_sites").striped(true).num_columns(4).show(ui, |ui| {
+                ui.strong("Location");
+                ui.strong("Size");
+                ui.strong("Change");
+                ui.strong("Blocks");
+                ui.end_row();
+                for site in &comparison.sites {
+                    if ui.link(format!("{}:{}", site.location.file, site.location.line)).clicked() {
+                        *goto = Some(site.location.clone());
+                    }
+                    ui.label(format_bytes(site.size));
+                    ui.label(format_signed_bytes(site.size_diff));
+                    ui.label(format!("{:+}", site.count_diff));
+                    ui.end_row();
+                }
+            });
+        }
+
+        ui.add_space(6.0);
+        egui::Grid::new("memory_compare_types").striped(true).num_columns(3).show(ui, |ui| {
+            ui.strong("Type");
+            ui.strong("Objects");
+            ui.strong("Change");
+            ui.end_row();
+            for (name, count, diff) in comparison.types.iter().take(TOP_TYPES) {
+                ui.monospace(name);
+                ui.label(count.to_string());
+                let color = if *diff > 0 { Color32::from_rgb(230, 120, 80) } else { Color32::from_rgb(90, 200, 90) };
+                ui.colored_label(color, format!("{diff:+}"));
+                ui.end_row();
+            }
+        });
+    }
+
+    /// Returns an allocation site the user clicked, for the editor to jump to.
+    pub fn ui(&mut self, ui: &mut Ui, running: bool) -> Option<SourceLocation> {
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(running, |ui| {
+                if ui.checkbox(&mut self.tracing, "Trace allocations")
+                    .on_hover_text("Runs tracemalloc in the game, which slows allocation down")
+                    .changed()
+                {
+                    set_tracing(self.tracing);
+                }
+                if ui.button(format!("{} Take snapshot", egui_phosphor::regular::CAMERA)).clicked() {
+                    request_sample(ui.input(|i| i.time), true);
+                }
+            });
+            if ui.button("Clear").clicked() {
+                clear();
+            }
+        });
+        ui.add(egui::Separator::default().grow(5.0));
+
+        let state = STATE.lock().unwrap();
+        if state.history.is_empty() {
+            ui.label("Run the game to sample its memory every second.");
+            return None;
+        }
+
+        let mut goto = None;
+        let mut compare_request = None;
+        egui::ScrollArea::vertical().id_salt("memory_scroll").show(ui, |ui| {
+            Self::graph(ui, &state);
+
+            if state.snapshots.len() >= 2 {
+                ui.horizontal(|ui| {
+                    let label = |index: usize| format!("#{}", index + 1);
+                    egui::ComboBox::from_id_salt("memory_compare_old").selected_text(label(self.compare_old)).show_ui(ui, |ui| {
+                        for index in 0..state.snapshots.len() {
+                            ui.selectable_value(&mut self.compare_old, index, label(index));
+                        }
+                    });
+                    ui.label("→");
+                    egui::ComboBox::from_id_salt("memory_compare_new").selected_text(label(self.compare_new)).show_ui(ui, |ui| {
+                        for index in 0..state.snapshots.len() {
+                            ui.selectable_value(&mut self.compare_new, index, label(index));
+                        }
+                    });
+                    if ui.button("Compare snapshots").clicked() {
+                        compare_request = Some((self.compare_old, self.compare_new));
+                    }
+                });
+            } else {
+                ui.label("Take two snapshots to compare them.");
+            }
+
+            if let Some(comparison) = &state.comparison {
+                egui::CollapsingHeader::new("Comparison").default_open(true).show(ui, |ui| {
+                    Self::comparison_ui(ui, comparison, &mut goto);
+                });
+            }
+
+            if let Some(latest) = &state.latest {
+                egui::CollapsingHeader::new("Top allocation sites").default_open(true).show(ui, |ui| {
+                    if latest.sites.is_empty() {
+                        ui.label("Turn on allocation tracing to see where memory is allocated.");
+                    } else {
+                        Self::sites_grid(ui, "memory_sites", &latest.sites, &mut goto);
+                    }
+                });
+
+                egui::CollapsingHeader::new("Objects by type").default_open(true).show(ui, |ui| {
+                    let mut types: Vec<(&String, &usize)> = latest.types.iter().collect();
+                    types.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+                    ui.label(format!("{} objects", latest.types.values().sum::<usize>()));
+                    egui::Grid::new("memory_types").striped(true).num_columns(2).show(ui, |ui| {
+                        for (name, count) in types.into_iter().take(TOP_TYPES) {
+                            ui.monospace(name);
+                            ui.label(count.to_string());
+                            ui.end_row();
+                        }
+                    });
+                });
+            }
+        });
+
+        drop(state);
+        if let Some((old, new)) = compare_request {
+            compare(old, new);
+        }
+        goto
+    }
+}
@@ -1,4 +1,28 @@
 pub mod python;
 pub mod redengine;
 pub mod helpers;
-pub mod ui;
\ No newline at end of file
+pub mod ui;
+pub mod headless;
+pub mod golden;
+pub mod testing;
+pub mod capture;
+pub mod viewport;
+pub mod debug_draw;
+pub mod scene;
+pub mod state_diff;
+pub mod save_state;
+pub mod rewind;
+pub mod frame_history;
+pub mod editor;
+pub mod find;
+pub mod gutter;
+pub mod editing;
+pub mod debounce;
+pub mod diagnostics;
+pub mod lsp;
+pub mod profiler;
+pub mod memory;
+pub mod debugger;
+pub mod search;
+pub mod symbols;
+pub mod project_search;
\ No newline at end of file
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use egui::{pos2, vec2, Align2, Color32, FontId, Painter, Pos2, Rect, Sense, Stroke, Ui};
+use pyo3::prelude::*;
+
+use crate::engine::editor::SourceLocation;
+use crate::engine::python;
+
+
+/// Flattens `pstats` data into `(file, line, name, calls, self, cumulative, callers)` tuples,
+/// where `callers` pairs each calling function with the cumulative time spent through it.
+const PROFILE_SUMMARY: &str = r#"
+import pstats
+
+def summarize(profile):
+    functions = []
+    for (file, line, name), (_, calls, self_time, cumulative, callers) in pstats.Stats(profile).stats.items():
+        edges = [(caller, edge[3]) for caller, edge in callers.items()]
+        functions.append((file, line, name, calls, self_time, cumulative, edges))
+    return functions
+"#;
+
+/// Flame graph nodes narrower than this share of the total time are not drawn.
+const FLAME_MIN_SHARE: f64 = 0.001;
+const FLAME_MAX_DEPTH: usize = 48;
+const FLAME_ROW_HEIGHT: f32 = 18.0;
+
+/// Frames left to profile, set by the UI and picked up by the runner before the next step.
+static REQUESTED: AtomicUsize = AtomicUsize::new(0);
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref SESSION: std::sync::Mutex<Option<ProfileSession>> = std::sync::Mutex::new(None);
+    static ref REPORT: std::sync::Mutex<Option<Arc<ProfileReport>>> = std::sync::Mutex::new(None);
+}
+
+
+struct ProfileSession {
+    profile: Py<PyAny>,
+    frames: usize,
+    /// `None` profiles until stopped.
+    limit: Option<usize>,
+}
+
+type FunctionKey = (String, usize, String);
+
+pub struct FunctionStats {
+    pub location: SourceLocation,
+    pub name: String,
+    pub calls: usize,
+    /// Seconds spent in the function itself.
+    pub self_time: f64,
+    /// Seconds spent in the function and everything it called.
+    pub cumulative: f64,
+    /// Indices of calling functions with the cumulative time spent through each.
+    callers: Vec<(usize, f64)>,
+}
+
+pub struct ProfileReport {
+    pub frames: usize,
+    pub functions: Vec<FunctionStats>,
+}
+
+impl ProfileReport {
+    fn extract(py: Python<'_>, profile: &Bound<'_, PyAny>, frames: usize) -> PyResult<Self> {
+        let summarize = python::helper_module(py, "profiler", PROFILE_SUMMARY)?.getattr("summarize")?;
+
+        let rows: Vec<(String, usize, String, usize, f64, f64, Vec<(FunctionKey, f64)>)> = summarize.call1((profile,))?.extract()?;
+        let index: HashMap<FunctionKey, usize> = rows
+            .iter()
+            .enumerate()
+            .map(|(i, (file, line, name, ..))| ((file.clone(), *line, name.clone()), i))
+            .collect();
+
+        let functions = rows
+            .into_iter()
+            .map(|(file, line, name, calls, self_time, cumulative, callers)| FunctionStats {
+                location: SourceLocation { file, line },
+                name,
+                calls,
+                self_time,
+                cumulative,
+                callers: callers.into_iter().filter_map(|(key, time)| Some((*index.get(&key)?, time))).collect(),
+            })
+            .collect();
+
+        Ok(Self { frames, functions })
+    }
+}
+
+
+/// Starts profiling the next `frames` steps, or every step until stopped when `None`.
+pub fn start_capture(frames: Option<usize>) {
+    STOP_REQUESTED.store(false, Ordering::Relaxed);
+    REQUESTED.store(frames.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+pub fn stop_capture() {
+    STOP_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Drops a capture left unfinished by a game that ended, keeping the last report.
+pub fn clear() {
+    REQUESTED.store(0, Ordering::Relaxed);
+    STOP_REQUESTED.store(false, Ordering::Relaxed);
+    *SESSION.lock().unwrap() = None;
+}
+
+/// Frames profiled so far and the frame limit, while a capture runs.
+pub fn capture_progress() -> Option<(usize, Option<usize>)> {
+    let session = SESSION.lock().unwrap();
+    session.as_ref().map(|session| (session.frames, session.limit))
+}
+
+/// Enables the profiler around the next generator step. Called by the runner.
+pub fn begin_step(py: Python<'_>) {
+    let requested = REQUESTED.swap(0, Ordering::Relaxed);
+    if requested > 0 {
+        let profile = py.import_bound("cProfile").and_then(|module| module.getattr("Profile")?.call0());
+        match profile {
+            Ok(profile) => {
+                *SESSION.lock().unwrap() = Some(ProfileSession {
+                    profile: profile.unbind(),
+                    frames: 0,
+                    limit: (requested != usize::MAX).then_some(requested),
+                });
+            }
+            Err(e) => e.print(py),
+        }
+    }
+
+    // The session stays unlocked while calling into Python, as the UI polls it every frame.
+    let Some(profile) = current_profile(py) else {
+        return;
+    };
+    if let Err(e) = profile.bind(py).call_method0("enable") {
+        e.print(py);
+    }
+}
+
+/// Disables the profiler after a step and publishes the report once the capture is over.
+pub fn end_step(py: Python<'_>) {
+    let Some(profile) = current_profile(py) else {
+        return;
+    };
+    if let Err(e) = profile.bind(py).call_method0("disable") {
+        e.print(py);
+    }
+
+    let finished = {
+        let mut guard = SESSION.lock().unwrap();
+        let Some(session) = guard.as_mut() else {
+            return;
+        };
+        session.frames += 1;
+
+        let finished = STOP_REQUESTED.swap(false, Ordering::Relaxed) || session.limit.is_some_and(|limit| session.frames >= limit);
+        if !finished {
+            return;
+        }
+        guard.take()
+    };
+
+    if let Some(session) = finished {
+        match ProfileReport::extract(py, session.profile.bind(py), session.frames) {
+            Ok(report) => *REPORT.lock().unwrap() = Some(Arc::new(report)),
+            Err(e) => e.print(py),
+        }
+    }
+}
+
+fn current_profile(py: Python<'_>) -> Option<Py<PyAny>> {
+    SESSION.lock().unwrap().as_ref().map(|session| session.profile.clone_ref(py))
+}
+
+
+struct FlameNode {
+    function: usize,
+    /// Seconds spent in this call path.
+    time: f64,
+    children: Vec<FlameNode>,
+}
+
+/// Rebuilds approximate call stacks from caller edges. `cProfile` only records who called
+/// whom, so a child's time under each path is scaled by the parent's share of its total time.
+fn build_flame(report: &ProfileReport) -> Vec<FlameNode> {
+    let mut callees = vec![Vec::new(); report.functions.len()];
+    for (callee, function) in report.functions.iter().enumerate() {
+        for &(caller, time) in &function.callers {
+            callees[caller].push((callee, time));
+        }
+    }
+
+    let roots: Vec<usize> = (0..report.functions.len()).filter(|&i| report.functions[i].callers.is_empty()).collect();
+    let total: f64 = roots.iter().map(|&i| report.functions[i].cumulative).sum();
+
+    fn children(report: &ProfileReport, callees: &[Vec<(usize, f64)>], node: &FlameNode, path: &mut Vec<usize>, min_time: f64) -> Vec<FlameNode> {
+        if path.len() >= FLAME_MAX_DEPTH {
+            return Vec::new();
+        }
+        let total = report.functions[node.function].cumulative;
+        let share = if total > 0.0 { node.time / total } else { 0.0 };
+
+        let mut nodes = Vec::new();
+        for &(callee, time) in &callees[node.function] {
+            let time = time * share;
+            if time < min_time || path.contains(&callee) {
+                continue;
+            }
+            let mut child = FlameNode { function: callee, time, children: Vec::new() };
+            path.push(callee);
+            child.children = children(report, callees, &child, path, min_time);
+            path.pop();
+            nodes.push(child);
+        }
+        nodes.sort_by(|a, b| b.time.total_cmp(&a.time));
+        nodes
+    }
+
+    let min_time = total * FLAME_MIN_SHARE;
+    roots
+        .into_iter()
+        .map(|root| {
+            let mut node = FlameNode { function: root, time: report.functions[root].cumulative, children: Vec::new() };
+            node.children = children(report, &callees, &node, &mut vec![root], min_time);
+            node
+        })
+        .collect()
+}
+
+fn flame_depth(nodes: &[FlameNode]) -> usize {
+    nodes.iter().map(|node| 1 + flame_depth(&node.children)).max().unwrap_or(0)
+}
+
+fn flame_color(name: &str) -> Color32 {
+    let hash = name.bytes().fold(0u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(u32::from(byte)));
+    Color32::from_rgb(200 + (hash % 55) as u8, 80 + (hash / 55 % 120) as u8, 40 + (hash / 6600 % 40) as u8)
+}
+
+
+/// Pointer position over the flame graph and the function found under it while painting.
+struct FlameHover {
+    pos: Option<Pos2>,
+    function: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SortColumn {
+    Name,
+    Calls,
+    SelfTime,
+    Cumulative,
+}
+
+/// The Profiler tab: capture controls, a per-function table and a flame graph.
+pub struct ProfilerView {
+    pub capture_frames: usize,
+    sort: SortColumn,
+    descending: bool,
+    selected: Option<usize>,
+    flame: Option<(Arc<ProfileReport>, Vec<FlameNode>)>,
+}
+
+impl Default for ProfilerView {
+    fn default() -> Self {
+        Self {
+            capture_frames: 120,
+            sort: SortColumn::Cumulative,
+            descending: true,
+            selected: None,
+            flame: None,
+        }
+    }
+}
+
+impl ProfilerView {
+    fn sort_header(&mut self, ui: &mut Ui, column: SortColumn, label: &str) {
+        let text = match (self.sort == column, self.descending) {
+            (true, true) => format!("{label} {}", egui_phosphor::regular::CARET_DOWN),
+            (true, false) => format!("{label} {}", egui_phosphor::regular::CARET_UP),
+            (false, _) => label.to_owned(),
+        };
+        if ui.selectable_label(self.sort == column, egui::RichText::new(text).strong()).clicked() {
+            self.descending = self.sort != column || !self.descending;
+            self.sort = column;
+        }
+    }
+
+    fn table(&mut self, ui: &mut Ui, report: &ProfileReport) -> Option<SourceLocation> {
+        let mut order: Vec<usize> = (0..report.functions.len()).collect();
+        let functions = &report.functions;
+        order.sort_by(|&a, &b| {
+            let (a, b) = (&functions[a], &functions[b]);
+            match self.sort {
+                SortColumn::Name => a.name.cmp(&b.name),
+                SortColumn::Calls => a.calls.cmp(&b.calls),
+                SortColumn::SelfTime => a.self_time.total_cmp(&b.self_time),
+                SortColumn::Cumulative => a.cumulative.total_cmp(&b.cumulative),
+            }
+        });
+        if self.descending {
+            order.reverse();
+        }
+
+        let per_frame = |seconds: f64| seconds * 1000.0 / report.frames.max(1) as f64;
+        let mut goto = None;
+        egui::Grid::new("profiler_table").striped(true).num_columns(5).show(ui, |ui| {
+            self.sort_header(ui, SortColumn::Name, "Function");
+            ui.label(egui::RichText::new("Location").strong());
+            self.sort_header(ui, SortColumn::Calls, "Calls");
+            self.sort_header(ui, SortColumn::SelfTime, "Self ms/frame");
+            self.sort_header(ui, SortColumn::Cumulative, "Total ms/frame");
+            ui.end_row();
+
+            for index in order {
+                let function = &functions[index];
+                let response = ui.selectable_label(self.selected == Some(index), &function.name);
+                if response.clicked() {
+                    self.selected = Some(index);
+                    goto = (function.location.line > 0).then(|| function.location.clone());
+                }
+                ui.label(format!("{}:{}", function.location.file, function.location.line));
+                ui.label(function.calls.to_string());
+                ui.label(format!("{:.3}", per_frame(function.self_time)));
+                ui.label(format!("{:.3}", per_frame(function.cumulative)));
+                ui.end_row();
+            }
+        });
+        goto
+    }
+
+    fn paint_flame(&self, painter: &Painter, report: &ProfileReport, nodes: &[FlameNode], rect: Rect, scale: f64, hover: &mut FlameHover) {
+        let mut x = rect.left();
+        for node in nodes {
+            let width = (node.time * scale) as f32;
+            let node_rect = Rect::from_min_size(pos2(x, rect.top()), vec2(width, FLAME_ROW_HEIGHT));
+            x += width;
+            if width < 1.0 {
+                continue;
+            }
+
+            let function = &report.functions[node.function];
+            painter.rect_filled(node_rect.shrink(0.5), 2.0, flame_color(&function.name));
+            if self.selected == Some(node.function) {
+                painter.rect_stroke(node_rect, 2.0, Stroke::new(2.0, Color32::WHITE), egui::StrokeKind::Inside);
+            }
+            if width > 30.0 {
+                let text_painter = painter.with_clip_rect(node_rect.intersect(painter.clip_rect()));
+                text_painter.text(node_rect.left_center() + vec2(4.0, 0.0), Align2::LEFT_CENTER, &function.name, FontId::proportional(12.0), Color32::BLACK);
+            }
+            if hover.pos.is_some_and(|pos| node_rect.contains(pos)) {
+                hover.function = Some(node.function);
+            }
+
+            let below = Rect::from_min_max(pos2(node_rect.left(), node_rect.bottom()), pos2(node_rect.right(), rect.bottom()));
+            self.paint_flame(painter, report, &node.children, below, scale, hover);
+        }
+    }
+
+    fn flame_graph(&mut self, ui: &mut Ui, report: &Arc<ProfileReport>) -> Option<SourceLocation> {
+        if self.flame.as_ref().is_none_or(|(cached, _)| !Arc::ptr_eq(cached, report)) {
+            self.flame = Some((report.clone(), build_flame(report)));
+        }
+        let Some((_, roots)) = &self.flame else {
+            return None;
+        };
+
+        let total: f64 = roots.iter().map(|node| node.time).sum();
+        let height = flame_depth(roots) as f32 * FLAME_ROW_HEIGHT;
+        let (rect, response) = ui.allocate_exact_size(vec2(ui.available_width(), height), Sense::click());
+        if total <= 0.0 {
+            return None;
+        }
+
+        let mut hover = FlameHover { pos: response.hover_pos(), function: None };
+        let scale = f64::from(rect.width()) / total;
+        self.paint_flame(&ui.painter_at(rect), report, roots, rect, scale, &mut hover);
+
+        let function = &report.functions[hover.function?];
+        let response = response.on_hover_text(format!(
+            "{}\n{}:{}\n{:.3} ms/frame total",
+            function.name,
+            function.location.file,
+            function.location.line,
+            function.cumulative * 1000.0 / report.frames.max(1) as f64,
+        ));
+        if response.clicked() {
+            self.selected = hover.function;
+            return (function.location.line > 0).then(|| function.location.clone());
+        }
+        None
+    }
+
+    /// Returns the source line of an entry the user clicked, for the editor to jump to.
+    pub fn ui(&mut self, ui: &mut Ui, running: bool) -> Option<SourceLocation> {
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(running, |ui| {
+                if let Some((frames, limit)) = capture_progress() {
+                    if ui.button(format!("{} Stop", egui_phosphor::regular::STOP_CIRCLE)).clicked() {
+                        stop_capture();
+                    }
+                    match limit {
+                        Some(limit) => ui.label(format!("Profiling {frames}/{limit} frames")),
+                        None => ui.label(format!("Profiling {frames} frames")),
+                    };
+                    ui.ctx().request_repaint();
+                } else {
+                    if ui.button(format!("{} Profile", egui_phosphor::regular::TIMER)).on_disabled_hover_text("Run the game to profile it").clicked() {
+                        start_capture((self.capture_frames > 0).then_some(self.capture_frames));
+                    }
+                    ui.add(egui::DragValue::new(&mut self.capture_frames).prefix("Frames: "))
+                        .on_hover_text("Number of frames to profile, 0 profiles until stopped");
+                }
+            });
+        });
+        ui.add(egui::Separator::default().grow(5.0));
+
+        let Some(report) = REPORT.lock().unwrap().clone() else {
+            ui.label("Profile the running game to see where each frame's time goes.");
+            return None;
+        };
+
+        ui.label(format!("{} functions over {} frames", report.functions.len(), report.frames));
+        let mut goto = None;
+        egui::ScrollArea::vertical().id_salt("profiler_scroll").show(ui, |ui| {
+            egui::CollapsingHeader::new("Flame graph").default_open(true).show(ui, |ui| {
+                if let Some(location) = self.flame_graph(ui, &report) {
+                    goto = Some(location);
+                }
+            });
+            egui::CollapsingHeader::new("Functions").default_open(true).show(ui, |ui| {
+                if let Some(location) = self.table(ui, &report) {
+                    goto = Some(location);
+                }
+            });
+        });
+        goto
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use egui::{text::LayoutJob, Color32, TextFormat, Ui};
+use regex::Regex;
+
+use crate::engine::editor::{Editor, SourceLocation};
+use crate::engine::find::{self, FindOptions};
+use crate::engine::search::{ContentResults, ContentSearch, SearchMatch, MAX_MATCHES};
+
+
+const REMOVED_COLOR: Color32 = Color32::from_rgb(230, 110, 110);
+const ADDED_COLOR: Color32 = Color32::from_rgb(110, 200, 110);
+const MATCH_BACKGROUND: Color32 = Color32::from_rgba_premultiplied(70, 60, 0, 70);
+
+/// `text` with every line run through `find::replace_all`, matching how results are listed.
+fn replace_lines(text: &str, regex: &Regex, replacement: &str, options: FindOptions) -> String {
+    text.split_inclusive('\n')
+        .map(|line| {
+            let (content, ending) = line
+                .strip_suffix("\r\n")
+                .map(|content| (content, "\r\n"))
+                .or_else(|| line.strip_suffix('\n').map(|content| (content, "\n")))
+                .unwrap_or((line, ""));
+            let mut replaced = find::replace_all(content, regex, replacement, options);
+            replaced.push_str(ending);
+            replaced
+        })
+        .collect()
+}
+
+
+/// One file rewritten by a replace, kept so it can be undone.
+struct FileChange {
+    path: PathBuf,
+    before: String,
+    after: String,
+}
+
+/// Where a file's new contents are written before being moved over it.
+fn staged_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{name}.redengine-replace"))
+}
+
+/// Writes the `after` (or `before` when undoing) contents of every change, or none of them.
+/// Everything is staged next to its file first so a failed write leaves the project untouched.
+fn write_all(changes: &[FileChange], undo: bool) -> Result<(), String> {
+    let contents = |change: &FileChange| if undo { change.before.clone() } else { change.after.clone() };
+    let previous = |change: &FileChange| if undo { change.after.clone() } else { change.before.clone() };
+
+    for (index, change) in changes.iter().enumerate() {
+        if let Err(e) = fs::write(staged_path(&change.path), contents(change)) {
+            for staged in &changes[..=index] {
+                fs::remove_file(staged_path(&staged.path)).ok();
+            }
+            return Err(format!("Could not write {}: {e}", change.path.display()));
+        }
+    }
+
+    for (index, change) in changes.iter().enumerate() {
+        if let Err(e) = fs::rename(staged_path(&change.path), &change.path) {
+            for done in &changes[..index] {
+                fs::write(&done.path, previous(done)).ok();
+            }
+            for pending in &changes[index..] {
+                fs::remove_file(staged_path(&pending.path)).ok();
+            }
+            return Err(format!("Could not replace {}: {e}", change.path.display()));
+        }
+    }
+    Ok(())
+}
+
+/// One line of a result, leading indentation dropped, with `ranges` given a background.
+fn line_job(ui: &Ui, prefix: &str, text: &str, ranges: &[Range<usize>], color: Color32) -> LayoutJob {
+    let plain = TextFormat { font_id: egui::TextStyle::Monospace.resolve(ui.style()), color, ..Default::default() };
+    let highlighted = TextFormat { background: MATCH_BACKGROUND, ..plain.clone() };
+
+    let mut job = LayoutJob::default();
+    job.append(prefix, 0.0, TextFormat { color: ui.visuals().weak_text_color(), ..plain.clone() });
+
+    let indent = text.len() - text.trim_start().len();
+    let mut from = indent;
+    for range in ranges.iter().filter(|range| range.end > indent) {
+        let start = range.start.max(indent);
+        job.append(&text[from..start], 0.0, plain.clone());
+        job.append(&text[start..range.end], 0.0, highlighted.clone());
+        from = range.end;
+    }
+    job.append(&text[from..], 0.0, plain);
+    job
+}
+
+
+/// The Search tab: find, and optionally replace, across every text file of the project.
+#[derive(Default)]
+pub struct ProjectSearchView {
+    query: String,
+    replacement: String,
+    options: FindOptions,
+    replacing: bool,
+    regex: Option<Result<Regex, String>>,
+    search: ContentSearch,
+    /// Query and options the results are for.
+    searched: Option<(String, FindOptions)>,
+    /// Files left out of a replace.
+    excluded: BTreeSet<PathBuf>,
+    /// Files changed by the last replace, to undo it.
+    last_replace: Vec<FileChange>,
+    status: String,
+}
+
+impl ProjectSearchView {
+    fn restart(&mut self, root: &Path) {
+        self.regex = find::build_regex(&self.query, self.options);
+        self.searched = Some((self.query.clone(), self.options));
+        self.excluded.clear();
+        match &self.regex {
+            Some(Ok(regex)) => self.search.start(root.to_owned(), regex.clone()),
+            _ => self.search.clear(),
+        }
+    }
+
+    /// Replaces every match in the files not excluded, as one change that can be undone.
+    fn replace(&mut self, regex: &Regex, editor: &mut Editor) {
+        let results = self.search.results();
+        let paths: Vec<PathBuf> = results
+            .by_file()
+            .map(|group| group[0].path.clone())
+            .filter(|path| !self.excluded.contains(path))
+            .collect();
+        if let Some(path) = paths.iter().find(|path| editor.is_unsaved(path)) {
+            self.status = format!("Save {} before replacing in it", path.display());
+            return;
+        }
+
+        let mut changes = vec![];
+        for path in paths {
+            let before = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) => {
+                    self.status = format!("Could not read {}: {e}", path.display());
+                    return;
+                }
+            };
+            let after = replace_lines(&before, regex, &self.replacement, self.options);
+            if after != before {
+                changes.push(FileChange { path, before, after });
+            }
+        }
+
+        match write_all(&changes, false) {
+            Ok(()) => {
+                self.status = format!("Replaced in {} files", changes.len());
+                editor.reload(changes.iter().map(|change| change.path.as_path()));
+                self.last_replace = changes;
+            }
+            Err(e) => self.status = e,
+        }
+    }
+
+    /// Puts back the files of the last replace, unless any changed since.
+    fn undo(&mut self, editor: &mut Editor) {
+        let changed = self.last_replace.iter().find(|change| {
+            editor.is_unsaved(&change.path) || fs::read_to_string(&change.path).ok().as_ref() != Some(&change.after)
+        });
+        if let Some(change) = changed {
+            self.status = format!("{} changed since the replace, so it cannot be undone", change.path.display());
+            return;
+        }
+
+        let changes = std::mem::take(&mut self.last_replace);
+        match write_all(&changes, true) {
+            Ok(()) => {
+                self.status = format!("Restored {} files", changes.len());
+                editor.reload(changes.iter().map(|change| change.path.as_path()));
+            }
+            Err(e) => {
+                self.status = e;
+                self.last_replace = changes;
+            }
+        }
+    }
+
+    /// Query field, search options and the replace toggle. Returns whether Search Again was clicked.
+    fn toolbar(&mut self, ui: &mut Ui) -> bool {
+        let mut refresh = false;
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .hint_text("Search in project")
+                    .desired_width(260.0),
+            );
+            self.options.ui(ui);
+            if ui.small_button(egui_phosphor::regular::ARROWS_CLOCKWISE).on_hover_text("Search again").clicked() {
+                refresh = true;
+            }
+            ui.toggle_value(&mut self.replacing, egui_phosphor::regular::SWAP).on_hover_text("Replace");
+        });
+        refresh
+    }
+
+    /// Replacement field with the replace and undo buttons. Returns whether each was clicked.
+    fn replace_row(&mut self, ui: &mut Ui, can_replace: bool, included: usize) -> (bool, bool) {
+        let mut replace = false;
+        let mut undo = false;
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.replacement)
+                    .hint_text(if self.options.regex { "Replace ($1 for groups)" } else { "Replace" })
+                    .desired_width(260.0),
+            );
+            if ui.add_enabled(can_replace, egui::Button::new(format!("Replace in {included} files")))
+                .on_disabled_hover_text("Wait for the search to finish, with fewer than the result limit")
+                .clicked()
+            {
+                replace = true;
+            }
+            if !self.last_replace.is_empty()
+                && ui.button(format!("{} Undo Replace", egui_phosphor::regular::ARROW_COUNTER_CLOCKWISE)).clicked()
+            {
+                undo = true;
+            }
+        });
+        (replace, undo)
+    }
+
+    /// Match counts, search progress and the outcome of the last replace.
+    fn summary(&self, ui: &mut Ui, results: &ContentResults, files: usize) {
+        ui.horizontal(|ui| {
+            match &self.regex {
+                Some(Err(e)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, "Invalid regex").on_hover_text(e);
+                }
+                Some(Ok(_)) => {
+                    ui.label(format!("{} matches in {files} files", results.matches.len()));
+                }
+                None => {}
+            }
+            if results.running {
+                ui.spinner();
+                ui.ctx().request_repaint();
+            } else if results.truncated {
+                ui.weak(format!("(first {MAX_MATCHES})"));
+            }
+            if !self.status.is_empty() {
+                ui.separator();
+                ui.weak(&self.status);
+            }
+        });
+    }
+
+    /// Matches grouped by file, previewing the replacement of each while replacing.
+    /// Returns the location of a match that was clicked.
+    fn result_list(&mut self, ui: &mut Ui, root: &Path, files: &[&[SearchMatch]], regex: Option<&Regex>) -> Option<SourceLocation> {
+        let mut clicked = None;
+        egui::ScrollArea::vertical().id_salt("project_search_results").show(ui, |ui| {
+            for group in files {
+                let path = &group[0].path;
+                let name = path.strip_prefix(root).unwrap_or(path).display().to_string();
+                let id = ui.make_persistent_id(("project_search_file", path));
+                egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true)
+                    .show_header(ui, |ui| {
+                        if self.replacing {
+                            let mut include = !self.excluded.contains(path);
+                            if ui.checkbox(&mut include, "").on_hover_text("Include in replace").changed() {
+                                if include {
+                                    self.excluded.remove(path);
+                                } else {
+                                    self.excluded.insert(path.clone());
+                                }
+                            }
+                        }
+                        ui.label(format!("{} {name} ({})", egui_phosphor::regular::FILE, group.len()));
+                    })
+                    .body(|ui| {
+                        let preview = regex.filter(|_| self.replacing && !self.excluded.contains(path));
+                        for found in *group {
+                            let prefix = format!("{:>4}  ", found.line);
+                            let Some(regex) = preview else {
+                                let job = line_job(ui, &prefix, &found.text, &found.ranges, ui.visuals().text_color());
+                                if ui.selectable_label(false, job).clicked() {
+                                    clicked = Some(SourceLocation { file: path.display().to_string(), line: found.line });
+                                }
+                                continue;
+                            };
+
+                            let replaced = find::replace_all(&found.text, regex, &self.replacement, self.options);
+                            let removed = line_job(ui, &format!("{prefix}- "), &found.text, &found.ranges, REMOVED_COLOR);
+                            let added = line_job(ui, &format!("{}+ ", " ".repeat(prefix.len())), &replaced, &[], ADDED_COLOR);
+                            if ui.selectable_label(false, removed).clicked() {
+                                clicked = Some(SourceLocation { file: path.display().to_string(), line: found.line });
+                            }
+                            ui.label(added);
+                        }
+                    });
+            }
+        });
+        clicked
+    }
+
+    /// Returns the location of a result that was clicked.
+    pub fn ui(&mut self, ui: &mut Ui, root: Option<&Path>, editor: &mut Editor) -> Option<SourceLocation> {
+        let Some(root) = root else {
+            ui.centered_and_justified(|ui| {
+                ui.label("Open a project to search it.");
+            });
+            return None;
+        };
+
+        let refresh = self.toolbar(ui);
+
+        let results = self.search.results();
+        let files: Vec<&[_]> = results.by_file().collect();
+        let included = files.iter().filter(|group| !self.excluded.contains(&group[0].path)).count();
+        let regex = self.regex.as_ref().and_then(|regex| regex.as_ref().ok()).cloned();
+
+        let (mut replace, mut undo) = (false, false);
+        if self.replacing {
+            let can_replace = regex.is_some() && !results.running && !results.truncated && included > 0;
+            (replace, undo) = self.replace_row(ui, can_replace, included);
+        }
+
+        self.summary(ui, &results, files.len());
+        ui.add(egui::Separator::default().grow(5.0));
+        let clicked = self.result_list(ui, root, &files, regex.as_ref());
+
+        if refresh || self.searched.as_ref() != Some(&(self.query.clone(), self.options)) {
+            self.restart(root);
+        }
+        if let (true, Some(regex)) = (replace, &regex) {
+            self.replace(regex, editor);
+            self.restart(root);
+        }
+        if undo {
+            self.undo(editor);
+            self.restart(root);
+        }
+        clicked
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::replace_lines;
+    use crate::engine::find::{self, FindOptions};
+
+    fn replace(text: &str, query: &str, replacement: &str, options: FindOptions) -> String {
+        let regex = find::build_regex(query, options).expect("query is not empty").expect("query compiles");
+        replace_lines(text, &regex, replacement, options)
+    }
+
+    #[test]
+    fn keeps_line_endings() {
+        let options = FindOptions::default();
+        assert_eq!(replace("foo bar\nfoo\n", "foo", "qux", options), "qux bar\nqux\n");
+        assert_eq!(replace("foo bar\r\nfoo\r\nbaz", "foo", "qux", options), "qux bar\r\nqux\r\nbaz");
+        assert_eq!(replace("foo\r\n\r\n", "foo", "", options), "\r\n\r\n");
+    }
+
+    #[test]
+    fn regex_anchors_do_not_see_carriage_returns() {
+        let options = FindOptions { regex: true, ..Default::default() };
+        assert_eq!(replace("a = 1\r\nb = 2\r\n", r"(\d)$", "[$1]", options), "a = [1]\r\nb = [2]\r\n");
+        assert_eq!(replace("x\r\n", r"\s+", "_", options), "x\r\n");
+    }
+
+    #[test]
+    fn leaves_unmatched_text_untouched() {
+        let text = "no match here\r\nnor here\n";
+        assert_eq!(replace(text, "foo", "bar", FindOptions::default()), text);
+    }
+}
@@ -1,10 +1,14 @@
 use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::collections::VecDeque;
+use std::path::Path;
+use std::time::Duration;
 
 use egui::{mutex::Mutex, ColorImage};
 
-use pyo3::{prelude::*, types::PyIterator};
-use pyo3::types::PyBytes;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyIterator, PyString};
 
 
 // static GAME_INSTANCE: OnceLock<Py<PyAny>> = OnceLock::new();
@@ -15,70 +19,316 @@ lazy_static::lazy_static! {
 }
 pub type Instruction = Box<dyn Fn(Python) + Send + 'static>;
 
+static PAUSED: AtomicBool = AtomicBool::new(false);
+static STEP_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+/// Set while the editor's game thread is running a game.
+static GAME_THREAD_RUNNING: AtomicBool = AtomicBool::new(false);
+/// Number of frames the running game has stepped.
+pub static FRAME_COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// Bumped whenever `FRAME_IMAGE` is replaced, so the viewport only uploads new frames.
+pub static FRAME_SERIAL: AtomicUsize = AtomicUsize::new(0);
 
-pub fn run_code_threaded(code_string: &str) {
-    let mut code = String::new();
-    code_string.clone_into(&mut code);
+/// Resolution of the `_frame_buffer` every game script renders into.
+pub const DEFAULT_FRAME_SIZE: [usize; 2] = [1280, 720];
+
+
+/// A running game: the `game` instance from `__main__` and the generator returned by its `test_run`.
+pub struct GameSession {
+    pub game: Py<PyAny>,
+    generator: Py<PyIterator>,
+    pub size: [usize; 2],
+    pub frame: usize,
+}
+
+impl GameSession {
+    /// Runs `code` inside `__main__` and starts the `game.test_run()` generator.
+    /// `filename` is what tracebacks and tools report for the script, and its folder is
+    /// added to `sys.path` so the script can import its neighbours.
+    pub fn launch(py: Python<'_>, code: &str, filename: &str, size: [usize; 2]) -> PyResult<Self> {
+        crate::engine::debug_draw::install_module(py)?;
+
+        if let Some(dir) = Path::new(filename).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            let sys_path = py.import_bound("sys")?.getattr("path")?;
+            let dir = dir.display().to_string();
+            if !sys_path.contains(&dir)? {
+                sys_path.call_method1("insert", (0, dir))?;
+            }
+        }
+
+        let main_module = PyModule::import_bound(py, "__main__")?;
+        let globals = main_module.dict();
+        globals.set_item("__file__", filename)?;
+        let builtins = py.import_bound("builtins")?;
+        let compiled = builtins.getattr("compile")?.call1((code, filename, "exec"))?;
+        builtins.getattr("exec")?.call1((compiled, &globals))?;
+
+        let game = main_module.getattr("game")?;
+        let generator = PyIterator::from_bound_object(&game.call_method0("test_run")?)?;
+
+        Ok(Self {
+            game: game.unbind(),
+            generator: generator.unbind(),
+            size,
+            frame: 0,
+        })
+    }
+
+    /// Another handle on the same running game, stepping the same generator.
+    pub fn clone_ref(&self, py: Python<'_>) -> Self {
+        Self {
+            game: self.game.clone_ref(py),
+            generator: self.generator.clone_ref(py),
+            size: self.size,
+            frame: self.frame,
+        }
+    }
+
+    /// Advances the game by one frame, returning `None` once `test_run` has finished.
+    pub fn step(&mut self, py: Python<'_>) -> PyResult<Option<ColorImage>> {
+        let mut generator = self.generator.bind(py).clone();
+        match generator.next() {
+            Some(result) => {
+                result?;
+            }
+            None => return Ok(None),
+        }
+
+        self.frame += 1;
+        self.frame_image(py).map(Some)
+    }
+
+    /// Reads the game's `_frame_buffer` into an image.
+    pub fn frame_image(&self, py: Python<'_>) -> PyResult<ColorImage> {
+        read_frame_buffer(self.game.bind(py), self.size)
+    }
+}
+
+/// Reads `game._frame_buffer` into an image of `size`.
+pub fn read_frame_buffer(game: &Bound<'_, PyAny>, size: [usize; 2]) -> PyResult<ColorImage> {
+    let py_frame_buffer = game.getattr("_frame_buffer")?;
+    let frame_buffer = py_frame_buffer.downcast::<PyBytes>()?;
+    let bytes = frame_buffer.as_bytes();
+
+    let [width, height] = size;
+    if bytes.len() != width * height * 4 {
+        return Err(PyValueError::new_err(format!(
+            "_frame_buffer holds {} bytes, expected {width}x{height} RGBA",
+            bytes.len()
+        )));
+    }
+
+    Ok(ColorImage::from_rgba_unmultiplied(size, bytes))
+}
+
+/// Redraws the game without stepping it and shows the result in the viewport.
+/// Used after its state was replaced while paused.
+pub fn redraw_viewport(game: &Bound<'_, PyAny>) -> PyResult<()> {
+    if game.hasattr("draw")? {
+        game.call_method0("draw")?;
+    }
+
+    let image = read_frame_buffer(game, DEFAULT_FRAME_SIZE)?;
+    if let Some(lock) = FRAME_IMAGE.get() {
+        *lock.lock() = Some(image);
+        FRAME_SERIAL.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+
+/// Seeds `random` (and `numpy.random` when it is installed) so runs are reproducible.
+pub fn seed_random(py: Python<'_>, seed: u64) -> PyResult<()> {
+    py.import_bound("random")?.call_method1("seed", (seed,))?;
+
+    if let Ok(numpy_random) = py.import_bound("numpy.random") {
+        numpy_random.call_method1("seed", (seed % (1 << 32),))?;
+    }
+    Ok(())
+}
+
+/// Posts a `KEYDOWN` or `KEYUP` event for `key` (a pygame key name such as `"w"` or `"space"`).
+pub fn post_key_event(py: Python<'_>, key: &str, down: bool) -> PyResult<()> {
+    let pygame = py.import_bound("pygame")?;
+    let key_code = pygame.getattr("key")?.call_method1("key_code", (key,))?;
+    let event_type = pygame.getattr(if down { "KEYDOWN" } else { "KEYUP" })?;
+
+    let attributes = PyDict::new_bound(py);
+    attributes.set_item("key", key_code)?;
+    let event = pygame.getattr("event")?.call_method1("Event", (event_type, attributes))?;
+    pygame.getattr("event")?.call_method1("post", (event,))?;
+    Ok(())
+}
+
+
+/// Returns the `redengine` module that scripts import, creating it on first use.
+pub fn redengine_module(py: Python<'_>) -> PyResult<Bound<'_, PyModule>> {
+    let sys_modules = py.import_bound("sys")?.getattr("modules")?;
+
+    if let Ok(module) = sys_modules.get_item("redengine") {
+        return Ok(module.downcast_into::<PyModule>()?);
+    }
+
+    let module = PyModule::new_bound(py, "redengine")?;
+    sys_modules.set_item("redengine", &module)?;
+    Ok(module)
+}
+
+/// Registers `module` as `redengine.<name>` so it can be imported from scripts.
+pub fn register_submodule(py: Python<'_>, name: &str, module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let sys_modules = py.import_bound("sys")?.getattr("modules")?;
+    sys_modules.set_item(format!("redengine.{name}"), module)?;
+    redengine_module(py)?.setattr(name, module)
+}
+
+/// Returns the editor's helper module `name` built from `code`, compiling it on first use.
+/// It is cached in `sys.modules` as `redengine._<name>`, so probes that run every few frames
+/// are not recompiled each time.
+pub fn helper_module<'py>(py: Python<'py>, name: &str, code: &str) -> PyResult<Bound<'py, PyModule>> {
+    helper_module_with(py, name, code, |_| Ok(()))
+}
+
+/// Like `helper_module`, running `setup` once when the module is first built, e.g. to add the
+/// functions implemented in Rust that its code calls.
+pub fn helper_module_with<'py>(
+    py: Python<'py>,
+    name: &str,
+    code: &str,
+    setup: impl FnOnce(&Bound<'py, PyModule>) -> PyResult<()>,
+) -> PyResult<Bound<'py, PyModule>> {
+    let sys_modules = py.import_bound("sys")?.getattr("modules")?;
+    let qualified = format!("redengine._{name}");
+    if let Ok(module) = sys_modules.get_item(&qualified) {
+        return Ok(module.downcast_into::<PyModule>()?);
+    }
+
+    let module = PyModule::from_code_bound(py, code, &format!("redengine_{name}.py"), &qualified)?;
+    setup(&module)?;
+    sys_modules.set_item(&qualified, &module)?;
+    Ok(module)
+}
+
+/// Formats a Python exception the same way the interpreter prints it.
+pub fn format_exception(py: Python<'_>, err: &PyErr) -> String {
+    let format = || -> PyResult<String> {
+        let lines = py.import_bound("traceback")?.call_method1(
+            "format_exception",
+            (err.get_type_bound(py), err.value_bound(py), err.traceback_bound(py)),
+        )?;
+        PyString::new_bound(py, "").call_method1("join", (lines,))?.extract()
+    };
+
+    format().unwrap_or_else(|_| err.to_string())
+}
+
+
+/// Runs queued instructions inside the current interpreter.
+pub fn run_queued_instructions(py: Python<'_>) {
+    while let Some(task) = INSTRUCTION_QUEUE.lock().unwrap().pop_front() {
+        task(py);
+    }
+}
+
+pub fn set_paused(paused: bool) {
+    PAUSED.store(paused, Ordering::Relaxed);
+    STEP_REQUESTS.store(0, Ordering::Relaxed);
+}
+
+pub fn is_paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+/// Advances a paused game by one frame.
+pub fn request_step() {
+    STEP_REQUESTS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn take_step() -> bool {
+    !is_paused() || STEP_REQUESTS.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1)).is_ok()
+}
+
+/// Runs `code` on the current thread, handing every rendered frame to `on_frame`.
+/// The game stops early when `on_frame` returns `false`, and holds while paused.
+pub fn run_code<F>(py: Python<'_>, code: &str, filename: &str, size: [usize; 2], mut on_frame: F) -> PyResult<()>
+where
+    F: FnMut(Python<'_>, &GameSession, ColorImage) -> bool,
+{
+    let mut session = GameSession::launch(py, code, filename, size)?;
+    FRAME_COUNTER.store(0, Ordering::Relaxed);
+
+    loop {
+        run_queued_instructions(py);
+        // Instructions may move the counter, e.g. when a rewind restores an earlier frame.
+        session.frame = FRAME_COUNTER.load(Ordering::Relaxed);
+
+        if !take_step() {
+            // Release the GIL so other threads can use Python while the game is paused.
+            py.allow_threads(|| std::thread::sleep(Duration::from_millis(10)));
+            continue;
+        }
+
+        crate::engine::profiler::begin_step(py);
+        let frame = session.step(py);
+        crate::engine::profiler::end_step(py);
+        let frame = frame?;
+        FRAME_COUNTER.store(session.frame, Ordering::Relaxed);
+
+        match frame {
+            Some(image) => {
+                if !on_frame(py, &session, image) {
+                    break;
+                }
+            }
+            None => break,
+        }
+    }
+
+    Ok(())
+}
+
+pub fn run_code_threaded(code_string: &str, filename: &str) {
+    let code = code_string.to_owned();
+    let filename = filename.to_owned();
 
-    
     pyo3::prepare_freethreaded_python();
+    set_paused(false);
+    GAME_THREAD_RUNNING.store(true, Ordering::Relaxed);
 
     std::thread::spawn(move || {
         Python::with_gil(|py| {
-            let _ = Python::run_bound(py, &code, None, None);
-          
-            let main_module = PyModule::import(py, "__main__").expect("Import __main__ failed");
-            let game = main_module.getattr("game").unwrap();
-            println!("Running test method after instancing: {}", game);
-            
-            match game.call_method0("test_run") {
-                Ok(generator) => {
-                    let mut gen_iter = PyIterator::from_object(generator).unwrap();
-
-                    loop { // or loop forever
-                        
-                        if let Some(task) = INSTRUCTION_QUEUE.lock().unwrap().pop_front() {
-                            task(py); // run inside current Python interpreter
-                        }
-
-                        let pool = unsafe { py.new_pool() };
-
-                        match gen_iter.next() {
-                            Some(_) => {
-                                let py_frame_buffer = game.getattr("_frame_buffer").unwrap();
-
-                                let pybuf: Result<&PyBytes, pyo3::PyDowncastError<'_>> = py_frame_buffer.downcast::<PyBytes>();
-                                let frame_buffer_bytes = pybuf.unwrap().as_bytes();
-
-                                let image = egui::ColorImage::from_rgba_unmultiplied([1280, 720], frame_buffer_bytes);
-                                
-                                if let Some(lock) = FRAME_IMAGE.get() {
-                                    let mut slot = lock.lock();
-                                    *slot = Some(image); // 🔁 Overwrite each frame
-                                }
-
-                                drop(pool);
-                            }
-                            // Some(Err(e)) => {
-                            //     e.print(py);
-                            //     break;
-                            // }
-                            None => break,
-                            
-                        }
-                    }
+            if let Err(e) = crate::engine::debugger::install(py) {
+                e.print(py);
+            }
+
+            let result = run_code(py, &code, &filename, DEFAULT_FRAME_SIZE, |py, session, image| {
+                crate::engine::capture::record_frame(&image);
+                crate::engine::frame_history::record(session.frame, &image);
+                crate::engine::debug_draw::end_frame();
+                crate::engine::rewind::record(py, session.game.bind(py), session.frame);
+                if is_paused() {
+                    crate::engine::state_diff::capture(py, session.game.bind(py), session.frame);
                 }
-                Err(e) => {
-                    e.print(py);
+
+                if let Some(lock) = FRAME_IMAGE.get() {
+                    let mut slot = lock.lock();
+                    *slot = Some(image); // 🔁 Overwrite each frame
+                    FRAME_SERIAL.fetch_add(1, Ordering::Relaxed);
                 }
+                true
+            });
+
+            if let Err(e) = result {
+                e.print(py);
             }
-            
         });
 
+        GAME_THREAD_RUNNING.store(false, Ordering::Relaxed);
         println!("Gracefully closing thread.")
     });
-    
+}
 
+/// Whether a game launched from the editor is still running in the interpreter.
+pub fn game_thread_running() -> bool {
+    GAME_THREAD_RUNNING.load(Ordering::Relaxed)
 }
 
 pub fn queue_python_instruction<F>(func: F)
@@ -108,4 +358,3 @@ where
 //         });
 //     });
 // }
-
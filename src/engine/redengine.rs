@@ -1,9 +1,12 @@
 use core::fmt;
-use std::{fs, path::PathBuf};
+use std::{fs, path::{Path, PathBuf}};
 
 use egui::Context;
 use serde::{Deserialize, Serialize};
 
+use crate::engine::rewind::RewindSettings;
+use crate::engine::viewport::{ScalingMode, TextureFilter};
+
 
 #[derive(Serialize, Deserialize)]
 pub enum FileExplorerItemType {
@@ -84,11 +87,55 @@ impl FileExplorerItem {
 
 
 
+/// Script that Play, the headless runner and tests start from, relative to the project root.
+pub const ENTRY_FILE: &str = "main.py";
+/// Per-project editor settings, kept in the project folder so they travel with the game.
+pub const SETTINGS_FILE: &str = "redengine.ron";
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ProjectSettings {
+    pub scaling_mode: ScalingMode,
+    pub texture_filter: TextureFilter,
+    pub letterbox_color: [u8; 3],
+    pub rewind: RewindSettings,
+}
+
+impl Default for ProjectSettings {
+    fn default() -> Self {
+        Self {
+            scaling_mode: ScalingMode::Fit,
+            // Pixel art stays sharp unless the project asks for smoothing.
+            texture_filter: TextureFilter::Nearest,
+            letterbox_color: [0, 0, 0],
+            rewind: RewindSettings::default(),
+        }
+    }
+}
+
+impl ProjectSettings {
+    /// Reads the project's settings file, using defaults when it is missing or invalid.
+    pub fn load(project_path: &Path) -> Self {
+        fs::read_to_string(project_path.join(SETTINGS_FILE))
+            .ok()
+            .and_then(|text| ron::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, project_path: &Path) -> Result<(), String> {
+        let path = project_path.join(SETTINGS_FILE);
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())?;
+        fs::write(&path, text).map_err(|e| format!("Could not write {}: {e}", path.display()))
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Project {
     pub project_path: Option<PathBuf>,
     pub root_item: Option<FileExplorerItem>,
     pub loaded: bool,
+    #[serde(skip)]
+    pub settings: ProjectSettings,
 }
 
 impl Default for Project {
@@ -97,6 +144,7 @@ impl Default for Project {
             project_path: None,
             root_item: None,
             loaded: false,
+            settings: ProjectSettings::default(),
         }
         
     }
@@ -152,6 +200,18 @@ impl Project {
         item.path == project.root_item.as_ref().unwrap().path
    }
 
+   pub fn save_settings(&self) {
+        if let Some(path) = &self.project_path {
+            if let Err(e) = self.settings.save(path) {
+                log::warn!("{e}");
+            }
+        }
+   }
+
+   pub fn entry_path(&self) -> Option<PathBuf> {
+        self.project_path.as_ref().map(|path| path.join(ENTRY_FILE))
+   }
+
 }
 
 
@@ -165,12 +225,25 @@ impl Project {
 pub struct GameState {
     pub(crate) running: bool,
     pub(crate) size: [usize; 2],
+    pub(crate) record_frames: usize,
+    pub(crate) state_slot: usize,
+    /// Why the last Play failed, shown in place of the game.
+    pub(crate) launch_error: Option<String>,
 }
 
-pub fn launch_game(code_string: &str, game_state: &mut GameState) {
-    crate::engine::python::run_code_threaded(&code_string);
-    game_state.running = true;
+/// Starts the game from the script at `entry` on its own thread.
+pub fn launch_game(entry: &Path, game_state: &mut GameState) -> Result<(), String> {
+    let code = fs::read_to_string(entry).map_err(|e| format!("Could not read {}: {e}", entry.display()))?;
 
+    crate::engine::state_diff::clear();
+    crate::engine::rewind::clear();
+    crate::engine::frame_history::clear();
+    crate::engine::memory::clear();
+    crate::engine::profiler::clear();
+    crate::engine::python::run_code_threaded(&code, &entry.display().to_string());
+    game_state.running = true;
+    game_state.launch_error = None;
+    Ok(())
 }
 
 pub fn close_game(game_state: &mut GameState, egui_ctx: &Context) {
@@ -179,6 +252,12 @@ pub fn close_game(game_state: &mut GameState, egui_ctx: &Context) {
         let game = main.getattr("game").unwrap();
         game.call_method0("quit").unwrap();
     });
+    crate::engine::capture::stop_recording();
+    crate::engine::profiler::clear();
+    // A game stopped at a breakpoint has to run on to reach `quit` as well.
+    crate::engine::debugger::resume(crate::engine::debugger::Command::Stop);
+    // A paused game has to step again to reach `quit`.
+    crate::engine::python::set_paused(false);
     egui_ctx.forget_image("viewport_texture");
     game_state.running = false;
 }
\ No newline at end of file
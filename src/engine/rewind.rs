@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+
+use egui::Ui;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{python, save_state};
+
+
+/// Frame rate the rewind length in seconds is converted at.
+const ASSUMED_FPS: usize = 60;
+
+lazy_static::lazy_static! {
+    static ref BUFFER: std::sync::Mutex<RewindBuffer> = std::sync::Mutex::new(RewindBuffer::default());
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct RewindSettings {
+    /// Off by default, as recording pickles the whole game every few frames on its thread.
+    pub enabled: bool,
+    /// How far back the timeline reaches.
+    pub seconds: usize,
+    /// Only every n-th frame is kept, trading scrub precision for memory and speed.
+    pub every: usize,
+    /// Oldest states are dropped once the buffer holds more than this.
+    pub max_megabytes: usize,
+}
+
+impl Default for RewindSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seconds: 10,
+            every: 2,
+            max_megabytes: 64,
+        }
+    }
+}
+
+impl RewindSettings {
+    /// Number of states the ring buffer holds.
+    pub fn capacity(&self) -> usize {
+        (self.seconds * ASSUMED_FPS / self.every.max(1)).max(1)
+    }
+
+    fn max_bytes(&self) -> usize {
+        self.max_megabytes.max(1) * 1024 * 1024
+    }
+}
+
+struct Entry {
+    frame: usize,
+    state: Vec<u8>,
+}
+
+#[derive(Default)]
+struct RewindBuffer {
+    settings: RewindSettings,
+    entries: VecDeque<Entry>,
+    /// Total size of the states in `entries`.
+    bytes: usize,
+    /// Frame last restored. Newer entries are dropped once the game steps on from it.
+    cursor: Option<usize>,
+}
+
+impl RewindBuffer {
+    fn trim(&mut self) {
+        // The newest state is always kept, even when it alone is over the limit.
+        while self.entries.len() > 1 && (self.entries.len() > self.settings.capacity() || self.bytes > self.settings.max_bytes()) {
+            if let Some(entry) = self.entries.pop_front() {
+                self.bytes -= entry.state.len();
+            }
+        }
+    }
+
+    fn retain(&mut self, keep: impl Fn(&Entry) -> bool) {
+        self.entries.retain(keep);
+        self.bytes = self.entries.iter().map(|entry| entry.state.len()).sum();
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+        self.cursor = None;
+    }
+}
+
+
+/// Applies the project's rewind settings. Called by the viewport every frame.
+pub fn configure(settings: RewindSettings) {
+    let mut buffer = BUFFER.lock().unwrap();
+    if buffer.settings == settings {
+        return;
+    }
+
+    buffer.settings = settings;
+    if settings.enabled {
+        buffer.trim();
+    } else {
+        buffer.clear();
+    }
+}
+
+/// Stores the state of `game` after `frame`. Called by the runner for every stepped frame.
+pub fn record(py: Python<'_>, game: &Bound<'_, PyAny>, frame: usize) {
+    let settings = BUFFER.lock().unwrap().settings;
+    if !settings.enabled || frame % settings.every.max(1) != 0 {
+        return;
+    }
+
+    // Serialized outside the lock so the timeline stays responsive.
+    let state = match save_state::serialize_without_frames(py, game) {
+        Ok(state) => state,
+        Err(e) => {
+            e.print(py);
+            return;
+        }
+    };
+
+    let mut buffer = BUFFER.lock().unwrap();
+    // Playing on from a rewound frame replaces the future that was recorded before.
+    if let Some(cursor) = buffer.cursor.take() {
+        buffer.retain(|entry| entry.frame <= cursor);
+    }
+    buffer.retain(|entry| entry.frame < frame);
+    buffer.bytes += state.len();
+    buffer.entries.push_back(Entry { frame, state });
+    buffer.trim();
+}
+
+/// Restores the recorded state of `frame` into the running game between frames.
+fn restore(frame: usize) {
+    BUFFER.lock().unwrap().cursor = Some(frame);
+
+    python::queue_python_instruction(move |py| {
+        let state = {
+            let mut buffer = BUFFER.lock().unwrap();
+            // Set again in case a frame that was mid-step when the scrub started cleared it.
+            buffer.cursor = Some(frame);
+            buffer.entries.iter().find(|entry| entry.frame == frame).map(|entry| entry.state.clone())
+        };
+        let Some(state) = state else {
+            return;
+        };
+
+        let result = PyModule::import_bound(py, "__main__")
+            .and_then(|main| main.getattr("game"))
+            .and_then(|game| {
+                save_state::restore(py, &game, &state)?;
+                python::FRAME_COUNTER.store(frame, Ordering::Relaxed);
+                python::redraw_viewport(&game)
+            });
+        if let Err(e) = result {
+            e.print(py);
+        }
+    });
+}
+
+pub fn clear() {
+    BUFFER.lock().unwrap().clear();
+}
+
+
+/// The scrubber under the viewport. Dragging it pauses the game on the chosen frame.
+/// Returns `true` when `settings` changed and should be saved.
+pub fn timeline(ui: &mut Ui, settings: &mut RewindSettings) -> bool {
+    let (frames, cursor, bytes) = {
+        let buffer = BUFFER.lock().unwrap();
+        let frames: Vec<usize> = buffer.entries.iter().map(|entry| entry.frame).collect();
+        (frames, buffer.cursor, buffer.bytes)
+    };
+
+    let mut settings_changed = false;
+    ui.horizontal(|ui| {
+        ui.menu_button(egui_phosphor::regular::CLOCK_COUNTER_CLOCKWISE, |ui| {
+            settings_changed |= ui.checkbox(&mut settings.enabled, "Record rewind history").changed();
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                settings_changed |= ui.add(egui::DragValue::new(&mut settings.seconds).range(1..=120).prefix("Length: ").suffix(" s")).changed();
+                settings_changed |= ui.add(egui::DragValue::new(&mut settings.every).range(1..=30).prefix("Every ").suffix(" frames")).changed();
+                settings_changed |= ui.add(egui::DragValue::new(&mut settings.max_megabytes).range(1..=4096).prefix("Memory: ").suffix(" MB")).changed();
+                ui.label(format!("Keeps up to {} states", settings.capacity()));
+                ui.weak(format!("Using {:.1} MB", bytes as f64 / (1024.0 * 1024.0)));
+            });
+        }).response.on_hover_text("Rewind settings for this project");
+
+        let Some(latest) = frames.len().checked_sub(1) else {
+            ui.label(if settings.enabled { "Recording..." } else { "Rewind is off, turn it on in its settings" });
+            return;
+        };
+
+        let mut index = cursor
+            .and_then(|cursor| frames.iter().position(|&frame| frame == cursor))
+            .unwrap_or(latest);
+        let label = format!("Frame {} ({}/{})", frames[index], index + 1, frames.len());
+
+        ui.spacing_mut().slider_width = (ui.available_width() - 140.0).max(60.0);
+        if ui.add(egui::Slider::new(&mut index, 0..=latest).show_value(false)).changed() {
+            python::set_paused(true);
+            restore(frames[index]);
+        }
+        ui.label(label);
+    });
+
+    settings_changed
+}
@@ -0,0 +1,219 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::engine::python;
+
+
+/// Folder inside a project that save-state slots are written to.
+pub const STATES_DIR: &str = "states";
+pub const SLOT_COUNT: usize = 9;
+
+/// Pickles the attributes of `game` one by one so a single unpicklable value only skips that
+/// attribute. Surfaces are stored by pixels, while windows, clocks and the display are kept
+/// live: they are never written and references to them are re-pointed at the running game's
+/// objects on load. Games can list extra attributes to leave alone in `_redengine_skip_state`.
+/// Rewind states also leave out frame copies, `_frame_buffer` and display-sized Surfaces,
+/// which the next drawn frame replaces anyway.
+const STATE_MODULE: &str = r#"
+import io
+import pickle
+import pygame
+
+LIVE_TYPES = tuple(t for t in (
+    getattr(pygame, "Window", None),
+    getattr(pygame.time, "Clock", None),
+) if t is not None)
+
+
+def _surface_from_bytes(data, size):
+    return pygame.image.frombytes(data, size, "RGBA")
+
+
+def _live_objects(game):
+    display = pygame.display.get_surface()
+    live = {}
+    for name, value in vars(game).items():
+        if isinstance(value, LIVE_TYPES) or (display is not None and value is display):
+            live[id(value)] = name
+    if display is not None:
+        live.setdefault(id(display), None)
+    return live
+
+
+class _Pickler(pickle.Pickler):
+    def __init__(self, file, live):
+        super().__init__(file, protocol=pickle.HIGHEST_PROTOCOL)
+        self.live = live
+
+    def persistent_id(self, obj):
+        if id(obj) in self.live:
+            return ("live", self.live[id(obj)])
+        return None
+
+    def reducer_override(self, obj):
+        if isinstance(obj, pygame.Surface):
+            return _surface_from_bytes, (pygame.image.tobytes(obj, "RGBA"), obj.get_size())
+        return NotImplemented
+
+
+class _Unpickler(pickle.Unpickler):
+    def __init__(self, file, game):
+        super().__init__(file)
+        self.game = game
+
+    def persistent_load(self, pid):
+        _, name = pid
+        if name is None:
+            return pygame.display.get_surface()
+        return getattr(self.game, name)
+
+
+FRAME_ATTRIBUTES = ("_frame_buffer",)
+
+
+def _is_frame(name, value):
+    display = pygame.display.get_surface()
+    if name in FRAME_ATTRIBUTES:
+        return True
+    return display is not None and isinstance(value, pygame.Surface) and value.get_size() == display.get_size()
+
+
+def dumps(game, skip_frames=False):
+    live = _live_objects(game)
+    skip = set(getattr(game, "_redengine_skip_state", ()))
+    state = {}
+    skipped = []
+    for name, value in vars(game).items():
+        if name in skip or id(value) in live or (skip_frames and _is_frame(name, value)):
+            skipped.append(name)
+            continue
+        buffer = io.BytesIO()
+        try:
+            _Pickler(buffer, live).dump(value)
+        except Exception:
+            skipped.append(name)
+            continue
+        state[name] = buffer.getvalue()
+    return pickle.dumps(state, protocol=pickle.HIGHEST_PROTOCOL), skipped
+
+
+def loads(game, data):
+    for name, blob in pickle.loads(data).items():
+        setattr(game, name, _Unpickler(io.BytesIO(blob), game).load())
+"#;
+
+lazy_static::lazy_static! {
+    /// Outcome of the last save or load, shown in the viewport toolbar.
+    pub static ref STATUS: std::sync::Mutex<String> = std::sync::Mutex::new(String::new());
+    /// Which slots of a project hold a state, so the toolbar does not check the disk every frame.
+    static ref USED_SLOTS: std::sync::Mutex<Option<(Option<PathBuf>, Vec<bool>)>> = std::sync::Mutex::new(None);
+}
+
+
+fn state_module(py: Python<'_>) -> PyResult<Bound<'_, PyModule>> {
+    // Cached in `sys.modules`, so pickle can find `_surface_from_bytes` by name. Also registered
+    // as `redengine.save_state`, the name slots written by earlier builds refer to it by.
+    python::helper_module_with(py, "save_state", STATE_MODULE, |module| python::register_submodule(py, "save_state", module))
+}
+
+/// Serializes the picklable parts of `game`, returning the data and the attributes that were skipped.
+pub fn serialize(py: Python<'_>, game: &Bound<'_, PyAny>) -> PyResult<(Vec<u8>, Vec<String>)> {
+    let (data, skipped): (Bound<'_, PyBytes>, Vec<String>) = state_module(py)?.getattr("dumps")?.call1((game,))?.extract()?;
+    Ok((data.as_bytes().to_vec(), skipped))
+}
+
+/// Like `serialize`, but leaves out copies of the frame so states stay small enough to keep
+/// hundreds of them for rewinding.
+pub fn serialize_without_frames(py: Python<'_>, game: &Bound<'_, PyAny>) -> PyResult<Vec<u8>> {
+    let (data, _): (Bound<'_, PyBytes>, Vec<String>) = state_module(py)?.getattr("dumps")?.call1((game, true))?.extract()?;
+    Ok(data.as_bytes().to_vec())
+}
+
+/// Restores attributes saved by `serialize` onto the live `game`.
+pub fn restore(py: Python<'_>, game: &Bound<'_, PyAny>, data: &[u8]) -> PyResult<()> {
+    state_module(py)?.getattr("loads")?.call1((game, PyBytes::new_bound(py, data)))?;
+    Ok(())
+}
+
+fn main_game(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    PyModule::import_bound(py, "__main__")?.getattr("game")
+}
+
+
+pub fn slot_path(project: Option<&Path>, slot: usize) -> PathBuf {
+    let dir = project.map(|p| p.join(STATES_DIR)).unwrap_or_else(|| PathBuf::from(STATES_DIR));
+    dir.join(format!("slot_{slot}.state"))
+}
+
+/// Whether each slot of `project`, from slot 1, holds a state. Read from disk when the project
+/// changes or after a save or load.
+pub fn used_slots(project: Option<&Path>) -> Vec<bool> {
+    let mut cache = USED_SLOTS.lock().unwrap();
+    match &*cache {
+        Some((cached, used)) if cached.as_deref() == project => used.clone(),
+        _ => {
+            let used: Vec<bool> = (1..=SLOT_COUNT).map(|slot| slot_path(project, slot).exists()).collect();
+            *cache = Some((project.map(Path::to_owned), used.clone()));
+            used
+        }
+    }
+}
+
+fn refresh_used_slots() {
+    *USED_SLOTS.lock().unwrap() = None;
+}
+
+fn set_status(message: String) {
+    *STATUS.lock().unwrap() = message;
+}
+
+/// Saves the running game into `path` between frames.
+pub fn save_slot(path: PathBuf) {
+    python::queue_python_instruction(move |py| {
+        let result = main_game(py).and_then(|game| serialize(py, &game));
+        let (data, skipped) = match result {
+            Ok(state) => state,
+            Err(e) => {
+                set_status(format!("Save failed: {}", python::format_exception(py, &e)));
+                return;
+            }
+        };
+
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&path, &data));
+        refresh_used_slots();
+        match written {
+            Ok(()) if skipped.is_empty() => set_status(format!("Saved {}", path.display())),
+            Ok(()) => set_status(format!("Saved {} (not saved: {})", path.display(), skipped.join(", "))),
+            Err(e) => set_status(format!("Could not write {}: {e}", path.display())),
+        }
+    });
+}
+
+/// Restores the state in `path` into the running game between frames.
+pub fn load_slot(path: PathBuf) {
+    python::queue_python_instruction(move |py| {
+        refresh_used_slots();
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) => {
+                set_status(format!("Could not read {}: {e}", path.display()));
+                return;
+            }
+        };
+
+        // Redrawn so a paused game shows the loaded state straight away.
+        let loaded = main_game(py).and_then(|game| {
+            restore(py, &game, &data)?;
+            python::redraw_viewport(&game)
+        });
+        match loaded {
+            Ok(()) => set_status(format!("Loaded {}", path.display())),
+            Err(e) => set_status(format!("Load failed: {}", python::format_exception(py, &e))),
+        }
+    });
+}
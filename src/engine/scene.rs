@@ -0,0 +1,239 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use egui::{pos2, vec2, Color32, Painter, Pos2, Rect, Stroke, Ui};
+use pyo3::prelude::*;
+
+use crate::engine::{helpers::lock, python, viewport::ViewportTransform};
+
+
+/// Seconds between walks of the live `game` object.
+const REFRESH_INTERVAL: f64 = 0.25;
+
+/// Collects sprite groups, sprites and rects reachable from `game` as nested tuples of
+/// `(path, label, kind, rect, children)`.
+const SCENE_WALKER: &str = r#"
+import types
+import pygame
+
+def rect_of(obj):
+    rect = getattr(obj, "rect", None)
+    return tuple(rect) if isinstance(rect, pygame.Rect) else None
+
+def walk(obj, path, depth):
+    nodes = []
+    for name, value in list(vars(obj).items()):
+        child = f"{path}.{name}"
+        if isinstance(value, pygame.sprite.AbstractGroup):
+            sprites = [
+                (f"{child}[{i}]", type(sprite).__name__, "sprite", rect_of(sprite), [])
+                for i, sprite in enumerate(value.sprites())
+            ]
+            nodes.append((child, name, "group", None, sprites))
+        elif isinstance(value, pygame.sprite.Sprite):
+            nodes.append((child, name, "sprite", rect_of(value), []))
+        elif isinstance(value, pygame.Rect):
+            nodes.append((child, name, "rect", tuple(value), []))
+        elif depth > 0 and hasattr(value, "__dict__") and not isinstance(value, (type, types.ModuleType)):
+            children = walk(value, child, depth - 1)
+            if children:
+                nodes.append((child, name, "object", None, children))
+    return nodes
+"#;
+
+static REFRESH_PENDING: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref SCENE: std::sync::Mutex<Vec<SceneNode>> = std::sync::Mutex::new(Vec::new());
+    /// Why the last walk of the game failed, shown in the Sprites tab instead of printed on every refresh.
+    static ref WALK_ERROR: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SceneNodeKind {
+    Group,
+    Sprite,
+    Rect,
+    Object,
+}
+
+#[derive(Clone)]
+pub struct SceneNode {
+    /// Attribute path from `game`, e.g. `game.enemies[2]`. Used as the node's identity.
+    pub path: String,
+    pub label: String,
+    pub kind: SceneNodeKind,
+    /// Bounds in game coordinates.
+    pub rect: Option<Rect>,
+    pub children: Vec<SceneNode>,
+}
+
+impl SceneNode {
+    fn extract(value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let (path, label, kind, rect, children): (String, String, String, Option<(f32, f32, f32, f32)>, Vec<Bound<'_, PyAny>>) =
+            value.extract()?;
+
+        Ok(Self {
+            path,
+            label,
+            kind: match kind.as_str() {
+                "group" => SceneNodeKind::Group,
+                "sprite" => SceneNodeKind::Sprite,
+                "rect" => SceneNodeKind::Rect,
+                _ => SceneNodeKind::Object,
+            },
+            rect: rect.map(|(x, y, w, h)| Rect::from_min_size(pos2(x, y), vec2(w, h))),
+            children: children.iter().map(Self::extract).collect::<PyResult<_>>()?,
+        })
+    }
+
+    fn find<'a>(nodes: &'a [Self], path: &str) -> Option<&'a Self> {
+        nodes.iter().find_map(|node| {
+            if node.path == path {
+                Some(node)
+            } else {
+                Self::find(&node.children, path)
+            }
+        })
+    }
+
+    /// The last sprite (or failing that, rect) in draw order whose bounds contain `pos`.
+    fn hit(nodes: &[Self], pos: Pos2) -> Option<&Self> {
+        fn collect<'a>(nodes: &'a [SceneNode], pos: Pos2, hits: &mut Vec<&'a SceneNode>) {
+            for node in nodes {
+                if node.rect.is_some_and(|rect| rect.contains(pos)) {
+                    hits.push(node);
+                }
+                collect(&node.children, pos, hits);
+            }
+        }
+
+        let mut hits = vec![];
+        collect(nodes, pos, &mut hits);
+        hits.iter()
+            .rev()
+            .find(|node| node.kind == SceneNodeKind::Sprite)
+            .or_else(|| hits.last())
+            .copied()
+    }
+}
+
+
+fn walk_game(py: Python<'_>) -> PyResult<Vec<SceneNode>> {
+    let game = PyModule::import_bound(py, "__main__")?.getattr("game")?;
+    let walk = python::helper_module(py, "scene", SCENE_WALKER)?.getattr("walk")?;
+    let nodes = walk.call1((game, "game", 2))?;
+    nodes.iter()?.map(|node| SceneNode::extract(&node?)).collect()
+}
+
+/// Queues a walk of the live `game` object unless one is already waiting to run.
+fn request_refresh() {
+    if REFRESH_PENDING.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    python::queue_python_instruction(|py| {
+        match walk_game(py) {
+            Ok(nodes) => {
+                *lock(&SCENE) = nodes;
+                *lock(&WALK_ERROR) = None;
+            }
+            Err(e) => *lock(&WALK_ERROR) = Some(python::format_exception(py, &e)),
+        }
+        REFRESH_PENDING.store(false, Ordering::Relaxed);
+    });
+}
+
+
+/// Lists sprite groups and rects on the running game and tracks which one is selected.
+#[derive(Default)]
+pub struct SpriteBrowser {
+    pub selected: Option<String>,
+    last_refresh: f64,
+}
+
+impl SpriteBrowser {
+    /// Refreshes the hierarchy from the game every `REFRESH_INTERVAL` seconds while it runs.
+    pub fn update(&mut self, time: f64, running: bool) {
+        if !running {
+            lock(&SCENE).clear();
+            *lock(&WALK_ERROR) = None;
+            return;
+        }
+        if time - self.last_refresh >= REFRESH_INTERVAL {
+            self.last_refresh = time;
+            request_refresh();
+        }
+    }
+
+    /// Selects whatever lies under `screen` in the viewport.
+    pub fn select_at(&mut self, transform: &ViewportTransform, screen: Pos2) {
+        let pos = transform.to_game(screen);
+        self.selected = SceneNode::hit(&lock(&SCENE), pos).map(|node| node.path.clone());
+    }
+
+    /// Outlines the selected node's rect over the viewport.
+    pub fn paint_selection(&self, painter: &Painter, transform: &ViewportTransform) {
+        let Some(path) = &self.selected else {
+            return;
+        };
+        let scene = lock(&SCENE);
+        let Some(rect) = SceneNode::find(&scene, path).and_then(|node| node.rect) else {
+            return;
+        };
+
+        let rect = Rect::from_min_max(transform.to_screen(rect.min), transform.to_screen(rect.max));
+        painter.rect_stroke(rect, 0.0, Stroke::new(2.0, Color32::YELLOW), egui::StrokeKind::Outside);
+        painter.text(rect.left_top(), egui::Align2::LEFT_BOTTOM, path, egui::FontId::monospace(12.0), Color32::YELLOW);
+    }
+
+    fn node_ui(&mut self, ui: &mut Ui, node: &SceneNode) {
+        let icon = match node.kind {
+            SceneNodeKind::Group => egui_phosphor::regular::STACK,
+            SceneNodeKind::Sprite => egui_phosphor::regular::IMAGE,
+            SceneNodeKind::Rect => egui_phosphor::regular::RECTANGLE,
+            SceneNodeKind::Object => egui_phosphor::regular::CUBE,
+        };
+        let label = match node.rect {
+            Some(rect) => format!("{icon} {} ({}, {}, {}, {})", node.label, rect.min.x, rect.min.y, rect.width(), rect.height()),
+            None => format!("{icon} {}", node.label),
+        };
+
+        if node.children.is_empty() {
+            let selected = self.selected.as_deref() == Some(node.path.as_str());
+            if ui.selectable_label(selected, label).on_hover_text(&node.path).clicked() {
+                self.selected = Some(node.path.clone());
+            }
+        } else {
+            egui::CollapsingHeader::new(label)
+                .id_salt(&node.path)
+                .default_open(true)
+                .show(ui, |ui| {
+                    for child in &node.children {
+                        self.node_ui(ui, child);
+                    }
+                });
+        }
+    }
+
+    /// The Sprites side tab.
+    pub fn ui(&mut self, ui: &mut Ui) {
+        if let Some(error) = lock(&WALK_ERROR).as_ref() {
+            ui.colored_label(ui.visuals().error_fg_color, "Could not list the game's sprites:");
+            ui.label(egui::RichText::new(error).monospace().small());
+            ui.separator();
+        }
+
+        let scene = lock(&SCENE).clone();
+        if scene.is_empty() {
+            ui.label("Run the game to browse its sprites and rects.");
+            return;
+        }
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for node in &scene {
+                self.node_ui(ui, node);
+            }
+        });
+    }
+}
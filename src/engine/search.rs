@@ -0,0 +1,371 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use egui::Ui;
+use regex::Regex;
+
+use crate::engine::editor::{Editor, SourceLocation};
+use crate::engine::find::{self, FindOptions};
+
+
+/// Folders never searched: tool caches, virtual environments and version control.
+const SKIPPED_DIRS: [&str; 4] = ["__pycache__", "venv", "node_modules", "target"];
+/// Files bigger than this are assumed not to be source and skipped.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+pub const MAX_MATCHES: usize = 1000;
+/// Matched lines longer than this are cut short in the File Explorer.
+const MAX_LINE_LEN: usize = 160;
+
+
+/// Whether every character of `pattern` appears in `text` in order, ignoring case and spaces.
+pub fn fuzzy_match(pattern: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .all(|p| text.any(|c| c == p))
+}
+
+
+/// Converts a `.gitignore` glob into a regex over `/` separated relative paths.
+fn glob_to_regex(glob: &str) -> String {
+    let mut pattern = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '[' => {
+                let rest: String = chars.clone().collect();
+                let (negated, body) = rest.strip_prefix('!').map_or((false, rest.as_str()), |body| (true, body));
+                // A `]` right at the start belongs to the class rather than closing it.
+                let Some(end) = body.char_indices().skip(1).find(|&(_, c)| c == ']').map(|(index, _)| index) else {
+                    pattern.push_str(r"\[");
+                    continue;
+                };
+                let class = body[..end].replace('[', r"\[").replace(']', r"\]");
+                // Like other wildcards, a negated class never matches a `/`.
+                pattern.push_str(&if negated { format!("[^/{class}]") } else { format!("[{class}]") });
+                for _ in 0..usize::from(negated) + body[..=end].chars().count() {
+                    chars.next();
+                }
+            }
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern
+}
+
+struct IgnoreRule {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+/// Patterns from the `.gitignore` at the root of a project.
+#[derive(Default)]
+pub struct IgnoreRules {
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreRules {
+    pub fn load(root: &Path) -> Self {
+        fs::read_to_string(root.join(".gitignore")).map(|text| Self::parse(&text)).unwrap_or_default()
+    }
+
+    /// Parses the lines of a `.gitignore` file.
+    fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let (negated, line) = line.strip_prefix('!').map_or((false, line), |line| (true, line));
+                let (dir_only, line) = line.strip_suffix('/').map_or((false, line), |line| (true, line));
+                // Patterns with a slash are relative to the root, others match at any depth.
+                let anchored = line.contains('/');
+                let glob = glob_to_regex(line.trim_start_matches('/'));
+                let pattern = if anchored { format!("^{glob}$") } else { format!("(?:^|/){glob}$") };
+                Regex::new(&pattern).ok().map(|regex| IgnoreRule { regex, negated, dir_only })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Whether `relative`, a path under the root, is ignored. The last matching rule wins.
+    pub fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(&relative))
+            .is_some_and(|rule| !rule.negated)
+    }
+}
+
+/// Files under `root` worth searching, skipping hidden folders, caches and ignored paths.
+pub fn text_files(root: &Path) -> Vec<PathBuf> {
+    fn walk(root: &Path, dir: &Path, rules: &IgnoreRules, files: &mut Vec<PathBuf>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let is_dir = path.is_dir();
+            if name.starts_with('.') || rules.is_ignored(path.strip_prefix(root).unwrap_or(&path), is_dir) {
+                continue;
+            }
+
+            if is_dir {
+                if !SKIPPED_DIRS.contains(&name.as_str()) {
+                    walk(root, &path, rules, files);
+                }
+            } else if entry.metadata().is_ok_and(|metadata| metadata.len() <= MAX_FILE_SIZE) {
+                files.push(path);
+            }
+        }
+    }
+
+    let mut files = vec![];
+    walk(root, root, &IgnoreRules::load(root), &mut files);
+    files.sort();
+    files
+}
+
+
+#[derive(Clone)]
+pub struct SearchMatch {
+    pub path: PathBuf,
+    /// 1-based.
+    pub line: usize,
+    pub text: String,
+    /// Byte ranges of the matches within `text`.
+    pub ranges: Vec<Range<usize>>,
+}
+
+#[derive(Clone, Default)]
+pub struct ContentResults {
+    pub matches: Vec<SearchMatch>,
+    pub running: bool,
+    /// Set when the search stopped at `MAX_MATCHES`.
+    pub truncated: bool,
+}
+
+impl ContentResults {
+    /// Matches grouped by file, in path order.
+    pub fn by_file(&self) -> impl Iterator<Item = &[SearchMatch]> {
+        self.matches.chunk_by(|a, b| a.path == b.path)
+    }
+}
+
+fn trimmed_line(line: &str) -> String {
+    let line = line.trim();
+    match line.char_indices().nth(MAX_LINE_LEN) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_owned(),
+    }
+}
+
+/// Greps the text files of a project line by line on a background thread.
+/// Starting a new search stops the one still running.
+#[derive(Default)]
+pub struct ContentSearch {
+    results: Arc<Mutex<ContentResults>>,
+    generation: Arc<AtomicUsize>,
+}
+
+impl ContentSearch {
+    pub fn results(&self) -> ContentResults {
+        self.results.lock().unwrap().clone()
+    }
+
+    /// Drops the results and stops any running search.
+    pub fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        *self.results.lock().unwrap() = ContentResults::default();
+    }
+
+    pub fn start(&self, root: PathBuf, regex: Regex) {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        *self.results.lock().unwrap() = ContentResults { running: true, ..Default::default() };
+
+        let results = self.results.clone();
+        let current = self.generation.clone();
+        std::thread::spawn(move || {
+            for path in text_files(&root) {
+                if current.load(Ordering::Relaxed) != generation {
+                    return;
+                }
+                // Binary files fail to read as UTF-8 and are skipped here.
+                let Ok(text) = fs::read_to_string(&path) else {
+                    continue;
+                };
+
+                let found: Vec<SearchMatch> = text
+                    .lines()
+                    .enumerate()
+                    .filter_map(|(index, line)| {
+                        let ranges = find::find_matches(line, &regex);
+                        (!ranges.is_empty()).then(|| SearchMatch { path: path.clone(), line: index + 1, text: line.to_owned(), ranges })
+                    })
+                    .collect();
+                if found.is_empty() {
+                    continue;
+                }
+
+                let mut results = results.lock().unwrap();
+                if current.load(Ordering::Relaxed) != generation {
+                    return;
+                }
+                results.matches.extend(found);
+                if results.matches.len() >= MAX_MATCHES {
+                    results.matches.truncate(MAX_MATCHES);
+                    results.truncated = true;
+                    results.running = false;
+                    return;
+                }
+            }
+
+            if current.load(Ordering::Relaxed) == generation {
+                results.lock().unwrap().running = false;
+            }
+        });
+    }
+}
+
+
+/// State of the File Explorer search box.
+#[derive(Default)]
+pub struct FileSearch {
+    /// Search inside files instead of filtering the tree by name.
+    pub in_contents: bool,
+    /// Query the current content results were started for.
+    searched: Option<String>,
+    search: ContentSearch,
+}
+
+impl FileSearch {
+    /// Shows content matches for `term` under `root`, starting a new search when it changed.
+    /// Clicking a line opens it in `editor`.
+    pub fn contents_ui(&mut self, ui: &mut Ui, root: &Path, term: &str, editor: &mut Editor) {
+        if self.searched.as_deref() != Some(term) {
+            self.searched = Some(term.to_owned());
+            match find::build_regex(term, FindOptions::default()) {
+                Some(Ok(regex)) => self.search.start(root.to_owned(), regex),
+                _ => self.search.clear(),
+            }
+        }
+
+        let results = self.search.results();
+        if term.is_empty() {
+            ui.label("Type to search inside the project's files.");
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            let files = results.matches.iter().map(|m| &m.path).collect::<std::collections::BTreeSet<_>>().len();
+            ui.label(format!("{} matches in {files} files", results.matches.len()));
+            if results.running {
+                ui.spinner();
+                ui.ctx().request_repaint();
+            } else if results.truncated {
+                ui.weak(format!("(first {MAX_MATCHES})"));
+            }
+        });
+
+        egui::ScrollArea::vertical().id_salt("content_search_results").show(ui, |ui| {
+            for group in results.by_file() {
+                let path = &group[0].path;
+                let name = path.strip_prefix(root).unwrap_or(path).display().to_string();
+                egui::CollapsingHeader::new(format!("{} {name} ({})", egui_phosphor::regular::FILE, group.len()))
+                    .id_salt(path)
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for found in group {
+                            let label = egui::RichText::new(format!("{}: {}", found.line, trimmed_line(&found.text))).monospace();
+                            if ui.selectable_label(false, label).clicked() {
+                                editor.goto(&SourceLocation { file: found.path.display().to_string(), line: found.line });
+                            }
+                        }
+                    });
+            }
+        });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::IgnoreRules;
+
+    fn ignored(gitignore: &str, path: &str, is_dir: bool) -> bool {
+        IgnoreRules::parse(gitignore).is_ignored(Path::new(path), is_dir)
+    }
+
+    #[test]
+    fn unanchored_patterns_match_at_any_depth() {
+        assert!(ignored("*.log", "debug.log", false));
+        assert!(ignored("*.log", "logs/today/debug.log", false));
+        assert!(!ignored("*.log", "debug.log.txt", false));
+        assert!(!ignored("*.log", "logs/debug.txt", false));
+    }
+
+    #[test]
+    fn anchored_patterns_match_from_the_root() {
+        assert!(ignored("/build", "build", true));
+        assert!(!ignored("/build", "src/build", true));
+        assert!(ignored("docs/*.md", "docs/readme.md", false));
+        assert!(!ignored("docs/*.md", "docs/api/readme.md", false));
+        assert!(ignored("docs/**/*.md", "docs/api/readme.md", false));
+    }
+
+    #[test]
+    fn dir_only_patterns_skip_files() {
+        assert!(ignored("cache/", "cache", true));
+        assert!(ignored("cache/", "assets/cache", true));
+        assert!(!ignored("cache/", "cache", false));
+    }
+
+    #[test]
+    fn later_negations_win() {
+        let gitignore = "*.png\n!keep.png\n";
+        assert!(ignored(gitignore, "sprites/player.png", false));
+        assert!(!ignored(gitignore, "sprites/keep.png", false));
+        assert!(ignored("!keep.png\n*.png\n", "keep.png", false));
+    }
+
+    #[test]
+    fn character_classes() {
+        assert!(ignored("frame_[0-9].png", "frame_7.png", false));
+        assert!(!ignored("frame_[0-9].png", "frame_-.png", false));
+        assert!(!ignored("frame_[0-9].png", "frame_a.png", false));
+        assert!(ignored("[!a]*.tmp", "b.tmp", false));
+        assert!(!ignored("[!a]*.tmp", "a.tmp", false));
+        assert!(ignored("[]x].txt", "].txt", false));
+        assert!(!ignored("a[!x]b", "a/b", false));
+        // An unclosed bracket is literal.
+        assert!(ignored("[abc", "[abc", false));
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_not_rules() {
+        assert!(!ignored("# *.py\n\n", "main.py", false));
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use egui::{Color32, RichText, Ui};
+use pyo3::prelude::*;
+
+use crate::engine::python;
+
+
+/// Number of stepped frames kept for comparison.
+const HISTORY_LEN: usize = 60;
+
+/// Returns `repr`s of `game`'s attributes, plus one level into attributes that are plain
+/// objects so values like `testObject.changing_attr` show up.
+const SNAPSHOT_WALKER: &str = r#"
+import reprlib
+import types
+
+_repr = reprlib.Repr()
+_repr.maxstring = 120
+_repr.maxother = 120
+
+def snapshot(game):
+    values = {}
+    for name, value in list(vars(game).items()):
+        values[name] = value
+        if hasattr(value, "__dict__") and not isinstance(value, (type, types.ModuleType)):
+            for child, child_value in list(vars(value).items()):
+                values[f"{name}.{child}"] = child_value
+    return {name: _repr.repr(value) for name, value in values.items()}
+"#;
+
+lazy_static::lazy_static! {
+    static ref HISTORY: std::sync::Mutex<VecDeque<Snapshot>> = std::sync::Mutex::new(VecDeque::new());
+}
+
+
+#[derive(Clone)]
+pub struct Snapshot {
+    pub frame: usize,
+    pub values: BTreeMap<String, String>,
+}
+
+pub enum Change<'a> {
+    Added(&'a str),
+    Removed(&'a str),
+    Changed { old: &'a str, new: &'a str },
+    Unchanged(&'a str),
+}
+
+/// Compares two snapshots attribute by attribute, in name order.
+pub fn diff<'a>(old: &'a Snapshot, new: &'a Snapshot) -> Vec<(&'a str, Change<'a>)> {
+    let mut names: Vec<&str> = old.values.keys().chain(new.values.keys()).map(String::as_str).collect();
+    names.sort_unstable();
+    names.dedup();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let change = match (old.values.get(name), new.values.get(name)) {
+                (None, Some(new)) => Change::Added(new),
+                (Some(old), None) => Change::Removed(old),
+                (Some(old), Some(new)) if old != new => Change::Changed { old, new },
+                (Some(old), _) => Change::Unchanged(old),
+                (None, None) => unreachable!("name came from one of the snapshots"),
+            };
+            (name, change)
+        })
+        .collect()
+}
+
+fn take_snapshot(py: Python<'_>, game: &Bound<'_, PyAny>) -> PyResult<BTreeMap<String, String>> {
+    let snapshot = python::helper_module(py, "state_diff", SNAPSHOT_WALKER)?.getattr("snapshot")?;
+    snapshot.call1((game,))?.extract()
+}
+
+/// Records the game's state for `frame`. Called by the runner for every stepped frame.
+pub fn capture(py: Python<'_>, game: &Bound<'_, PyAny>, frame: usize) {
+    match take_snapshot(py, game) {
+        Ok(values) => {
+            let mut history = HISTORY.lock().unwrap();
+            history.retain(|snapshot| snapshot.frame != frame);
+            history.push_back(Snapshot { frame, values });
+            while history.len() > HISTORY_LEN {
+                history.pop_front();
+            }
+        }
+        Err(e) => e.print(py),
+    }
+}
+
+/// Records the current frame as the starting point for diffs. Used when the game is paused.
+pub fn capture_current() {
+    python::queue_python_instruction(|py| {
+        let game = PyModule::import_bound(py, "__main__").and_then(|main| main.getattr("game"));
+        match game {
+            Ok(game) => capture(py, &game, python::FRAME_COUNTER.load(std::sync::atomic::Ordering::Relaxed)),
+            Err(e) => e.print(py),
+        }
+    });
+}
+
+pub fn clear() {
+    HISTORY.lock().unwrap().clear();
+}
+
+
+/// Shows what changed on the `game` object between consecutive stepped frames.
+#[derive(Default)]
+pub struct StateDiffView {
+    /// Index into the history of the newer snapshot being compared, `None` follows the latest.
+    pub selected: Option<usize>,
+    pub show_unchanged: bool,
+}
+
+impl StateDiffView {
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let history = HISTORY.lock().unwrap().clone();
+        if history.len() < 2 {
+            ui.label("Pause the game and step it to compare frames.");
+            return;
+        }
+
+        let latest = history.len() - 1;
+        let mut index = self.selected.unwrap_or(latest).clamp(1, latest);
+
+        ui.horizontal(|ui| {
+            let (old, new) = (&history[index - 1], &history[index]);
+            ui.label(format!("Frame {} → {}", old.frame, new.frame));
+            if ui.add(egui::Slider::new(&mut index, 1..=latest).show_value(false)).changed() {
+                self.selected = Some(index);
+            }
+            if ui.selectable_label(self.selected.is_none(), "Latest").clicked() {
+                self.selected = None;
+            }
+            ui.checkbox(&mut self.show_unchanged, "Show unchanged");
+            if ui.button("Clear").clicked() {
+                clear();
+                self.selected = None;
+            }
+        });
+        ui.add(egui::Separator::default().grow(5.0));
+
+        let (old, new) = (&history[index - 1], &history[index]);
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("state_diff_grid").striped(true).num_columns(3).show(ui, |ui| {
+                for (name, change) in diff(old, new) {
+                    match change {
+                        Change::Added(value) => {
+                            ui.label(RichText::new(format!("+ {name}")).color(Color32::from_rgb(90, 200, 90)));
+                            ui.label("");
+                            ui.monospace(value);
+                        }
+                        Change::Removed(value) => {
+                            ui.label(RichText::new(format!("- {name}")).color(Color32::from_rgb(230, 80, 80)));
+                            ui.monospace(value);
+                            ui.label("");
+                        }
+                        Change::Changed { old, new } => {
+                            ui.label(RichText::new(format!("~ {name}")).color(Color32::from_rgb(230, 190, 60)));
+                            ui.monospace(old);
+                            ui.label(RichText::new(new).monospace().background_color(Color32::from_rgb(90, 70, 20)));
+                        }
+                        Change::Unchanged(value) => {
+                            if !self.show_unchanged {
+                                continue;
+                            }
+                            ui.label(format!("  {name}"));
+                            ui.monospace(value);
+                            ui.monospace(value);
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Change, Snapshot};
+
+    fn snapshot(frame: usize, values: &[(&str, &str)]) -> Snapshot {
+        Snapshot { frame, values: values.iter().map(|&(name, value)| (name.to_owned(), value.to_owned())).collect() }
+    }
+
+    #[test]
+    fn classifies_attributes_in_name_order() {
+        let old = snapshot(1, &[("score", "10"), ("player.x", "4"), ("paused", "False"), ("boss", "<Boss>")]);
+        let new = snapshot(2, &[("score", "12"), ("player.x", "4"), ("paused", "False"), ("lives", "3")]);
+
+        let changes: Vec<String> = diff(&old, &new)
+            .into_iter()
+            .map(|(name, change)| match change {
+                Change::Added(new) => format!("+ {name} {new}"),
+                Change::Removed(old) => format!("- {name} {old}"),
+                Change::Changed { old, new } => format!("~ {name} {old} -> {new}"),
+                Change::Unchanged(value) => format!("= {name} {value}"),
+            })
+            .collect();
+
+        assert_eq!(changes, ["- boss <Boss>", "+ lives 3", "= paused False", "= player.x 4", "~ score 10 -> 12"]);
+    }
+}
@@ -0,0 +1,518 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use egui::{Id, Key, Modifiers, Ui};
+use pyo3::prelude::*;
+
+use crate::engine::debounce::DebouncedParser;
+use crate::engine::editor::SourceLocation;
+use crate::engine::{helpers::lock, python, search};
+
+
+/// Most symbols the project picker lists at once.
+const MAX_PICKER_RESULTS: usize = 50;
+
+/// Lists the classes, methods, functions and constants of a module, or `None` if it does not parse.
+const SYMBOL_PROBE: &str = r#"
+import ast
+
+def symbols(source):
+    try:
+        tree = ast.parse(source)
+    except (SyntaxError, ValueError):
+        return None
+    found = []
+    for node in tree.body:
+        if isinstance(node, ast.ClassDef):
+            bases = ", ".join(ast.unparse(base) for base in node.bases)
+            found.append(("class", node.name, node.lineno, None, f"({bases})" if bases else ""))
+            for item in node.body:
+                if isinstance(item, (ast.FunctionDef, ast.AsyncFunctionDef)):
+                    found.append(("method", item.name, item.lineno, node.name, f"({ast.unparse(item.args)})"))
+        elif isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef)):
+            found.append(("function", node.name, node.lineno, None, f"({ast.unparse(node.args)})"))
+        elif isinstance(node, (ast.Assign, ast.AnnAssign)):
+            targets = node.targets if isinstance(node, ast.Assign) else [node.target]
+            for target in targets:
+                for name in ast.walk(target):
+                    if isinstance(name, ast.Name) and name.id.isupper():
+                        found.append(("constant", name.id, node.lineno, None, ""))
+    return found
+"#;
+
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Class,
+    Method,
+    Function,
+    Constant,
+}
+
+impl SymbolKind {
+    fn from_probe(kind: &str) -> Option<Self> {
+        match kind {
+            "class" => Some(Self::Class),
+            "method" => Some(Self::Method),
+            "function" => Some(Self::Function),
+            "constant" => Some(Self::Constant),
+            _ => None,
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Class => egui_phosphor::regular::CUBE,
+            Self::Method | Self::Function => egui_phosphor::regular::FUNCTION,
+            Self::Constant => egui_phosphor::regular::HASH,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    pub name: String,
+    /// 1-based.
+    pub line: usize,
+    /// Class a method belongs to.
+    pub container: Option<String>,
+    /// Parameters of a function or method, bases of a class.
+    pub detail: String,
+}
+
+impl Symbol {
+    /// Name qualified by its class, like `Player.update`.
+    pub fn qualified_name(&self) -> String {
+        match &self.container {
+            Some(container) => format!("{container}.{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+type ProbeResult = Option<Vec<(String, String, usize, Option<String>, String)>>;
+
+/// Loads `SYMBOL_PROBE` and returns its `symbols` function.
+fn symbol_probe(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    python::helper_module(py, "symbols", SYMBOL_PROBE)?.getattr("symbols")
+}
+
+/// Symbols of the module `text` as found by `probe`, or `None` if it does not parse.
+fn parse_symbols(probe: &Bound<'_, PyAny>, text: &str) -> PyResult<Option<Vec<Symbol>>> {
+    let found: ProbeResult = probe.call1((text,))?.extract()?;
+    Ok(symbols_from_probe(found))
+}
+
+/// Turns the tuples `SYMBOL_PROBE` returns into symbols, skipping kinds the editor does not know.
+fn symbols_from_probe(found: ProbeResult) -> Option<Vec<Symbol>> {
+    found.map(|found| {
+        found
+            .into_iter()
+            .filter_map(|(kind, name, line, container, detail)| {
+                Some(Symbol { kind: SymbolKind::from_probe(&kind)?, name, line, container, detail })
+            })
+            .collect()
+    })
+}
+
+/// Rows of the outline matching `filter`, as indices into `symbols`: each class, function or
+/// constant with the methods listed under it. A class is kept while any of its methods match,
+/// and shows all of them when its own name does.
+fn outline_entries(symbols: &[Symbol], filter: &str) -> Vec<(usize, Vec<usize>)> {
+    let shown = |symbol: &Symbol| search::fuzzy_match(filter, &symbol.name);
+
+    let mut entries = vec![];
+    for (index, symbol) in symbols.iter().enumerate() {
+        match symbol.kind {
+            SymbolKind::Method => {}
+            SymbolKind::Class => {
+                let methods: Vec<usize> = symbols[index + 1..]
+                    .iter()
+                    .enumerate()
+                    .take_while(|(_, method)| method.kind == SymbolKind::Method)
+                    .filter(|(_, method)| shown(symbol) || shown(method))
+                    .map(|(offset, _)| index + 1 + offset)
+                    .collect();
+                if shown(symbol) || !methods.is_empty() {
+                    entries.push((index, methods));
+                }
+            }
+            SymbolKind::Function | SymbolKind::Constant => {
+                if shown(symbol) {
+                    entries.push((index, vec![]));
+                }
+            }
+        }
+    }
+    entries
+}
+
+
+#[derive(Default)]
+struct ParsedOutline {
+    path: PathBuf,
+    /// The buffer last sent for parsing.
+    text: String,
+    parsing: bool,
+    symbols: Vec<Symbol>,
+}
+
+/// The Outline tab: symbols of the active file, reparsed in the background as it is edited.
+/// While the buffer does not parse, the last outline that did stays up.
+pub struct Outline {
+    parsed: Arc<Mutex<ParsedOutline>>,
+    parser: DebouncedParser<PathBuf>,
+    filter: String,
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        let parsed = Arc::new(Mutex::new(ParsedOutline::default()));
+        let shared = parsed.clone();
+        let parser = DebouncedParser::spawn(move |py, path: &PathBuf, text: &str| {
+            let symbols = symbol_probe(py).and_then(|probe| parse_symbols(&probe, text));
+
+            // A newer buffer, or another file, may have been sent while this one parsed.
+            let mut parsed = lock(&shared);
+            if parsed.path == *path && parsed.text == text {
+                parsed.parsing = false;
+                if let Ok(Some(symbols)) = symbols {
+                    parsed.symbols = symbols;
+                }
+            }
+        });
+        Self { parsed, parser, filter: String::new() }
+    }
+}
+
+impl Outline {
+    /// Reparses `text` of `path` once it settles, if it changed since it was last seen.
+    pub fn sync(&self, path: &Path, text: &str) {
+        let mut parsed = lock(&self.parsed);
+        if parsed.path == path && parsed.text == text {
+            return;
+        }
+        if parsed.path != path {
+            parsed.path = path.to_owned();
+            parsed.symbols.clear();
+        }
+        parsed.text = text.to_owned();
+        parsed.parsing = true;
+        self.parser.send(path.to_owned(), text.to_owned());
+    }
+
+    /// Forgets the outline when no Python file is active.
+    pub fn clear(&self) {
+        // With no path, a parse still running does not land.
+        *lock(&self.parsed) = ParsedOutline::default();
+    }
+
+    fn symbol_button(ui: &mut Ui, symbol: &Symbol, current: bool) -> bool {
+        let label = egui::RichText::new(format!("{} {}", symbol.kind.icon(), symbol.name));
+        ui.horizontal(|ui| {
+            let clicked = ui.selectable_label(current, label).on_hover_text(format!("{}{}", symbol.name, symbol.detail)).clicked();
+            ui.weak(symbol.line.to_string());
+            clicked
+        })
+        .inner
+    }
+
+    /// Lists the symbols of `path`, marking the one holding `cursor_line`. Returns the one that was clicked.
+    pub fn ui(&mut self, ui: &mut Ui, path: &Path, cursor_line: Option<usize>) -> Option<SourceLocation> {
+        let parsed = lock(&self.parsed);
+        if parsed.parsing {
+            ui.ctx().request_repaint();
+        }
+        if parsed.path != path {
+            return None;
+        }
+
+        ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("Filter symbols...").desired_width(f32::INFINITY));
+        ui.add(egui::Separator::default().grow(5.0));
+        if parsed.symbols.is_empty() {
+            ui.weak(if parsed.parsing { "Parsing..." } else { "No classes, functions or constants." });
+            return None;
+        }
+
+        // The symbol the cursor is in is the last one starting at or above it.
+        let current = cursor_line.and_then(|line| parsed.symbols.iter().rposition(|symbol| symbol.line <= line));
+
+        let mut clicked = None;
+        egui::ScrollArea::vertical().id_salt("outline").show(ui, |ui| {
+            for (index, methods) in outline_entries(&parsed.symbols, self.filter.trim()) {
+                let symbol = &parsed.symbols[index];
+                if methods.is_empty() {
+                    if Self::symbol_button(ui, symbol, current == Some(index)) {
+                        clicked = Some(symbol.line);
+                    }
+                    continue;
+                }
+
+                let id = ui.make_persistent_id(("outline_class", &symbol.name, symbol.line));
+                egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true)
+                    .show_header(ui, |ui| {
+                        if Self::symbol_button(ui, symbol, current == Some(index)) {
+                            clicked = Some(symbol.line);
+                        }
+                    })
+                    .body(|ui| {
+                        for method_index in methods {
+                            let method = &parsed.symbols[method_index];
+                            if Self::symbol_button(ui, method, current == Some(method_index)) {
+                                clicked = Some(method.line);
+                            }
+                        }
+                    });
+            }
+        });
+        clicked.map(|line| SourceLocation { file: path.display().to_string(), line })
+    }
+}
+
+
+#[derive(Default)]
+struct IndexResults {
+    symbols: Vec<(PathBuf, Symbol)>,
+    running: bool,
+    /// Bumped whenever `symbols` is replaced.
+    version: usize,
+}
+
+/// Symbols of every `.py` file in a project, collected on a background thread.
+/// Rebuilding stops the build still running.
+#[derive(Default)]
+struct SymbolIndex {
+    results: Arc<Mutex<IndexResults>>,
+    generation: Arc<AtomicUsize>,
+}
+
+impl SymbolIndex {
+    fn rebuild(&self, root: PathBuf) {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        lock(&self.results).running = true;
+
+        let results = self.results.clone();
+        let current = self.generation.clone();
+        std::thread::spawn(move || {
+            let files: Vec<(PathBuf, String)> = search::text_files(&root)
+                .into_iter()
+                .filter(|path| path.extension().is_some_and(|ext| ext == "py"))
+                .filter_map(|path| Some((path.clone(), std::fs::read_to_string(&path).ok()?)))
+                .collect();
+
+            pyo3::prepare_freethreaded_python();
+            let symbols = Python::with_gil(|py| -> PyResult<Option<Vec<(PathBuf, Symbol)>>> {
+                let probe = symbol_probe(py)?;
+                let mut symbols = vec![];
+                for (path, text) in files {
+                    if current.load(Ordering::Relaxed) != generation {
+                        return Ok(None);
+                    }
+                    // Files that do not parse are left out until they do.
+                    let found = parse_symbols(&probe, &text)?.unwrap_or_default();
+                    symbols.extend(found.into_iter().map(|symbol| (path.clone(), symbol)));
+                }
+                Ok(Some(symbols))
+            });
+
+            let mut results = lock(&results);
+            if current.load(Ordering::Relaxed) == generation {
+                results.running = false;
+                if let Ok(Some(symbols)) = symbols {
+                    results.symbols = symbols;
+                    results.version += 1;
+                }
+            }
+        });
+    }
+}
+
+/// The best picker matches for the lowercase `query`: names starting with it first, then shorter names.
+fn rank_matches<'a>(symbols: &'a [(PathBuf, Symbol)], query: &str) -> Vec<&'a (PathBuf, Symbol)> {
+    let mut matches: Vec<&(PathBuf, Symbol)> = symbols
+        .iter()
+        .filter(|(_, symbol)| search::fuzzy_match(query, &symbol.qualified_name()))
+        .collect();
+    matches.sort_by_cached_key(|(_, symbol)| (!symbol.name.to_lowercase().starts_with(query), symbol.name.len()));
+    matches.truncate(MAX_PICKER_RESULTS);
+    matches
+}
+
+
+/// The go to symbol in project picker (Ctrl+T).
+#[derive(Default)]
+pub struct SymbolPicker {
+    pub open: bool,
+    root: PathBuf,
+    query: String,
+    selected: usize,
+    index: SymbolIndex,
+    /// Best matches for `query`, kept until the query or the index changes.
+    matches: Vec<(PathBuf, Symbol)>,
+    /// Query and index version `matches` were found for.
+    matched: Option<(String, usize)>,
+}
+
+impl SymbolPicker {
+    /// Opens the picker over the project at `root`, reindexing it so recent edits show up.
+    pub fn show(&mut self, root: &Path) {
+        self.open = true;
+        self.root = root.to_owned();
+        self.query.clear();
+        self.selected = 0;
+        self.matched = None;
+        self.index.rebuild(root.to_owned());
+    }
+
+    /// Finds the best matches again if the query or the index changed since they were found.
+    /// Returns whether the index is still being built.
+    fn update_matches(&mut self) -> bool {
+        let results = lock(&self.index.results);
+        let query = self.query.trim().to_lowercase();
+        let key = (query, results.version);
+        if self.matched.as_ref() != Some(&key) {
+            self.matches = rank_matches(&results.symbols, &key.0).into_iter().cloned().collect();
+            self.matched = Some(key);
+        }
+        results.running
+    }
+
+    /// Returns the symbol that was picked.
+    pub fn ui(&mut self, ctx: &egui::Context) -> Option<SourceLocation> {
+        if !self.open {
+            return None;
+        }
+        let running = self.update_matches();
+        let matches = &self.matches;
+
+        let mut picked = None;
+        let response = egui::Modal::new(Id::new("symbol_picker")).show(ctx, |ui| {
+            ui.set_width(480.0);
+            ui.horizontal(|ui| {
+                ui.label("Go to symbol in project");
+                if running {
+                    ui.spinner();
+                    ui.ctx().request_repaint();
+                }
+            });
+
+            let down = ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::ArrowDown));
+            let up = ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::ArrowUp));
+            if down {
+                self.selected += 1;
+            } else if up {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            self.selected = self.selected.min(matches.len().saturating_sub(1));
+
+            let field = ui.add(egui::TextEdit::singleline(&mut self.query).hint_text("Symbol name").desired_width(f32::INFINITY));
+            field.request_focus();
+            if field.changed() {
+                self.selected = 0;
+            }
+            if field.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                picked = matches.get(self.selected);
+            }
+
+            egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                if matches.is_empty() && !running {
+                    ui.weak("No matching symbols.");
+                }
+                for (index, entry) in matches.iter().enumerate() {
+                    let (path, symbol) = entry;
+                    let file = path.strip_prefix(&self.root).unwrap_or(path).display();
+                    let response = ui.horizontal(|ui| {
+                        let response = ui.selectable_label(index == self.selected, format!("{} {}", symbol.kind.icon(), symbol.qualified_name()));
+                        ui.weak(format!("{file}:{}", symbol.line));
+                        response
+                    }).inner;
+                    if index == self.selected && (up || down) {
+                        response.scroll_to_me(None);
+                    }
+                    if response.clicked() {
+                        picked = Some(entry);
+                    }
+                }
+            });
+        });
+
+        if let Some((path, symbol)) = picked {
+            self.open = false;
+            return Some(SourceLocation { file: path.display().to_string(), line: symbol.line });
+        }
+        if response.should_close() {
+            self.open = false;
+        }
+        None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{outline_entries, rank_matches, symbols_from_probe, Symbol, SymbolKind};
+
+    fn symbol(kind: SymbolKind, name: &str, line: usize) -> Symbol {
+        Symbol { kind, name: name.to_owned(), line, container: None, detail: String::new() }
+    }
+
+    fn outline() -> Vec<Symbol> {
+        vec![
+            symbol(SymbolKind::Constant, "SPEED", 1),
+            symbol(SymbolKind::Class, "Player", 3),
+            symbol(SymbolKind::Method, "update", 4),
+            symbol(SymbolKind::Method, "draw", 7),
+            symbol(SymbolKind::Class, "Enemy", 10),
+            symbol(SymbolKind::Function, "main", 14),
+        ]
+    }
+
+    #[test]
+    fn probe_results_become_symbols() {
+        let found = vec![
+            ("class".to_owned(), "Player".to_owned(), 4, None, "(Sprite)".to_owned()),
+            ("method".to_owned(), "update".to_owned(), 5, Some("Player".to_owned()), "(self, dt)".to_owned()),
+            ("lambda".to_owned(), "skipped".to_owned(), 7, None, String::new()),
+            ("constant".to_owned(), "SPEED".to_owned(), 9, None, String::new()),
+        ];
+        let symbols = symbols_from_probe(Some(found)).unwrap();
+
+        let names: Vec<String> = symbols.iter().map(Symbol::qualified_name).collect();
+        assert_eq!(names, ["Player", "Player.update", "SPEED"]);
+        assert!(symbols[0].kind == SymbolKind::Class && symbols[1].kind == SymbolKind::Method && symbols[2].kind == SymbolKind::Constant);
+        assert_eq!((symbols[1].line, symbols[1].detail.as_str()), (5, "(self, dt)"));
+    }
+
+    #[test]
+    fn unparsable_source_has_no_symbols() {
+        assert!(symbols_from_probe(None).is_none());
+    }
+
+    #[test]
+    fn methods_are_grouped_under_their_class() {
+        assert_eq!(outline_entries(&outline(), ""), [(0, vec![]), (1, vec![2, 3]), (4, vec![]), (5, vec![])]);
+    }
+
+    #[test]
+    fn filtering_keeps_classes_with_matching_methods() {
+        assert_eq!(outline_entries(&outline(), "draw"), [(1, vec![3])]);
+        // A matching class shows all of its methods.
+        assert_eq!(outline_entries(&outline(), "player"), [(1, vec![2, 3])]);
+        assert_eq!(outline_entries(&outline(), "main"), [(5, vec![])]);
+    }
+
+    #[test]
+    fn picker_ranks_prefix_matches_then_shorter_names() {
+        let symbols: Vec<(PathBuf, Symbol)> = ["reset_level", "draw", "update", "draw_hud", "redraw"]
+            .into_iter()
+            .map(|name| (PathBuf::from("game.py"), symbol(SymbolKind::Function, name, 1)))
+            .collect();
+
+        let ranked: Vec<&str> = rank_matches(&symbols, "dr").into_iter().map(|(_, symbol)| symbol.name.as_str()).collect();
+        assert_eq!(ranked, ["draw", "draw_hud", "redraw"]);
+    }
+}
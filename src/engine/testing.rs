@@ -0,0 +1,394 @@
+use std::{fs, path::{Path, PathBuf}, time::{Duration, Instant}};
+
+use pyo3::exceptions::{PyAssertionError, PyRuntimeError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::engine::{python::{self, GameSession}, redengine::ENTRY_FILE};
+
+
+/// Folder inside a project that holds the `test_*.py` scenario files.
+pub const TESTS_DIR: &str = "tests";
+
+struct Harness {
+    project: PathBuf,
+    session: Option<GameSession>,
+}
+
+lazy_static::lazy_static! {
+    static ref HARNESS: std::sync::Mutex<Harness> = std::sync::Mutex::new(Harness { project: PathBuf::new(), session: None });
+    pub static ref TEST_RUN: std::sync::Mutex<TestRun> = std::sync::Mutex::new(TestRun::default());
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    Errored,
+}
+
+#[derive(Clone)]
+pub struct TestResult {
+    pub file: PathBuf,
+    pub name: String,
+    pub outcome: TestOutcome,
+    pub message: String,
+    pub duration: Duration,
+}
+
+/// Progress of the test run started from the Tests tab.
+#[derive(Default)]
+pub struct TestRun {
+    pub running: bool,
+    pub results: Vec<TestResult>,
+    pub error: Option<String>,
+}
+
+
+// ---- `redengine.testing` ----------------------------------------------------
+
+fn with_session<T>(f: impl FnOnce(&mut GameSession) -> PyResult<T>) -> PyResult<T> {
+    let mut harness = HARNESS.lock().unwrap();
+    match &mut harness.session {
+        Some(session) => f(session),
+        None => Err(PyRuntimeError::new_err("No game running, call redengine.testing.launch() first")),
+    }
+}
+
+fn stop_session(py: Python<'_>) {
+    let session = HARNESS.lock().unwrap().session.take();
+    if let Some(session) = session {
+        if let Err(e) = session.game.bind(py).call_method0("quit") {
+            e.print(py);
+        }
+    }
+}
+
+/// Launches the project's game (or `script`, relative to the project) through the engine's runner.
+#[pyfunction]
+#[pyo3(signature = (script = None, seed = None))]
+fn launch(py: Python<'_>, script: Option<PathBuf>, seed: Option<u64>) -> PyResult<Py<PyAny>> {
+    stop_session(py);
+
+    let project = HARNESS.lock().unwrap().project.clone();
+    let path = project.join(script.unwrap_or_else(|| PathBuf::from(ENTRY_FILE)));
+    let code = fs::read_to_string(&path)
+        .map_err(|e| PyRuntimeError::new_err(format!("Could not read {}: {e}", path.display())))?;
+
+    if let Some(seed) = seed {
+        python::seed_random(py, seed)?;
+    }
+
+    let session = GameSession::launch(py, &code, &path.display().to_string(), python::DEFAULT_FRAME_SIZE)?;
+    let game = session.game.clone_ref(py);
+    HARNESS.lock().unwrap().session = Some(session);
+    Ok(game)
+}
+
+/// Steps the game `frames` times and returns the current frame number.
+#[pyfunction]
+#[pyo3(signature = (frames = 1))]
+fn advance(py: Python<'_>, frames: usize) -> PyResult<usize> {
+    // Stepped through a handle of its own with the harness unlocked, so the game can call
+    // back into `redengine.testing` while it runs.
+    let mut session = with_session(|session| Ok(session.clone_ref(py)))?;
+    for _ in 0..frames {
+        python::run_queued_instructions(py);
+        let stepped = session.step(py);
+
+        // Unless the game was relaunched meanwhile, `frame()` reports the progress.
+        let mut harness = HARNESS.lock().unwrap();
+        if let Some(current) = harness.session.as_mut().filter(|current| current.game.is(&session.game)) {
+            current.frame = session.frame;
+        }
+        drop(harness);
+
+        if stepped?.is_none() {
+            return Err(PyRuntimeError::new_err(format!("Game ended at frame {}", session.frame)));
+        }
+    }
+    Ok(session.frame)
+}
+
+// pyo3 0.21 unwraps required reference arguments inside an `unsafe fn` without an unsafe block,
+// which edition 2024 reports, so the functions taking `&str` are kept together here.
+#[expect(unsafe_op_in_unsafe_fn, reason = "generated by pyo3's argument extraction")]
+mod keys {
+    use pyo3::prelude::*;
+
+    use crate::engine::python;
+
+    /// Posts a key down and key up event for `key`, e.g. `press("r")`.
+    #[pyfunction]
+    pub fn press(py: Python<'_>, key: &str) -> PyResult<()> {
+        python::post_key_event(py, key, true)?;
+        python::post_key_event(py, key, false)
+    }
+
+    #[pyfunction]
+    pub fn key_down(py: Python<'_>, key: &str) -> PyResult<()> {
+        python::post_key_event(py, key, true)
+    }
+
+    #[pyfunction]
+    pub fn key_up(py: Python<'_>, key: &str) -> PyResult<()> {
+        python::post_key_event(py, key, false)
+    }
+}
+
+/// The running `game` object, for asserting on its attributes.
+#[pyfunction]
+fn game(py: Python<'_>) -> PyResult<Py<PyAny>> {
+    with_session(|session| Ok(session.game.clone_ref(py)))
+}
+
+#[pyfunction]
+fn frame() -> PyResult<usize> {
+    with_session(|session| Ok(session.frame))
+}
+
+fn install_module(py: Python<'_>) -> PyResult<()> {
+    let module = PyModule::new_bound(py, "redengine.testing")?;
+    module.add_function(wrap_pyfunction_bound!(launch, &module)?)?;
+    module.add_function(wrap_pyfunction_bound!(advance, &module)?)?;
+    module.add_function(wrap_pyfunction_bound!(keys::press, &module)?)?;
+    module.add_function(wrap_pyfunction_bound!(keys::key_down, &module)?)?;
+    module.add_function(wrap_pyfunction_bound!(keys::key_up, &module)?)?;
+    module.add_function(wrap_pyfunction_bound!(game, &module)?)?;
+    module.add_function(wrap_pyfunction_bound!(frame, &module)?)?;
+    python::register_submodule(py, "testing", &module)
+}
+
+
+// ---- Test runner ------------------------------------------------------------
+
+/// Lists the `test_*.py` files in the project's `tests` folder.
+pub fn discover(project: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(project.join(TESTS_DIR))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                    name.starts_with("test_") && name.ends_with(".py")
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    files.sort();
+    files
+}
+
+fn run_file(py: Python<'_>, file: &Path, on_result: &mut impl FnMut(TestResult)) -> PyResult<()> {
+    let code = fs::read_to_string(file)
+        .map_err(|e| PyRuntimeError::new_err(format!("Could not read {}: {e}", file.display())))?;
+
+    let globals = PyDict::new_bound(py);
+    globals.set_item("__name__", "redengine_test")?;
+    globals.set_item("__file__", file)?;
+    globals.set_item("__builtins__", py.import_bound("builtins")?)?;
+    py.run_bound(&code, Some(&globals), None)?;
+
+    let tests: Vec<(String, Bound<'_, PyAny>)> = globals
+        .iter()
+        .filter_map(|(name, value)| Some((name.extract::<String>().ok()?, value)))
+        .filter(|(name, value)| name.starts_with("test_") && value.is_callable())
+        .collect();
+
+    for (name, test) in tests {
+        let started = Instant::now();
+        let result = test.call0();
+        stop_session(py);
+
+        let (outcome, message) = match result {
+            Ok(_) => (TestOutcome::Passed, String::new()),
+            Err(e) if e.is_instance_of::<PyAssertionError>(py) => (TestOutcome::Failed, python::format_exception(py, &e)),
+            Err(e) => (TestOutcome::Errored, python::format_exception(py, &e)),
+        };
+
+        on_result(TestResult {
+            file: file.to_path_buf(),
+            name,
+            outcome,
+            message,
+            duration: started.elapsed(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Runs every scenario test in the project on the current thread.
+/// A file that fails to load is reported as a single errored test.
+pub fn run_tests(project: &Path, mut on_result: impl FnMut(TestResult)) {
+    pyo3::prepare_freethreaded_python();
+
+    Python::with_gil(|py| {
+        HARNESS.lock().unwrap().project = project.to_path_buf();
+
+        let setup = || -> PyResult<()> {
+            install_module(py)?;
+            py.import_bound("sys")?.getattr("path")?.call_method1("insert", (0, project))?;
+            Ok(())
+        };
+        if let Err(e) = setup() {
+            e.print(py);
+            return;
+        }
+
+        for file in discover(project) {
+            let started = Instant::now();
+            if let Err(e) = run_file(py, &file, &mut on_result) {
+                on_result(TestResult {
+                    file: file.clone(),
+                    name: "<module>".to_owned(),
+                    outcome: TestOutcome::Errored,
+                    message: python::format_exception(py, &e),
+                    duration: started.elapsed(),
+                });
+            }
+        }
+    });
+}
+
+/// Runs the project's tests on a background thread, publishing results to `TEST_RUN`.
+pub fn run_tests_threaded(project: PathBuf) {
+    *TEST_RUN.lock().unwrap() = TestRun { running: true, ..Default::default() };
+
+    std::thread::spawn(move || {
+        if discover(&project).is_empty() {
+            TEST_RUN.lock().unwrap().error = Some(format!("No test_*.py files in {}", project.join(TESTS_DIR).display()));
+        }
+
+        run_tests(&project, |result| TEST_RUN.lock().unwrap().results.push(result));
+        TEST_RUN.lock().unwrap().running = false;
+    });
+}
+
+
+// ---- Reports ----------------------------------------------------------------
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Formats results as JUnit XML with one `testsuite` per test file.
+pub fn to_junit_xml(project: &Path, results: &[TestResult]) -> String {
+    let mut files: Vec<&PathBuf> = results.iter().map(|r| &r.file).collect();
+    files.dedup();
+
+    let count = |results: &[&TestResult], outcome| results.iter().filter(|r| r.outcome == outcome).count();
+    let seconds = |results: &[&TestResult]| results.iter().map(|r| r.duration.as_secs_f64()).sum::<f64>();
+
+    let all: Vec<&TestResult> = results.iter().collect();
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+        all.len(),
+        count(&all, TestOutcome::Failed),
+        count(&all, TestOutcome::Errored),
+        seconds(&all),
+    );
+
+    for file in files {
+        let suite: Vec<&TestResult> = results.iter().filter(|r| &r.file == file).collect();
+        let suite_name = file.strip_prefix(project).unwrap_or(file).display().to_string();
+        let class_name = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+            escape_xml(&suite_name),
+            suite.len(),
+            count(&suite, TestOutcome::Failed),
+            count(&suite, TestOutcome::Errored),
+            seconds(&suite),
+        ));
+
+        for result in suite {
+            let case = format!(
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                escape_xml(&class_name),
+                escape_xml(&result.name),
+                result.duration.as_secs_f64(),
+            );
+            let tag = match result.outcome {
+                TestOutcome::Passed => {
+                    xml.push_str(&format!("{case}/>\n"));
+                    continue;
+                }
+                TestOutcome::Failed => "failure",
+                TestOutcome::Errored => "error",
+            };
+            let summary = result.message.lines().last().unwrap_or_default();
+            xml.push_str(&format!(
+                "{case}>\n      <{tag} message=\"{}\">{}</{tag}>\n    </testcase>\n",
+                escape_xml(summary),
+                escape_xml(&result.message),
+            ));
+        }
+
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use super::{escape_xml, to_junit_xml, TestOutcome, TestResult};
+
+    fn result(file: &str, name: &str, outcome: TestOutcome, message: &str) -> TestResult {
+        TestResult {
+            file: PathBuf::from(file),
+            name: name.to_owned(),
+            outcome,
+            message: message.to_owned(),
+            duration: Duration::from_millis(250),
+        }
+    }
+
+    #[test]
+    fn escapes_markup_characters() {
+        assert_eq!(escape_xml(r#"<a href="x">Tom & Jerry's</a>"#), "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;");
+        assert_eq!(escape_xml("&lt;"), "&amp;lt;");
+    }
+
+    #[test]
+    fn counts_tests_per_suite_and_overall() {
+        let results = [
+            result("/game/tests/test_menu.py", "test_opens", TestOutcome::Passed, ""),
+            result("/game/tests/test_menu.py", "test_closes", TestOutcome::Failed, "AssertionError"),
+            result("/game/tests/test_save.py", "test_loads", TestOutcome::Errored, "KeyError: 'slot'"),
+        ];
+        let xml = to_junit_xml(Path::new("/game"), &results);
+
+        assert!(xml.contains(r#"<testsuites tests="3" failures="1" errors="1" time="0.750">"#));
+        assert!(xml.contains(r#"<testsuite name="tests/test_menu.py" tests="2" failures="1" errors="0" time="0.500">"#));
+        assert!(xml.contains(r#"<testsuite name="tests/test_save.py" tests="1" failures="0" errors="1" time="0.250">"#));
+        assert!(xml.contains(r#"<testcase classname="test_menu" name="test_opens" time="0.250"/>"#));
+    }
+
+    #[test]
+    fn failures_and_errors_carry_the_last_message_line() {
+        let results = [
+            result("/game/tests/test_menu.py", "test_closes", TestOutcome::Failed, "Traceback:\n  line 3\nAssertionError: 1 < 2"),
+            result("/game/tests/test_menu.py", "test_crashes", TestOutcome::Errored, "KeyError: 'slot'"),
+        ];
+        let xml = to_junit_xml(Path::new("/game"), &results);
+
+        assert!(xml.contains(r#"<failure message="AssertionError: 1 &lt; 2">Traceback:"#));
+        assert!(xml.contains("</failure>\n    </testcase>"));
+        assert!(xml.contains(r#"<error message="KeyError: &apos;slot&apos;">KeyError: &apos;slot&apos;</error>"#));
+    }
+}
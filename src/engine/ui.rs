@@ -3,102 +3,358 @@ use core::f32;
 use egui::{Context, Stroke, TextureHandle, Ui, WidgetText};
 use egui_dock::TabViewer;
 
+use crate::engine::capture::{self, CaptureFormat};
+use crate::engine::debug_draw;
+use crate::engine::debugger::DebugView;
+use crate::engine::diagnostics;
+use crate::engine::editor::{Editor, SourceLocation};
+use crate::engine::frame_history::{self, FrameHistoryView};
+use crate::engine::memory::MemoryView;
+use crate::engine::profiler::ProfilerView;
+use crate::engine::project_search::ProjectSearchView;
+use crate::engine::python::{self, FRAME_IMAGE};
 use crate::engine::redengine::{self, FileExplorerItem, GameState, Project};
+use crate::engine::rewind;
+use crate::engine::save_state;
+use crate::engine::search::{self, FileSearch};
+use crate::engine::scene::SpriteBrowser;
+use crate::engine::state_diff::{self, StateDiffView};
+use crate::engine::testing::{self, TestOutcome};
+use crate::engine::viewport::{self, ViewportView};
 
 #[derive(Debug, PartialEq)]
 pub enum  CentralPanelTab {
     Viewport,
     Scripting,
+    Tests,
+    StateDiff,
+    Profiler,
+    Memory,
+    Search,
+    Problems,
 }
 
 pub struct CentralTabViewer<'a> {
     pub viewport_texture: &'a mut Option<TextureHandle>,
-    pub code_editor_content: &'a mut String,
+    pub viewport_view: &'a mut ViewportView,
+    pub viewport_detached: &'a mut bool,
+    pub sprite_browser: &'a mut SpriteBrowser,
+    pub state_diff: &'a mut StateDiffView,
+    pub frame_history: &'a mut FrameHistoryView,
+    pub profiler: &'a mut ProfilerView,
+    pub memory: &'a mut MemoryView,
+    pub project_search: &'a mut ProjectSearchView,
+    /// Source line another tab asked the Scripting tab to show.
+    pub goto_source: &'a mut Option<SourceLocation>,
+    pub editor: &'a mut Editor,
     pub game_state: &'a mut GameState,
+    pub project: &'a mut Project,
     pub egui_ctx: &'a Context,
 }
 
-impl<'a> TabViewer for CentralTabViewer<'a> {
-    type Tab =  CentralPanelTab;
+impl CentralTabViewer<'_> {
+    /// Starts the project's entry script, or shows why it could not start in place of the game.
+    fn launch(&mut self) {
+        // Imports are read from disk, so unsaved edits are saved before running,
+        // and a failed save keeps the game from running stale code.
+        let launched = if !self.editor.save_all() {
+            Err(self.editor.status.clone())
+        } else {
+            match self.project.entry_path() {
+                Some(entry) => redengine::launch_game(&entry, self.game_state),
+                None => Err("Open a project to run its entry file.".to_owned()),
+            }
+        };
+        if let Err(e) = launched {
+            self.game_state.launch_error = Some(e);
+        }
+    }
 
-    fn title(&mut self, tab: &mut Self::Tab) -> WidgetText {
-        match tab {
-            CentralPanelTab::Viewport => "Viewport".into(),
-            CentralPanelTab::Scripting => "Scripting".into(),
+    /// Toolbar, game image and view controls. Shared by the Viewport tab and the detached window.
+    pub fn draw_viewport(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+           let button_ico = |b| if b { format!("{}", egui_phosphor::regular::STOP)} else { format!("{}", egui_phosphor::regular::PLAY) };
+           
+           // The test harness runs scenarios in the same `__main__`, so Play waits for it to finish.
+           let tests_running = testing::TEST_RUN.lock().unwrap().running;
+           if ui.add_enabled( // Handles the launch/close button
+                self.game_state.running || !tests_running,
+                egui::Button::new(egui::RichText::new(button_ico(self.game_state.running)))
+                    .min_size(egui::vec2(75.0, 0.0))
+           ).on_disabled_hover_text("Wait for the tests to finish").clicked() { // Handles the button logic when clicked
+                if self.game_state.running {
+                    redengine::close_game(&mut self.game_state, self.egui_ctx);
+                } else {
+                    self.launch();
+                }
+           }
+
+           ui.add_enabled_ui(self.game_state.running, |ui| {
+                let paused = python::is_paused();
+                let (icon, hint) = if paused {
+                    (egui_phosphor::regular::PLAY_PAUSE, "Resume")
+                } else {
+                    (egui_phosphor::regular::PAUSE, "Pause")
+                };
+                if ui.selectable_label(paused, icon).on_hover_text(hint).clicked() {
+                    python::set_paused(!paused);
+                    if !paused {
+                        state_diff::capture_current();
+                    }
+                }
+                if ui.add_enabled(paused, egui::Button::new(egui_phosphor::regular::SKIP_FORWARD))
+                    .on_hover_text("Step one frame")
+                    .clicked()
+                {
+                    python::request_step();
+                }
+                ui.label(format!("Frame {}", python::FRAME_COUNTER.load(std::sync::atomic::Ordering::Relaxed)));
+           });
+
+           ui.separator();
+           self.draw_save_state_toolbar(ui);
+           ui.separator();
+           self.draw_capture_toolbar(ui);
+
+           ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let (icon, hint) = if *self.viewport_detached {
+                    (egui_phosphor::regular::ARROW_SQUARE_IN, "Dock viewport back into the editor")
+                } else {
+                    (egui_phosphor::regular::ARROW_SQUARE_OUT, "Pop viewport out into its own window")
+                };
+                if ui.button(icon).on_hover_text(hint).clicked() {
+                    *self.viewport_detached = !*self.viewport_detached;
+                }
+           });
+        });
+        ui.add(egui::Separator::default().grow(5.0));
+
+        // Draw viewport texture
+        let texture = self.viewport_texture.as_ref().filter(|_| self.game_state.running);
+        if let Some(tex) = texture {
+            // Space for the rewind timeline, the controls row and the filmstrip when it is open.
+            let row_height = ui.spacing().interact_size.y + ui.spacing().item_spacing.y;
+            let filmstrip_height = if self.frame_history.show_filmstrip { frame_history::FILMSTRIP_HEIGHT } else { 0.0 };
+            let (response, transform) = self.viewport_view.show(ui, tex, self.game_state.size, &self.project.settings, 2.0 * row_height + filmstrip_height);
+            viewport::forward_input(ui, &response);
+
+            if response.clicked() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    self.sprite_browser.select_at(&transform, pos);
+                }
+            }
+
+            let overlay = ui.painter_at(response.rect);
+            debug_draw::set_enabled(self.viewport_view.show_debug_shapes);
+            if self.viewport_view.show_debug_shapes {
+                debug_draw::paint(&overlay, &transform);
+            }
+            self.sprite_browser.paint_selection(&overlay, &transform);
+
+            rewind::configure(self.project.settings.rewind);
+            if rewind::timeline(ui, &mut self.project.settings.rewind) {
+                self.project.save_settings();
+            }
+            ui.horizontal(|ui| {
+                if self.viewport_view.controls(ui, &mut self.project.settings) {
+                    self.project.save_settings();
+                }
+                ui.toggle_value(&mut self.frame_history.show_filmstrip, egui_phosphor::regular::FILM_REEL)
+                    .on_hover_text("Recently rendered frames");
+                ui.separator();
+                viewport::pixel_readout(ui, &transform, response.hover_pos());
+            });
+            if self.frame_history.show_filmstrip {
+                self.frame_history.filmstrip(ui);
+            }
+            self.frame_history.inspector(ui.ctx());
+        } else {
+            ui.centered_and_justified(|ui| {
+                match &self.game_state.launch_error {
+                    Some(error) => ui.label(error),
+                    None => ui.label("No image available"),
+                };
+            });
         }
     }
 
-    fn ui(&mut self, ui: &mut Ui, tab: &mut Self::Tab) {
-        match tab {
-            CentralPanelTab::Viewport => {
-                ui.vertical_centered(|ui| {
-                   let button_ico = |b| if b { format!("{}", egui_phosphor::regular::STOP)} else { format!("{}", egui_phosphor::regular::PLAY) };
-                   
-                   if ui.add( // Handles the launch/close button
-                    egui::Button::new(egui::RichText::new(button_ico(self.game_state.running)))
-                            .min_size(egui::vec2(75.0, 0.0))
-                   ).clicked() { // Handles the button logic when clicked
-                        if self.game_state.running {
-                            redengine::close_game(&mut self.game_state, self.egui_ctx);
-                        } else {
-                            redengine::launch_game(&self.code_editor_content, &mut self.game_state);
-                        }
-                   }
-                
+    fn draw_save_state_toolbar(&mut self, ui: &mut Ui) {
+        let slot_path = save_state::slot_path(self.project.project_path.as_deref(), self.game_state.state_slot);
+        let used_slots = save_state::used_slots(self.project.project_path.as_deref());
+        let used = |slot: usize| used_slots.get(slot - 1).copied().unwrap_or_default();
+
+        ui.add_enabled_ui(self.game_state.running, |ui| {
+            egui::ComboBox::from_id_salt("save_state_slot")
+                .width(60.0)
+                .selected_text(format!("Slot {}", self.game_state.state_slot))
+                .show_ui(ui, |ui| {
+                    for slot in 1..=save_state::SLOT_COUNT {
+                        let label = if used(slot) { format!("Slot {slot} {}", egui_phosphor::regular::FLOPPY_DISK) } else { format!("Slot {slot}") };
+                        ui.selectable_value(&mut self.game_state.state_slot, slot, label);
+                    }
                 });
-                ui.add(egui::Separator::default().grow(5.0));
 
-                // Draw viewport texture
-                ui.centered_and_justified(|ui|{
-                    if self.game_state.running {
-                        if let Some(tex) = &self.viewport_texture {
-                            let original_size = self.game_state.size; // [width, height]
-                            let available_size = ui.available_size();
-                            let fit_size = crate::engine::helpers::fit_aspect(original_size, available_size);
-                            
-                            ui.image((tex.id(), fit_size));
-                        }
-                    } else {
-                        ui.label("No image available");
-                    }
-                });  
+            if ui.button(egui_phosphor::regular::FLOPPY_DISK).on_hover_text("Save state").clicked() {
+                save_state::save_slot(slot_path.clone());
+            }
+            if ui.add_enabled(used(self.game_state.state_slot), egui::Button::new(egui_phosphor::regular::ARROW_COUNTER_CLOCKWISE))
+                .on_hover_text("Load state")
+                .clicked()
+            {
+                save_state::load_slot(slot_path.clone());
+            }
+        });
 
+        let status = save_state::STATUS.lock().unwrap();
+        if !status.is_empty() {
+            ui.label(egui::RichText::new(egui_phosphor::regular::INFO)).on_hover_text(status.as_str());
+        }
+    }
+
+    fn draw_capture_toolbar(&mut self, ui: &mut Ui) {
+        let captures_dir = capture::captures_dir(self.project.project_path.as_deref());
+
+        ui.add_enabled_ui(self.game_state.running, |ui| {
+            if ui.button(egui_phosphor::regular::CAMERA).on_hover_text("Save frame as PNG").clicked() {
+                if let Some(frame) = FRAME_IMAGE.get().and_then(|lock| lock.lock().clone()) {
+                    capture::screenshot(&captures_dir, &frame);
+                }
             }
-            CentralPanelTab::Scripting => {
-                let mut theme =
-                egui_extras::syntax_highlighting::CodeTheme::from_memory(ui.ctx(), ui.style());
-                ui.collapsing("Theme", |ui| {
-                    ui.group(|ui| {
-                        theme.ui(ui);
-                        theme.clone().store_in_memory(ui.ctx());
-                    });
-                });
 
-                let mut layouter = |ui: &egui::Ui, buf: &dyn egui::TextBuffer, wrap_width: f32| {
-                    let mut layout_job = egui_extras::syntax_highlighting::highlight(
-                        ui.ctx(),
-                        ui.style(),
-                        &theme,
-                        buf.as_str(),
-                        "Python",
-                    );
-                    layout_job.wrap.max_width = wrap_width;
-                    ui.fonts(|f| f.layout_job(layout_job))
+            if let Some((_, recorded, limit)) = capture::recording_progress() {
+                if ui.button(egui_phosphor::regular::STOP_CIRCLE).on_hover_text("Stop recording").clicked() {
+                    capture::stop_recording();
+                }
+                match limit {
+                    Some(limit) => ui.label(format!("{} {recorded}/{limit}", egui_phosphor::regular::RECORD)),
+                    None => ui.label(format!("{} {recorded}", egui_phosphor::regular::RECORD)),
+                };
+                self.egui_ctx.request_repaint();
+            } else {
+                let limit = (self.game_state.record_frames > 0).then_some(self.game_state.record_frames);
+                if ui.button(egui_phosphor::regular::GIF).on_hover_text("Record to animated GIF").clicked() {
+                    capture::start_recording(&captures_dir, CaptureFormat::Gif, limit);
+                }
+                if ui.button(egui_phosphor::regular::FILM_STRIP).on_hover_text("Record to numbered PNGs").clicked() {
+                    capture::start_recording(&captures_dir, CaptureFormat::PngSequence, limit);
+                }
+                ui.add(egui::DragValue::new(&mut self.game_state.record_frames).prefix("Frames: "))
+                    .on_hover_text("Number of frames to record, 0 records until stopped");
+            }
+        });
+
+        ui.label(capture::CAPTURE_STATUS.lock().unwrap().as_str());
+    }
+
+    fn draw_tests(&self, ui: &mut Ui) {
+        let mut start_run = false;
+        let run = testing::TEST_RUN.lock().unwrap();
+
+        ui.horizontal(|ui| {
+            let can_run = !run.running && !self.game_state.running && self.project.project_path.is_some();
+            start_run = ui.add_enabled(
+                can_run,
+                egui::Button::new(format!("{} Run Tests", egui_phosphor::regular::PLAY)),
+            ).on_disabled_hover_text("Open a project and stop the game to run tests").clicked();
+
+            let count = |outcome| run.results.iter().filter(|r| r.outcome == outcome).count();
+            ui.label(format!(
+                "{} passed, {} failed, {} errors",
+                count(TestOutcome::Passed),
+                count(TestOutcome::Failed),
+                count(TestOutcome::Errored),
+            ));
+
+            if run.running {
+                ui.spinner();
+                self.egui_ctx.request_repaint();
+            }
+        });
+        ui.add(egui::Separator::default().grow(5.0));
+
+        if let Some(error) = &run.error {
+            ui.label(error);
+        }
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for result in &run.results {
+                let (icon, color) = match result.outcome {
+                    TestOutcome::Passed => (egui_phosphor::regular::CHECK_CIRCLE, egui::Color32::from_rgb(90, 200, 90)),
+                    TestOutcome::Failed => (egui_phosphor::regular::X_CIRCLE, egui::Color32::from_rgb(230, 80, 80)),
+                    TestOutcome::Errored => (egui_phosphor::regular::WARNING_CIRCLE, egui::Color32::from_rgb(230, 160, 60)),
                 };
+                let file = result.file.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                let header = egui::RichText::new(format!(
+                    "{icon} {file} :: {} ({:.2}s)",
+                    result.name,
+                    result.duration.as_secs_f32()
+                )).color(color);
+
+                if result.message.is_empty() {
+                    ui.label(header);
+                } else {
+                    egui::CollapsingHeader::new(header)
+                        .id_salt((&result.file, &result.name))
+                        .show(ui, |ui| {
+                            ui.label(egui::RichText::new(&result.message).monospace());
+                        });
+                }
+            }
+        });
+
+        drop(run);
+        if start_run {
+            if let Some(project_path) = &self.project.project_path {
+                testing::run_tests_threaded(project_path.clone());
+            }
+        }
+    }
+}
 
+impl<'a> TabViewer for CentralTabViewer<'a> {
+    type Tab =  CentralPanelTab;
 
-                egui::ScrollArea::vertical().show(ui, |ui| {
-                    ui.add(
-                        egui::TextEdit::multiline(self.code_editor_content)
-                            .font(egui::TextStyle::Monospace) // for cursor height
-                            .code_editor()
-                            .desired_rows(10)
-                            .lock_focus(true)
-                            .desired_width(f32::INFINITY)
-                            .layouter(&mut layouter),
-                    );
-                });
+    fn title(&mut self, tab: &mut Self::Tab) -> WidgetText {
+        match tab {
+            CentralPanelTab::Viewport => "Viewport".into(),
+            CentralPanelTab::Scripting => "Scripting".into(),
+            CentralPanelTab::Tests => "Tests".into(),
+            CentralPanelTab::StateDiff => "State Diff".into(),
+            CentralPanelTab::Profiler => "Profiler".into(),
+            CentralPanelTab::Memory => "Memory".into(),
+            CentralPanelTab::Search => "Search".into(),
+            CentralPanelTab::Problems => match diagnostics::problem_count() {
+                0 => "Problems".into(),
+                count => format!("Problems ({count})").into(),
+            },
+        }
+    }
 
+    fn ui(&mut self, ui: &mut Ui, tab: &mut Self::Tab) {
+        match tab {
+            CentralPanelTab::Viewport => self.draw_viewport(ui),
+            CentralPanelTab::Scripting => self.editor.ui(ui),
+            CentralPanelTab::Tests => self.draw_tests(ui),
+            CentralPanelTab::StateDiff => self.state_diff.ui(ui),
+            CentralPanelTab::Profiler => {
+                if let Some(location) = self.profiler.ui(ui, self.game_state.running) {
+                    *self.goto_source = Some(location);
+                }
+            }
+            CentralPanelTab::Memory => {
+                if let Some(location) = self.memory.ui(ui, self.game_state.running) {
+                    *self.goto_source = Some(location);
+                }
+            }
+            CentralPanelTab::Problems => {
+                if let Some(location) = diagnostics::problems_ui(ui) {
+                    *self.goto_source = Some(location);
+                }
+            }
+            CentralPanelTab::Search => {
+                if let Some(location) = self.project_search.ui(ui, self.project.project_path.as_deref(), self.editor) {
+                    *self.goto_source = Some(location);
+                }
             }
         }
     }
@@ -111,13 +367,22 @@ impl<'a> TabViewer for CentralTabViewer<'a> {
 
 
 
+#[derive(PartialEq)]
 pub enum SidePanelTab {
-    FileExplorer
+    FileExplorer,
+    Sprites,
+    Debug,
+    Outline,
 }
 
 pub struct SideTabViewer<'a> {
     pub project: &'a mut Project,
-    pub code_editor_content: &'a mut String,
+    pub sprite_browser: &'a mut SpriteBrowser,
+    pub debug_view: &'a mut DebugView,
+    pub goto_source: &'a mut Option<SourceLocation>,
+    pub editor: &'a mut Editor,
+    pub search_term: &'a mut String,
+    pub file_search: &'a mut FileSearch,
     pub egui_ctx: &'a Context,
 }
 
@@ -127,28 +392,46 @@ impl<'a> TabViewer for SideTabViewer<'a> {
     fn title(&mut self, tab: &mut Self::Tab) -> WidgetText {
         match tab {
             SidePanelTab::FileExplorer => "File Explorer".into(),
+            SidePanelTab::Sprites => "Sprites".into(),
+            SidePanelTab::Debug => "Debug".into(),
+            SidePanelTab::Outline => "Outline".into(),
         }
     }
 
     fn ui(&mut self, ui: &mut Ui, tab: &mut Self::Tab) {
         //let default_bg_color = ui.style().visuals.widgets.noninteractive.bg_fill;
 
-        fn draw_file_explorer_child(ui: &mut Ui, root_itm: &FileExplorerItem, project: &Project) {
+        /// Whether the item or anything below it matches the search filter.
+        fn matches_filter(item: &FileExplorerItem, filter: &str) -> bool {
+            search::fuzzy_match(filter, &item.name) || item.children.iter().any(|child| matches_filter(child, filter))
+        }
+
+        fn draw_file_explorer_child(ui: &mut Ui, root_itm: &FileExplorerItem, project: &Project, editor: &mut Editor, filter: &str) {
             let default_bg_color = ui.style().visuals.widgets.noninteractive.bg_fill;
 
             match &root_itm.item_type {
                 redengine::FileExplorerItemType::Folder => {
                    let header_name = |b| if b { format!("{} Project", egui_phosphor::regular::FOLDER)} else { format!("{} {}", egui_phosphor::regular::FOLDER, &root_itm.name) };
-                 
-                    egui::CollapsingHeader::new( header_name(Project::is_root(project, root_itm)))
+                    let is_root = Project::is_root(project, root_itm);
+                    if !is_root && !matches_filter(root_itm, filter) {
+                        return;
+                    }
+                    // A folder whose own name matches shows all of its contents.
+                    let filter = if !is_root && search::fuzzy_match(filter, &root_itm.name) { "" } else { filter };
+
+                    egui::CollapsingHeader::new( header_name(is_root))
                         .default_open(false)
+                        .open((!filter.is_empty()).then_some(true))
                         .show(ui, |ui| {
                             for child in &root_itm.children {
-                                draw_file_explorer_child(ui, child, &project);
+                                draw_file_explorer_child(ui, child, &project, editor, filter);
                             }
                         });
                 }
                 redengine::FileExplorerItemType::File => {
+                    if !search::fuzzy_match(filter, &root_itm.name) {
+                        return;
+                    }
                     let base_color = ui.style().visuals.widgets.noninteractive.bg_fill;
                     //  // your base color
                     ui.style_mut().visuals.widgets.active.weak_bg_fill = base_color;
@@ -171,8 +454,7 @@ impl<'a> TabViewer for SideTabViewer<'a> {
     
                     let response = ui.add(btn);
                     if response.clicked() {
-                        // let contents: String = fs::read_to_string(&root_itm.path).unwrap();
-                        // *self.code_editor_content = contents;
+                        editor.open(&root_itm.path);
                     }
                 }
             }
@@ -189,9 +471,13 @@ impl<'a> TabViewer for SideTabViewer<'a> {
                         // Code Here >>>>
                     };
 
+                    ui.toggle_value(&mut self.file_search.in_contents, egui_phosphor::regular::TEXT_AA)
+                        .on_hover_text("Search in file contents");
+
+                    let hint = if self.file_search.in_contents { "Search in files..." } else { "Search..." };
                     ui.add(
-                        egui::TextEdit::singleline(&mut String::from(""))
-                            .hint_text("Search...")
+                        egui::TextEdit::singleline(self.search_term)
+                            .hint_text(hint)
                             .desired_width(f32::INFINITY)
                     );
                 });
@@ -199,11 +485,26 @@ impl<'a> TabViewer for SideTabViewer<'a> {
                 ui.add(egui::Separator::default().grow(5.0));
                
                 if self.project.loaded {
-                    if let Some(root_itm) = &self.project.root_item {
-                        draw_file_explorer_child(ui, &root_itm, self.project);
+                    if self.file_search.in_contents {
+                        if let Some(root) = &self.project.project_path {
+                            self.file_search.contents_ui(ui, root, self.search_term.trim(), self.editor);
+                        }
+                    } else if let Some(root_itm) = &self.project.root_item {
+                        draw_file_explorer_child(ui, &root_itm, self.project, self.editor, self.search_term.trim());
                     }      
                 }
             }
+            SidePanelTab::Sprites => self.sprite_browser.ui(ui),
+            SidePanelTab::Debug => {
+                if let Some(location) = self.debug_view.ui(ui) {
+                    *self.goto_source = Some(location);
+                }
+            }
+            SidePanelTab::Outline => {
+                if let Some(location) = self.editor.outline_ui(ui) {
+                    *self.goto_source = Some(location);
+                }
+            }
         }
     }
 
@@ -0,0 +1,396 @@
+use std::borrow::Cow;
+
+use egui::{pos2, vec2, Color32, ColorImage, Context, Painter, Pos2, Rect, Response, Sense, Stroke, TextureHandle, TextureOptions, Ui, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{helpers, python::{self, FRAME_IMAGE}, redengine::ProjectSettings};
+
+
+/// Zoom levels the mouse wheel snaps between, in screen points per game pixel.
+const ZOOM_STEPS: [f32; 13] = [0.125, 0.25, 0.5, 1.0, 2.0, 3.0, 4.0, 6.0, 8.0, 12.0, 16.0, 24.0, 32.0];
+/// The pixel grid is only drawn once a game pixel is at least this many points wide.
+const GRID_MIN_SCALE: f32 = 8.0;
+/// Size of one checkerboard square behind transparent pixels, in points.
+const CHECKER_SIZE: f32 = 8.0;
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Channel {
+    #[default]
+    All,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl Channel {
+    pub const ALL: [Self; 5] = [Self::All, Self::Red, Self::Green, Self::Blue, Self::Alpha];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::All => "RGBA",
+            Self::Red => "R",
+            Self::Green => "G",
+            Self::Blue => "B",
+            Self::Alpha => "A",
+        }
+    }
+}
+
+
+/// How the game image is scaled to the viewport when it is not zoomed.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScalingMode {
+    #[default]
+    Fit,
+    IntegerScale,
+    Stretch,
+    Fixed1x,
+}
+
+impl ScalingMode {
+    pub const ALL: [Self; 4] = [Self::Fit, Self::IntegerScale, Self::Stretch, Self::Fixed1x];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Fit => "Fit",
+            Self::IntegerScale => "Integer scale",
+            Self::Stretch => "Stretch",
+            Self::Fixed1x => "Fixed 1x",
+        }
+    }
+
+    /// Size of the game image in screen points when shown in `available`.
+    pub fn image_size(self, original: [usize; 2], available: Vec2) -> Vec2 {
+        let original_size = vec2(original[0] as f32, original[1] as f32);
+        match self {
+            Self::Fit => helpers::fit_aspect(original, available),
+            Self::IntegerScale => original_size * helpers::integer_scale(original, available),
+            Self::Stretch => available,
+            Self::Fixed1x => original_size,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextureFilter {
+    #[default]
+    Nearest,
+    Linear,
+}
+
+impl TextureFilter {
+    pub fn texture_options(self) -> TextureOptions {
+        match self {
+            Self::Nearest => TextureOptions::NEAREST,
+            Self::Linear => TextureOptions::LINEAR,
+        }
+    }
+}
+
+
+/// Maps between game pixels and screen points for the image drawn in the viewport.
+#[derive(Clone, Copy)]
+pub struct ViewportTransform {
+    pub rect: Rect,
+    pub size: [usize; 2],
+}
+
+impl ViewportTransform {
+    /// Screen points per game pixel on each axis; they differ only when stretching.
+    pub fn scale(&self) -> Vec2 {
+        self.rect.size() / vec2(self.size[0] as f32, self.size[1] as f32)
+    }
+
+    pub fn to_screen(&self, game: Pos2) -> Pos2 {
+        self.rect.min + game.to_vec2() * self.scale()
+    }
+
+    pub fn to_game(&self, screen: Pos2) -> Pos2 {
+        ((screen - self.rect.min) / self.scale()).to_pos2()
+    }
+
+    /// The game pixel under `screen`, if it lies on the image.
+    pub fn pixel_at(&self, screen: Pos2) -> Option<[usize; 2]> {
+        if !self.rect.contains(screen) {
+            return None;
+        }
+        let game = self.to_game(screen);
+        let [w, h] = self.size;
+        Some([(game.x as usize).min(w - 1), (game.y as usize).min(h - 1)])
+    }
+}
+
+
+/// Zoom, pan and inspection settings for the viewport image.
+pub struct ViewportView {
+    /// `None` fits the image to the panel, otherwise screen points per game pixel.
+    pub zoom: Option<f32>,
+    pub pan: Vec2,
+    pub channel: Channel,
+    pub show_grid: bool,
+    pub show_debug_shapes: bool,
+    checker: Option<TextureHandle>,
+    /// Frame serial, channel and filter the viewport texture was last uploaded with.
+    uploaded: Option<(usize, Channel, TextureFilter)>,
+}
+
+impl Default for ViewportView {
+    fn default() -> Self {
+        Self {
+            zoom: None,
+            pan: Vec2::ZERO,
+            channel: Channel::All,
+            show_grid: true,
+            show_debug_shapes: true,
+            checker: None,
+            uploaded: None,
+        }
+    }
+}
+
+impl ViewportView {
+    fn zoom_step(current: f32, zoom_in: bool) -> f32 {
+        if zoom_in {
+            ZOOM_STEPS.iter().copied().find(|&z| z > current + f32::EPSILON).unwrap_or(ZOOM_STEPS[ZOOM_STEPS.len() - 1])
+        } else {
+            ZOOM_STEPS.iter().rev().copied().find(|&z| z < current - f32::EPSILON).unwrap_or(ZOOM_STEPS[0])
+        }
+    }
+
+    /// Whether the viewport texture has to be uploaded again to show frame `serial` with `filter`.
+    pub fn needs_upload(&mut self, serial: usize, filter: TextureFilter) -> bool {
+        let key = Some((serial, self.channel, filter));
+        let changed = self.uploaded != key;
+        self.uploaded = key;
+        changed
+    }
+
+    /// Returns `image` with only the selected channel kept, shown as greyscale.
+    pub fn isolate<'a>(&self, image: &'a ColorImage) -> Cow<'a, ColorImage> {
+        if self.channel == Channel::All {
+            return Cow::Borrowed(image);
+        }
+
+        let mut isolated = image.clone();
+        for pixel in &mut isolated.pixels {
+            let [r, g, b, a] = pixel.to_srgba_unmultiplied();
+            *pixel = Color32::from_gray(match self.channel {
+                Channel::Red => r,
+                Channel::Green => g,
+                Channel::Blue => b,
+                Channel::Alpha | Channel::All => a,
+            });
+        }
+        Cow::Owned(isolated)
+    }
+
+    /// Zoom, grid and channel controls, plus the project's display settings.
+    /// Returns `true` when `settings` changed and should be saved.
+    pub fn controls(&mut self, ui: &mut Ui, settings: &mut ProjectSettings) -> bool {
+        let mut settings_changed = false;
+        ui.menu_button(egui_phosphor::regular::MONITOR, |ui| {
+            ui.label("Scaling");
+            for mode in ScalingMode::ALL {
+                settings_changed |= ui.radio_value(&mut settings.scaling_mode, mode, mode.label()).changed();
+            }
+            ui.separator();
+            ui.label("Filtering");
+            settings_changed |= ui.radio_value(&mut settings.texture_filter, TextureFilter::Nearest, "Nearest").changed();
+            settings_changed |= ui.radio_value(&mut settings.texture_filter, TextureFilter::Linear, "Linear").changed();
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Letterbox");
+                settings_changed |= ui.color_edit_button_srgb(&mut settings.letterbox_color).changed();
+            });
+        }).response.on_hover_text("Display settings for this project");
+
+        if ui.selectable_label(self.zoom.is_none(), settings.scaling_mode.label()).clicked() {
+            self.zoom = None;
+        }
+        if ui.selectable_label(self.zoom == Some(1.0), "1:1").clicked() {
+            self.zoom = Some(1.0);
+            self.pan = Vec2::ZERO;
+        }
+        if let Some(zoom) = &mut self.zoom {
+            if ui.button(egui_phosphor::regular::MAGNIFYING_GLASS_MINUS).clicked() {
+                *zoom = Self::zoom_step(*zoom, false);
+            }
+            ui.label(format!("{}%", (*zoom * 100.0).round()));
+            if ui.button(egui_phosphor::regular::MAGNIFYING_GLASS_PLUS).clicked() {
+                *zoom = Self::zoom_step(*zoom, true);
+            }
+        }
+
+        ui.separator();
+        ui.toggle_value(&mut self.show_grid, egui_phosphor::regular::GRID_FOUR)
+            .on_hover_text("Pixel grid at high zoom");
+        ui.toggle_value(&mut self.show_debug_shapes, egui_phosphor::regular::BOUNDING_BOX)
+            .on_hover_text("Shapes drawn with redengine.debug");
+
+        for channel in Channel::ALL {
+            ui.selectable_value(&mut self.channel, channel, channel.label());
+        }
+
+        settings_changed
+    }
+
+    fn paint_checker(&mut self, ctx: &Context, painter: &Painter, rect: Rect) {
+        let checker = self.checker.get_or_insert_with(|| {
+            let (light, dark) = (Color32::from_gray(110), Color32::from_gray(80));
+            let image = ColorImage::from_rgba_unmultiplied(
+                [2, 2],
+                &[light, dark, dark, light].map(|c| c.to_array()).concat(),
+            );
+            ctx.load_texture("viewport_checker", image, TextureOptions::NEAREST_REPEAT)
+        });
+
+        let uv = Rect::from_min_max(pos2(0.0, 0.0), (rect.size() / (CHECKER_SIZE * 2.0)).to_pos2());
+        painter.image(checker.id(), rect, uv, Color32::WHITE);
+    }
+
+    fn paint_grid(painter: &Painter, transform: &ViewportTransform) {
+        let clip = painter.clip_rect().intersect(transform.rect);
+        let stroke = Stroke::new(1.0, Color32::from_black_alpha(90));
+        let min = transform.to_game(clip.min);
+        let max = transform.to_game(clip.max);
+
+        for x in (min.x.floor() as usize)..=(max.x.ceil() as usize) {
+            let sx = transform.to_screen(pos2(x as f32, 0.0)).x;
+            painter.vline(sx, clip.y_range(), stroke);
+        }
+        for y in (min.y.floor() as usize)..=(max.y.ceil() as usize) {
+            let sy = transform.to_screen(pos2(0.0, y as f32)).y;
+            painter.hline(clip.x_range(), sy, stroke);
+        }
+    }
+
+    /// Draws the game image, handling wheel zoom around the cursor and drag panning.
+    /// Leaves `reserved_height` points below for whatever is drawn under the image.
+    pub fn show(&mut self, ui: &mut Ui, texture: &TextureHandle, size: [usize; 2], settings: &ProjectSettings, reserved_height: f32) -> (Response, ViewportTransform) {
+        let (area, response) = ui.allocate_exact_size(ui.available_size() - vec2(0.0, reserved_height), Sense::click_and_drag());
+
+        let unzoomed_size = settings.scaling_mode.image_size(size, area.size());
+        let scale = self.zoom.unwrap_or(unzoomed_size.x / size[0] as f32);
+
+        if let Some(hover) = response.hover_pos() {
+            let scroll = ui.input(|i| i.raw_scroll_delta.y);
+            if scroll != 0.0 {
+                let zoom = Self::zoom_step(scale, scroll > 0.0);
+                // Keep the game pixel under the cursor in place.
+                let anchor = hover - area.center();
+                self.pan = anchor - (anchor - self.pan) * (zoom / scale);
+                self.zoom = Some(zoom);
+            }
+        }
+
+        match self.zoom {
+            Some(_) if response.dragged() => self.pan += response.drag_delta(),
+            Some(_) => {}
+            None => self.pan = Vec2::ZERO,
+        }
+
+        let image_size = match self.zoom {
+            Some(zoom) => vec2(size[0] as f32, size[1] as f32) * zoom,
+            None => unzoomed_size,
+        };
+        let rect = Rect::from_center_size(area.center() + self.pan, image_size);
+        let transform = ViewportTransform { rect, size };
+        let painter = ui.painter_at(area);
+
+        let [r, g, b] = settings.letterbox_color;
+        painter.rect_filled(area, 0.0, Color32::from_rgb(r, g, b));
+
+        if self.channel == Channel::All {
+            self.paint_checker(ui.ctx(), &painter, rect);
+        }
+        painter.image(texture.id(), rect, Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)), Color32::WHITE);
+
+        if self.show_grid && transform.scale().min_elem() >= GRID_MIN_SCALE {
+            Self::paint_grid(&painter, &transform);
+        }
+
+        (response, transform)
+    }
+}
+
+
+/// Shows the game-space coordinate and RGBA value of the pixel under the cursor.
+pub fn pixel_readout(ui: &mut Ui, transform: &ViewportTransform, hover: Option<Pos2>) {
+    let Some([x, y]) = hover.and_then(|pos| transform.pixel_at(pos)) else {
+        return;
+    };
+    ui.label(format!("{x}, {y}"));
+
+    let pixel = FRAME_IMAGE.get().and_then(|lock| {
+        let frame = lock.lock();
+        let image = frame.as_ref()?;
+        (image.size == transform.size).then(|| image.pixels[y * image.size[0] + x])
+    });
+
+    if let Some(pixel) = pixel {
+        let [r, g, b, a] = pixel.to_srgba_unmultiplied();
+        let (swatch, _) = ui.allocate_exact_size(vec2(12.0, 12.0), Sense::hover());
+        ui.painter().rect_filled(swatch, 2.0, Color32::from_rgb(r, g, b));
+        ui.monospace(format!("RGBA({r}, {g}, {b}, {a})"));
+    }
+}
+
+
+/// pygame's name for `key`, as accepted by `pygame.key.key_code`.
+fn pygame_key_name(key: egui::Key) -> String {
+    match key {
+        egui::Key::ArrowUp => "up",
+        egui::Key::ArrowDown => "down",
+        egui::Key::ArrowLeft => "left",
+        egui::Key::ArrowRight => "right",
+        egui::Key::Enter => "return",
+        egui::Key::Escape => "escape",
+        egui::Key::Space => "space",
+        egui::Key::Tab => "tab",
+        egui::Key::Backspace => "backspace",
+        egui::Key::Delete => "delete",
+        _ => return key.name().to_lowercase(),
+    }
+    .to_owned()
+}
+
+/// Forwards key presses to the game while the viewport image has keyboard focus.
+/// Clicking the image gives it focus.
+pub fn forward_input(ui: &Ui, response: &Response) {
+    if response.clicked() {
+        response.request_focus();
+    }
+    if !response.has_focus() {
+        return;
+    }
+
+    // Keep arrows, Tab and Escape from moving focus away from the game.
+    ui.memory_mut(|m| {
+        m.set_focus_lock_filter(response.id, egui::EventFilter {
+            tab: true,
+            horizontal_arrows: true,
+            vertical_arrows: true,
+            escape: true,
+        });
+    });
+
+    let keys: Vec<(String, bool)> = ui.input(|i| {
+        i.events
+            .iter()
+            .filter_map(|event| match event {
+                egui::Event::Key { key, pressed, repeat: false, .. } => Some((pygame_key_name(*key), *pressed)),
+                _ => None,
+            })
+            .collect()
+    });
+
+    for (key, pressed) in keys {
+        python::queue_python_instruction(move |py| {
+            if let Err(e) = python::post_key_event(py, &key, pressed) {
+                e.print(py);
+            }
+        });
+    }
+}
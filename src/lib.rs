@@ -3,4 +3,6 @@
 mod app;
 pub use app::TemplateApp;
 
+pub mod cli;
+
 mod engine;
\ No newline at end of file
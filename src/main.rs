@@ -7,6 +7,14 @@
 
 fn main() -> eframe::Result {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+
+    // Subcommands such as `run` work without a display, so they never open the editor.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = redengine_rust::cli::dispatch(&args) {
+        #[expect(clippy::exit, reason = "headless runs report failure through the exit code")]
+        std::process::exit(code);
+    }
+
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1080.0, 608.0])